@@ -0,0 +1,40 @@
+use thiserror::Error;
+
+/// Errors when parsing a data report.
+#[derive(Error, Debug, PartialEq)]
+pub enum DataReportError {
+    /// A tagged line does not have the expected layout
+    #[error("Malformed data report line {line}: {message}")]
+    MalformedLine {
+        /// Line number in the stream, starting at 1
+        line: usize,
+        /// What is wrong
+        message: String,
+    },
+
+    /// A value of a tag is not a number
+    #[error("Invalid value '{value}' of {tag} in data report line {line}")]
+    InvalidValue {
+        /// Line number in the stream, starting at 1
+        line: usize,
+        /// The tag the value belongs to
+        tag: String,
+        /// The value
+        value: String,
+    },
+
+    /// The number of values does not match the number of points returned
+    #[error("Data report line {line} has {found} {tag} values, expected {expected}")]
+    CountMismatch {
+        /// Line number in the stream, starting at 1
+        line: usize,
+        /// The tag of the values
+        tag: String,
+        /// The number of points returned
+        expected: usize,
+        /// The number of values found
+        found: usize,
+    },
+}
+
+pub type Result<T> = std::result::Result<T, DataReportError>;
@@ -0,0 +1,17 @@
+//! Parser of the data report printed by generated scripts.
+//!
+//! The `_DataReport()` function of a generated script prints the reading buffers as tagged
+//! lines. [`ReportParser`] turns that output, fed in arbitrary pieces, into a [`Report`] with
+//! one [`BufferRecord`] per reading buffer, which can be exported as CSV or JSON.
+
+#![cfg_attr(coverage_nightly, feature(coverage_attribute))]
+
+pub mod error;
+pub mod parser;
+pub mod report;
+pub mod tags;
+
+pub use error::DataReportError;
+pub use parser::{ReportEvent, ReportParser};
+pub use report::{BufferChunk, BufferRecord, Report};
+pub use tags::Tags;
@@ -0,0 +1,440 @@
+use std::collections::HashMap;
+
+use crate::error::{DataReportError, Result};
+use crate::report::{BufferChunk, Report};
+use crate::tags::Tags;
+
+/// Something the parser recognized in the stream.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReportEvent {
+    /// A data report started, the previous report is discarded.
+    Started,
+    /// The sweep started.
+    SweepStarted,
+    /// Points of a reading buffer were received.
+    Points(BufferChunk),
+    /// A line that is not part of the report was printed while it was running.
+    Message(String),
+    /// All data was sent.
+    Completed(Report),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    /// Waiting for the start of a report, other output is skipped.
+    Idle,
+    Reporting,
+}
+
+/// Incremental parser of the data report printed by a generated script.
+///
+/// Output of the instrument is passed to [`ReportParser::feed`] in pieces of any size, the
+/// parser keeps incomplete lines until their line break arrives.
+///
+/// ```
+/// use data_report::{ReportEvent, ReportParser};
+///
+/// let mut parser = ReportParser::new();
+/// parser.feed("(({{Data}}))\n{SWEEP-START}\n{NAME}smua_nvbuffer1");
+/// parser.feed("{PTS-IN-BUFF}2{PTS-RETURNED}2{START}{READINGS}1.0e-3,2.0e-3{END}\n");
+/// let events = parser.feed("{COMPLETE}\n(({{}}))\n");
+///
+/// assert!(matches!(events[0], Ok(ReportEvent::Completed(_))));
+/// assert_eq!(parser.report().buffers[0].readings, vec![1.0e-3, 2.0e-3]);
+/// ```
+#[derive(Debug)]
+pub struct ReportParser {
+    tags: Tags,
+    start_line: String,
+    pending: String,
+    line: usize,
+    state: State,
+    report: Report,
+}
+
+impl Default for ReportParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ReportParser {
+    /// Creates a parser for the tags of the script generator.
+    pub fn new() -> Self {
+        Self::with_tags(Tags::default())
+    }
+
+    /// Creates a parser for a report printed with `tags`.
+    pub fn with_tags(tags: Tags) -> Self {
+        ReportParser {
+            start_line: format!("(({{{{{}}}}}))", tags.data_report),
+            tags,
+            pending: String::new(),
+            line: 0,
+            state: State::Idle,
+            report: Report::default(),
+        }
+    }
+
+    /// Returns the report received so far, or the last completed one.
+    pub fn report(&self) -> &Report {
+        &self.report
+    }
+
+    /// Consumes the parser and returns the report received so far.
+    pub fn into_report(self) -> Report {
+        self.report
+    }
+
+    /// Parses the complete lines of `text`, together with the incomplete line left over from
+    /// the previous call, and returns what was recognized, in stream order.
+    ///
+    /// A malformed line yields an error in place of its event and is skipped, the following
+    /// lines are parsed as usual.
+    pub fn feed(&mut self, text: &str) -> Vec<Result<ReportEvent>> {
+        self.pending.push_str(text);
+        let mut events = Vec::new();
+        while let Some(end) = self.pending.find('\n') {
+            let line: String = self.pending.drain(..=end).collect();
+            events.extend(self.parse_line(&line).transpose());
+        }
+        events
+    }
+
+    /// Parses the text left over at the end of the stream, like [`ReportParser::feed`]. The
+    /// text is split at carriage returns as well, the last line break of a stream may be one.
+    pub fn finish(&mut self) -> Vec<Result<ReportEvent>> {
+        let pending = std::mem::take(&mut self.pending);
+        pending
+            .split(['\r', '\n'])
+            .filter_map(|line| self.parse_line(line).transpose())
+            .collect()
+    }
+
+    fn parse_line(&mut self, line: &str) -> Result<Option<ReportEvent>> {
+        self.line += 1;
+        let line = line.trim();
+        if line == self.start_line {
+            self.state = State::Reporting;
+            self.report = Report::default();
+            return Ok(Some(ReportEvent::Started));
+        }
+        if self.state == State::Idle || line.is_empty() || line == "(({{}}))" {
+            return Ok(None);
+        }
+
+        if line == tag(&self.tags.sweep_start) {
+            self.report.sweep_started = true;
+            Ok(Some(ReportEvent::SweepStarted))
+        } else if line == tag(&self.tags.complete) {
+            self.report.complete = true;
+            self.state = State::Idle;
+            Ok(Some(ReportEvent::Completed(self.report.clone())))
+        } else if line.starts_with(&tag(&self.tags.name)) {
+            let chunk = self.parse_points(line)?;
+            self.report.add(chunk.clone());
+            Ok(Some(ReportEvent::Points(chunk)))
+        } else {
+            self.report.messages.push(line.to_string());
+            Ok(Some(ReportEvent::Message(line.to_string())))
+        }
+    }
+
+    /// Parses `{NAME}<name>{PTS-IN-BUFF}<n>{PTS-RETURNED}<n>{START}`, optionally followed by
+    /// `{EXPECTED-COUNT}<n>{BASE-TIME-STAMP}<t>`, then `{READINGS}<values>`, optional
+    /// `{TIMESTAMPS}<values>` and `{SRCVALS}<values>`, and `{END}`.
+    fn parse_points(&self, line: &str) -> Result<BufferChunk> {
+        let fields = self.split_fields(line)?;
+        let get = |name: &String| fields.get(name.as_str()).copied();
+        let require = |name: &String| {
+            get(name).ok_or_else(|| self.malformed(&format!("missing {}", tag(name))))
+        };
+        require(&self.tags.start)?;
+        if get(&self.tags.end) != Some("") {
+            return Err(self.malformed(&format!("missing {} at the end", tag(&self.tags.end))));
+        }
+
+        let points_returned =
+            self.count(&self.tags.pts_returned, require(&self.tags.pts_returned)?)?;
+        let readings = self.values(
+            &self.tags.readings,
+            require(&self.tags.readings)?,
+            points_returned,
+        )?;
+        let optional_values = |name: &String| {
+            get(name)
+                .map(|value| self.values(name, value, points_returned))
+                .transpose()
+        };
+
+        Ok(BufferChunk {
+            name: require(&self.tags.name)?.to_string(),
+            points_in_buffer: self
+                .count(&self.tags.pts_in_buff, require(&self.tags.pts_in_buff)?)?,
+            expected_count: get(&self.tags.expected_count)
+                .map(|value| self.count(&self.tags.expected_count, value))
+                .transpose()?,
+            base_timestamp: get(&self.tags.base_time_stamp)
+                .map(|value| self.number(&self.tags.base_time_stamp, value))
+                .transpose()?,
+            readings,
+            timestamps: optional_values(&self.tags.timestamps)?,
+            source_values: optional_values(&self.tags.srcvals)?,
+        })
+    }
+
+    /// Splits `line` into the values of its tags, keyed by tag name.
+    fn split_fields<'a, 'b>(&'a self, line: &'b str) -> Result<HashMap<&'a str, &'b str>> {
+        let tags = [
+            &self.tags.name,
+            &self.tags.pts_in_buff,
+            &self.tags.pts_returned,
+            &self.tags.start,
+            &self.tags.expected_count,
+            &self.tags.base_time_stamp,
+            &self.tags.readings,
+            &self.tags.timestamps,
+            &self.tags.srcvals,
+            &self.tags.end,
+        ]
+        .map(|name| (name.as_str(), tag(name)));
+
+        // positions of all tags in the line, in order
+        let mut markers: Vec<(usize, &str, usize)> = tags
+            .iter()
+            .flat_map(|(name, marker)| {
+                line.match_indices(marker.as_str())
+                    .map(move |(position, _)| (position, *name, marker.len()))
+            })
+            .collect();
+        markers.sort_unstable();
+
+        let mut fields = HashMap::new();
+        for (i, &(position, name, len)) in markers.iter().enumerate() {
+            if i == 0 && position != 0 {
+                return Err(self.malformed("unexpected text before the first tag"));
+            }
+            let end = markers.get(i + 1).map_or(line.len(), |next| next.0);
+            let value = line.get(position + len..end).unwrap_or_default();
+            if fields.insert(name, value).is_some() {
+                return Err(self.malformed(&format!("{} appears more than once", tag(name))));
+            }
+        }
+        Ok(fields)
+    }
+
+    fn count(&self, name: &str, value: &str) -> Result<usize> {
+        value
+            .trim()
+            .parse()
+            .map_err(|_| self.invalid_value(name, value))
+    }
+
+    fn number(&self, name: &str, value: &str) -> Result<f64> {
+        parse_number(value).ok_or_else(|| self.invalid_value(name, value))
+    }
+
+    fn values(&self, name: &str, value: &str, expected: usize) -> Result<Vec<f64>> {
+        let values = if value.trim().is_empty() {
+            Vec::new()
+        } else {
+            value
+                .split(',')
+                .map(|item| self.number(name, item))
+                .collect::<Result<Vec<f64>>>()?
+        };
+        if values.len() != expected {
+            return Err(DataReportError::CountMismatch {
+                line: self.line,
+                tag: name.to_string(),
+                expected,
+                found: values.len(),
+            });
+        }
+        Ok(values)
+    }
+
+    fn malformed(&self, message: &str) -> DataReportError {
+        DataReportError::MalformedLine {
+            line: self.line,
+            message: message.to_string(),
+        }
+    }
+
+    fn invalid_value(&self, name: &str, value: &str) -> DataReportError {
+        DataReportError::InvalidValue {
+            line: self.line,
+            tag: name.to_string(),
+            value: value.trim().to_string(),
+        }
+    }
+}
+
+fn tag(name: &str) -> String {
+    format!("{{{name}}}")
+}
+
+/// Parses a number printed by Lua's `%e` or `%f`, including the `nan` and `inf` spellings
+/// of the C library.
+fn parse_number(value: &str) -> Option<f64> {
+    let value = value.trim();
+    match value.to_ascii_lowercase().as_str() {
+        "nan" | "-nan" | "+nan" => Some(f64::NAN),
+        _ => value.parse().ok(),
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod tests {
+    use super::*;
+
+    /// Returns the events of `results`, panicking on an error.
+    fn events(results: Vec<Result<ReportEvent>>) -> Vec<ReportEvent> {
+        results.into_iter().map(Result::unwrap).collect()
+    }
+
+    const STREAM: &str = "\
+loading script
+(({{Data}}))
+{SWEEP-START}
+{NAME}smua_nvbuffer1{PTS-IN-BUFF}3{PTS-RETURNED}2{START}{EXPECTED-COUNT}4{BASE-TIME-STAMP}12.500000{READINGS}1.000000e-03,2.000000e-03{TIMESTAMPS}0.000000e+00,1.000000e-02{SRCVALS}1.000000e+00,2.000000e+00{END}
+{NAME}smub_nvbuffer1{PTS-IN-BUFF}4{PTS-RETURNED}4{START}{EXPECTED-COUNT}4{BASE-TIME-STAMP}12.600000{READINGS}5.0e-01,nan,-nan,9.91e+37{END}
+Compliance reached on smua
+{NAME}smua_nvbuffer1{PTS-IN-BUFF}4{PTS-RETURNED}2{START}{READINGS}3.000000e-03,4.000000e-03{TIMESTAMPS}2.000000e-02,3.000000e-02{SRCVALS}3.000000e+00,4.000000e+00{END}
+{COMPLETE}
+(({{}}))
+>";
+
+    #[test]
+    fn test_parse_stream_in_pieces() {
+        let mut parser = ReportParser::new();
+        let mut events = Vec::new();
+        // split inside tags, values and line breaks
+        for piece in STREAM.as_bytes().chunks(7) {
+            events.extend(self::events(
+                parser.feed(std::str::from_utf8(piece).unwrap()),
+            ));
+        }
+        events.extend(self::events(parser.finish()));
+
+        assert_eq!(events[0], ReportEvent::Started);
+        assert_eq!(events[1], ReportEvent::SweepStarted);
+        assert_eq!(
+            events[4],
+            ReportEvent::Message("Compliance reached on smua".to_string())
+        );
+        assert!(matches!(events.last(), Some(ReportEvent::Completed(_))));
+
+        let report = parser.report();
+        assert!(report.sweep_started && report.complete);
+        assert_eq!(report.buffers.len(), 2);
+
+        let smua = report.buffer("smua_nvbuffer1").unwrap();
+        assert_eq!(smua.expected_count, Some(4));
+        assert_eq!(smua.base_timestamp, Some(12.5));
+        assert_eq!(smua.readings, vec![1e-3, 2e-3, 3e-3, 4e-3]);
+        assert_eq!(smua.timestamps, vec![0.0, 1e-2, 2e-2, 3e-2]);
+        assert_eq!(smua.source_values, vec![1.0, 2.0, 3.0, 4.0]);
+        assert!(smua.is_complete());
+
+        let smub = report.buffer("smub_nvbuffer1").unwrap();
+        assert!(smub.readings[1].is_nan() && smub.readings[2].is_nan());
+        assert!(smub.timestamps.is_empty());
+    }
+
+    #[test]
+    fn test_crlf_line_breaks() {
+        let mut parser = ReportParser::new();
+        events(parser.feed(&STREAM.replace('\n', "\r\n")));
+        assert_eq!(parser.report().buffers.len(), 2);
+        assert!(parser.report().complete);
+    }
+
+    #[test]
+    fn test_output_outside_a_report_is_skipped() {
+        let mut parser = ReportParser::new();
+        let events = parser.feed("{NAME}x{PTS-IN-BUFF}1{PTS-RETURNED}1{START}{READINGS}1{END}\n");
+        assert!(events.is_empty());
+    }
+
+    #[test]
+    fn test_malformed_lines() {
+        let mut parser = ReportParser::new();
+        events(parser.feed("(({{Data}}))\n"));
+
+        assert_eq!(
+            parser.feed("{NAME}a{PTS-IN-BUFF}1{PTS-RETURNED}2{START}{READINGS}1.0{END}\n"),
+            vec![Err(DataReportError::CountMismatch {
+                line: 2,
+                tag: "READINGS".to_string(),
+                expected: 2,
+                found: 1,
+            })]
+        );
+        assert_eq!(
+            parser.feed("{NAME}a{PTS-IN-BUFF}1{PTS-RETURNED}1{START}{READINGS}x{END}\n"),
+            vec![Err(DataReportError::InvalidValue {
+                line: 3,
+                tag: "READINGS".to_string(),
+                value: "x".to_string(),
+            })]
+        );
+        assert!(matches!(
+            parser
+                .feed("{NAME}a{PTS-IN-BUFF}1{PTS-RETURNED}1{START}{READINGS}1.0\n")
+                .as_slice(),
+            [Err(DataReportError::MalformedLine { line: 4, .. })]
+        ));
+        // the parser recovers after an error
+        events(parser.feed("{NAME}a{PTS-IN-BUFF}1{PTS-RETURNED}1{START}{READINGS}1.0{END}\n"));
+        assert_eq!(parser.report().buffers[0].readings, vec![1.0]);
+    }
+
+    #[test]
+    fn test_events_around_a_malformed_line_are_kept() {
+        let mut parser = ReportParser::new();
+        let results = parser.feed(
+            "(({{Data}}))\n\
+             {NAME}a{PTS-IN-BUFF}1{PTS-RETURNED}1{START}{READINGS}x{END}\n\
+             {SWEEP-START}\n",
+        );
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0], Ok(ReportEvent::Started));
+        assert!(matches!(
+            results[1],
+            Err(DataReportError::InvalidValue { line: 2, .. })
+        ));
+        assert_eq!(results[2], Ok(ReportEvent::SweepStarted));
+        assert!(parser.report().sweep_started);
+    }
+
+    #[test]
+    fn test_finish_parses_each_remaining_line() {
+        let mut parser = ReportParser::new();
+        events(parser.feed("(({{Data}}))\n{SWEEP-START}\r{COMPLETE}"));
+        let events = events(parser.finish());
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0], ReportEvent::SweepStarted);
+        assert!(matches!(events[1], ReportEvent::Completed(_)));
+        assert!(parser.finish().is_empty());
+    }
+
+    #[test]
+    fn test_export() {
+        let mut parser = ReportParser::new();
+        events(parser.feed(STREAM));
+        let report = parser.report();
+
+        let csv = report.to_csv();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], "buffer,index,reading,timestamp,source_value");
+        assert_eq!(lines[1], "smua_nvbuffer1,1,0.001,0,1");
+        assert_eq!(lines[5], "smub_nvbuffer1,1,0.5,,");
+        assert_eq!(lines.len(), 9);
+
+        let json: serde_json::Value = serde_json::from_str(&report.to_json().unwrap()).unwrap();
+        assert_eq!(json["buffers"][0]["name"], "smua_nvbuffer1");
+        assert_eq!(json["buffers"][1]["readings"][1], serde_json::Value::Null);
+    }
+}
@@ -0,0 +1,135 @@
+use std::fmt::Write as _;
+
+use serde::{Deserialize, Serialize};
+
+/// The points of one reading buffer returned by one transfer of the data report.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BufferChunk {
+    /// Name of the reading buffer.
+    pub name: String,
+    /// Number of points in the buffer when the transfer was made.
+    pub points_in_buffer: usize,
+    /// Number of points the buffer holds once the sweep is done, sent with the first transfer.
+    pub expected_count: Option<usize>,
+    /// Time stamp of the first reading, sent with the first transfer.
+    pub base_timestamp: Option<f64>,
+    pub readings: Vec<f64>,
+    /// Set if the buffer collects time stamps.
+    pub timestamps: Option<Vec<f64>>,
+    /// Set if the buffer collects source values.
+    pub source_values: Option<Vec<f64>>,
+}
+
+/// All points of one reading buffer received so far.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct BufferRecord {
+    /// Name of the reading buffer.
+    pub name: String,
+    /// Number of points the buffer holds once the sweep is done.
+    pub expected_count: Option<usize>,
+    /// Time stamp of the first reading.
+    pub base_timestamp: Option<f64>,
+    pub readings: Vec<f64>,
+    /// Time stamps relative to `base_timestamp`, empty if the buffer does not collect them.
+    pub timestamps: Vec<f64>,
+    /// Source values, empty if the buffer does not collect them.
+    pub source_values: Vec<f64>,
+}
+
+impl BufferRecord {
+    /// Returns whether all expected points were received.
+    pub fn is_complete(&self) -> bool {
+        self.expected_count
+            .is_some_and(|expected| self.readings.len() >= expected)
+    }
+
+    fn append(&mut self, chunk: BufferChunk) {
+        if chunk.expected_count.is_some() {
+            self.expected_count = chunk.expected_count;
+        }
+        if chunk.base_timestamp.is_some() {
+            self.base_timestamp = chunk.base_timestamp;
+        }
+        self.readings.extend(chunk.readings);
+        self.timestamps.extend(chunk.timestamps.unwrap_or_default());
+        self.source_values
+            .extend(chunk.source_values.unwrap_or_default());
+    }
+}
+
+/// The data report of one script run.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct Report {
+    /// Reading buffers in the order they were first reported.
+    pub buffers: Vec<BufferRecord>,
+    /// Set once the sweep started.
+    pub sweep_started: bool,
+    /// Set once the script reported that all data was sent.
+    pub complete: bool,
+    /// Lines printed during the report that are not part of it, such as warnings.
+    pub messages: Vec<String>,
+}
+
+impl Report {
+    /// Returns the record of the buffer `name`, if any points of it were received.
+    pub fn buffer(&self, name: &str) -> Option<&BufferRecord> {
+        self.buffers.iter().find(|buffer| buffer.name == name)
+    }
+
+    /// Appends the points of `chunk` to the record of its buffer.
+    pub fn add(&mut self, chunk: BufferChunk) {
+        let index = match self
+            .buffers
+            .iter()
+            .position(|buffer| buffer.name == chunk.name)
+        {
+            Some(index) => index,
+            None => {
+                self.buffers.push(BufferRecord {
+                    name: chunk.name.clone(),
+                    ..BufferRecord::default()
+                });
+                self.buffers.len() - 1
+            }
+        };
+        self.buffers[index].append(chunk);
+    }
+
+    /// Returns the points as CSV with the columns `buffer,index,reading,timestamp,source_value`,
+    /// one row per point. The index starts at 1, missing values are left empty.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("buffer,index,reading,timestamp,source_value\n");
+        for buffer in &self.buffers {
+            let name = csv_field(&buffer.name);
+            for (i, reading) in buffer.readings.iter().enumerate() {
+                let timestamp = optional(buffer.timestamps.get(i));
+                let source_value = optional(buffer.source_values.get(i));
+                let _ = writeln!(csv, "{name},{},{reading},{timestamp},{source_value}", i + 1);
+            }
+        }
+        csv
+    }
+
+    /// Returns the report as pretty printed JSON. Values that are not finite are written as
+    /// `null`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the report cannot be serialized.
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+}
+
+fn optional(value: Option<&f64>) -> String {
+    value.map(ToString::to_string).unwrap_or_default()
+}
+
+/// Quotes `value` if it contains a delimiter, quote or line break.
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
@@ -0,0 +1,62 @@
+/// Tags marking the parts of a data report.
+///
+/// The script prints the report start as `(({{<data_report>}}))` and every other tag as
+/// `{<tag>}`. The defaults are the values filled in by the script generator.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tags {
+    pub data_report: String,
+    pub sweep_start: String,
+    pub start: String,
+    pub name: String,
+    pub expected_count: String,
+    pub pts_in_buff: String,
+    pub pts_returned: String,
+    pub base_time_stamp: String,
+    pub readings: String,
+    pub timestamps: String,
+    pub srcvals: String,
+    pub end: String,
+    pub complete: String,
+}
+
+impl Default for Tags {
+    fn default() -> Self {
+        Tags {
+            data_report: "Data".to_string(),
+            sweep_start: "SWEEP-START".to_string(),
+            start: "START".to_string(),
+            name: "NAME".to_string(),
+            expected_count: "EXPECTED-COUNT".to_string(),
+            pts_in_buff: "PTS-IN-BUFF".to_string(),
+            pts_returned: "PTS-RETURNED".to_string(),
+            base_time_stamp: "BASE-TIME-STAMP".to_string(),
+            readings: "READINGS".to_string(),
+            timestamps: "TIMESTAMPS".to_string(),
+            srcvals: "SRCVALS".to_string(),
+            end: "END".to_string(),
+            complete: "COMPLETE".to_string(),
+        }
+    }
+}
+
+impl Tags {
+    /// Returns the template placeholders of the script generator and the tag each one is
+    /// replaced with.
+    pub fn replacements(&self) -> [(&'static str, &str); 13] {
+        [
+            ("TAG-DATA-REPORT", &self.data_report),
+            ("TAG-SWEEP-START", &self.sweep_start),
+            ("TAG-START", &self.start),
+            ("TAG-NAME", &self.name),
+            ("TAG-EXPECTED-COUNT", &self.expected_count),
+            ("TAG-PTS-IN-BUFF", &self.pts_in_buff),
+            ("TAG-PTS-RETURNED", &self.pts_returned),
+            ("TAG-BASE-TIME-STAMP", &self.base_time_stamp),
+            ("TAG-READINGS", &self.readings),
+            ("TAG-TIMESTAMPS", &self.timestamps),
+            ("TAG-SRCVALS", &self.srcvals),
+            ("TAG-END", &self.end),
+            ("TAG-COMPLETE", &self.complete),
+        ]
+    }
+}
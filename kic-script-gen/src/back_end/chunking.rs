@@ -0,0 +1,410 @@
+//! Splitting of large WebSocket messages into chunk frames and their reassembly.
+//!
+//! A message longer than [`MAX_FRAME_LEN`] is sent as frames of the form
+//! `{"type":"chunk","msg_id":...,"chunk_index":i,"total_chunks":n,"data":...}`. Concatenating
+//! the `data` of all frames of a `msg_id` in index order gives the original message. The UI
+//! uses the same framing in both directions.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use actix_ws::{Closed, Session};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::protocol::{ErrorCode, ProtocolError};
+
+/// Longest message sent as a single frame, in bytes.
+pub const MAX_FRAME_LEN: usize = 30 * 1024;
+/// Largest number of chunks accepted for one message.
+pub const MAX_TOTAL_CHUNKS: usize = 1 << 16;
+/// Largest reassembled message accepted, in bytes.
+pub const MAX_MESSAGE_LEN: usize = 64 * 1024 * 1024;
+/// Largest number of bytes buffered for incomplete messages of one connection.
+pub const MAX_PENDING_LEN: usize = 128 * 1024 * 1024;
+/// Largest number of incomplete messages buffered for one connection.
+pub const MAX_PENDING_MESSAGES: usize = 8;
+/// Time after the last received chunk after which an incomplete message is discarded.
+pub const CHUNK_TIMEOUT: Duration = Duration::from_secs(30);
+
+static NEXT_MSG_ID: AtomicU64 = AtomicU64::new(0);
+
+/// One frame of a chunked message.
+#[derive(Deserialize, Debug, Clone)]
+pub struct Chunk {
+    pub msg_id: String,
+    pub chunk_index: usize,
+    pub total_chunks: usize,
+    pub data: String,
+}
+
+/// Outgoing frame, `type` comes first so that receivers can recognize chunks cheaply.
+#[derive(Serialize)]
+struct ChunkFrame<'a> {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    msg_id: &'a str,
+    chunk_index: usize,
+    total_chunks: usize,
+    data: &'a str,
+}
+
+impl Chunk {
+    /// Returns the chunk carried by `msg`, `None` if `msg` is not a chunk frame, or an error
+    /// if it is a chunk frame with invalid metadata.
+    ///
+    /// Only objects with `"type":"chunk"` are chunk frames, other messages may have a
+    /// `msg_id` of their own.
+    pub fn parse(msg: &str) -> Option<Result<Chunk, ProtocolError>> {
+        let Ok(Value::Object(map)) = serde_json::from_str::<Value>(msg) else {
+            return None;
+        };
+        if map.get("type").and_then(Value::as_str) != Some("chunk") {
+            return None;
+        }
+        Some(serde_json::from_value(Value::Object(map)).map_err(|e| {
+            ProtocolError::new(ErrorCode::MalformedMessage, format!("invalid chunk: {e}"))
+        }))
+    }
+}
+
+struct PartialMessage {
+    chunks: Vec<Option<String>>,
+    received: usize,
+    len: usize,
+    last_update: Instant,
+}
+
+/// Reassembles chunked messages received on one connection.
+#[derive(Default)]
+pub struct ChunkAssembler {
+    pending: HashMap<String, PartialMessage>,
+    pending_len: usize,
+}
+
+impl ChunkAssembler {
+    /// Adds a chunk and returns the complete message once all its chunks were received.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error, and discards the partial message, if the chunk metadata is invalid
+    /// or inconsistent with earlier chunks, or if a size limit would be exceeded.
+    pub fn push(&mut self, chunk: Chunk) -> Result<Option<String>, ProtocolError> {
+        self.push_at(chunk, Instant::now())
+    }
+
+    /// Adds a chunk received at `now`, see [`ChunkAssembler::push`].
+    fn push_at(&mut self, chunk: Chunk, now: Instant) -> Result<Option<String>, ProtocolError> {
+        self.evict_expired(now);
+
+        if chunk.total_chunks == 0 || chunk.total_chunks > MAX_TOTAL_CHUNKS {
+            self.discard(&chunk.msg_id);
+            return Err(invalid(format!(
+                "total_chunks {} of message '{}' must be between 1 and {MAX_TOTAL_CHUNKS}",
+                chunk.total_chunks, chunk.msg_id
+            )));
+        }
+        if chunk.chunk_index >= chunk.total_chunks {
+            self.discard(&chunk.msg_id);
+            return Err(invalid(format!(
+                "chunk_index {} of message '{}' is out of range for {} chunks",
+                chunk.chunk_index, chunk.msg_id, chunk.total_chunks
+            )));
+        }
+        if !self.pending.contains_key(&chunk.msg_id) && self.pending.len() >= MAX_PENDING_MESSAGES {
+            return Err(invalid(format!(
+                "too many incomplete messages, message '{}' is rejected",
+                chunk.msg_id
+            )));
+        }
+
+        let partial = self
+            .pending
+            .entry(chunk.msg_id.clone())
+            .or_insert_with(|| PartialMessage {
+                chunks: vec![None; chunk.total_chunks],
+                received: 0,
+                len: 0,
+                last_update: now,
+            });
+        if partial.chunks.len() != chunk.total_chunks {
+            let expected = partial.chunks.len();
+            self.discard(&chunk.msg_id);
+            return Err(invalid(format!(
+                "total_chunks of message '{}' changed from {expected} to {}",
+                chunk.msg_id, chunk.total_chunks
+            )));
+        }
+
+        // a repeated chunk replaces the earlier copy
+        let replaced = partial.chunks[chunk.chunk_index]
+            .as_ref()
+            .map_or(0, String::len);
+        let len = partial.len - replaced + chunk.data.len();
+        let pending_len = self.pending_len - replaced + chunk.data.len();
+        if len > MAX_MESSAGE_LEN || pending_len > MAX_PENDING_LEN {
+            self.discard(&chunk.msg_id);
+            return Err(invalid(format!(
+                "message '{}' exceeds the size limit",
+                chunk.msg_id
+            )));
+        }
+        if partial.chunks[chunk.chunk_index].is_none() {
+            partial.received += 1;
+        }
+        partial.chunks[chunk.chunk_index] = Some(chunk.data);
+        partial.len = len;
+        partial.last_update = now;
+        self.pending_len = pending_len;
+
+        if partial.received < partial.chunks.len() {
+            return Ok(None);
+        }
+        let Some(partial) = self.pending.remove(&chunk.msg_id) else {
+            return Ok(None);
+        };
+        self.pending_len -= partial.len;
+        let mut message = String::with_capacity(partial.len);
+        for data in partial.chunks.into_iter().flatten() {
+            message.push_str(&data);
+        }
+        Ok(Some(message))
+    }
+
+    fn discard(&mut self, msg_id: &str) {
+        if let Some(partial) = self.pending.remove(msg_id) {
+            self.pending_len -= partial.len;
+        }
+    }
+
+    fn evict_expired(&mut self, now: Instant) {
+        let pending_len = &mut self.pending_len;
+        self.pending.retain(|msg_id, partial| {
+            let expired = now.duration_since(partial.last_update) > CHUNK_TIMEOUT;
+            if expired {
+                tracing::warn!(
+                    "Discarding incomplete message '{msg_id}' after receiving {} of {} chunks",
+                    partial.received,
+                    partial.chunks.len()
+                );
+                *pending_len -= partial.len;
+            }
+            !expired
+        });
+    }
+}
+
+/// Splits `text` into the frames it is sent as: `text` itself if it fits into one frame,
+/// chunk frames otherwise.
+pub fn split(text: String) -> Vec<String> {
+    if text.len() <= MAX_FRAME_LEN {
+        return vec![text];
+    }
+
+    let mut pieces = Vec::new();
+    let mut rest = text.as_str();
+    while !rest.is_empty() {
+        let mut end = MAX_FRAME_LEN.min(rest.len());
+        while !rest.is_char_boundary(end) {
+            end -= 1;
+        }
+        let (piece, tail) = rest.split_at(end);
+        pieces.push(piece);
+        rest = tail;
+    }
+
+    let msg_id = format!("server-{}", NEXT_MSG_ID.fetch_add(1, Ordering::Relaxed));
+    pieces
+        .iter()
+        .enumerate()
+        .map(|(chunk_index, data)| {
+            serde_json::to_string(&ChunkFrame {
+                kind: "chunk",
+                msg_id: &msg_id,
+                chunk_index,
+                total_chunks: pieces.len(),
+                data,
+            })
+            .unwrap_or_default()
+        })
+        .collect()
+}
+
+/// Sends `text` over `session`, chunked if it does not fit into one frame.
+///
+/// # Errors
+///
+/// Returns an error if the connection is closed.
+pub async fn send_text(session: &mut Session, text: String) -> Result<(), Closed> {
+    for frame in split(text) {
+        session.text(frame).await?;
+    }
+    Ok(())
+}
+
+fn invalid(message: String) -> ProtocolError {
+    ProtocolError::new(ErrorCode::InvalidArgument, message)
+}
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod tests {
+    use super::*;
+
+    fn chunk(msg_id: &str, chunk_index: usize, total_chunks: usize, data: &str) -> Chunk {
+        Chunk {
+            msg_id: msg_id.to_string(),
+            chunk_index,
+            total_chunks,
+            data: data.to_string(),
+        }
+    }
+
+    fn rejected(result: Result<Option<String>, ProtocolError>) -> String {
+        let error = result.unwrap_err();
+        assert_eq!(error.code, ErrorCode::InvalidArgument);
+        error.message
+    }
+
+    #[test]
+    fn test_parse() {
+        assert!(Chunk::parse("not json").is_none());
+        assert!(Chunk::parse(r#"{"command":"x","msg_id":"1"}"#).is_none());
+
+        let frame = r#"{ "data": "ab", "total_chunks": 2, "chunk_index": 1, "msg_id": "m", "type": "chunk" }"#;
+        let chunk = Chunk::parse(frame).unwrap().unwrap();
+        assert_eq!((chunk.chunk_index, chunk.total_chunks), (1, 2));
+        assert_eq!(chunk.data, "ab");
+
+        let error = Chunk::parse(r#"{"type":"chunk","msg_id":"m"}"#)
+            .unwrap()
+            .unwrap_err();
+        assert_eq!(error.code, ErrorCode::MalformedMessage);
+    }
+
+    #[test]
+    fn test_split_round_trip() {
+        let text: String = "äbc".repeat(MAX_FRAME_LEN);
+        let frames = split(text.clone());
+        assert!(frames.len() > 1);
+
+        let mut assembler = ChunkAssembler::default();
+        let mut message = None;
+        // out of order
+        for frame in frames.iter().rev() {
+            assert!(message.is_none());
+            message = assembler
+                .push(Chunk::parse(frame).unwrap().unwrap())
+                .unwrap();
+        }
+        assert_eq!(message.as_deref(), Some(text.as_str()));
+        assert_eq!(assembler.pending_len, 0);
+
+        let frames = split(String::from("short"));
+        assert_eq!(frames, vec!["short".to_string()]);
+    }
+
+    #[test]
+    fn test_duplicate_chunk() {
+        let mut assembler = ChunkAssembler::default();
+        assert_eq!(assembler.push(chunk("m", 0, 2, "first")).unwrap(), None);
+        assert_eq!(assembler.push(chunk("m", 0, 2, "a")).unwrap(), None);
+        assert_eq!(assembler.pending_len, 1);
+        assert_eq!(
+            assembler.push(chunk("m", 1, 2, "b")).unwrap().as_deref(),
+            Some("ab")
+        );
+        assert!(assembler.pending.is_empty());
+    }
+
+    #[test]
+    fn test_invalid_metadata() {
+        let mut assembler = ChunkAssembler::default();
+        assert!(rejected(assembler.push(chunk("m", 0, 0, ""))).contains("total_chunks 0"));
+        assert!(
+            rejected(assembler.push(chunk("m", 0, MAX_TOTAL_CHUNKS + 1, "")))
+                .contains("must be between 1")
+        );
+        assert!(rejected(assembler.push(chunk("m", 2, 2, ""))).contains("out of range"));
+
+        // an inconsistent chunk discards the partial message
+        assert_eq!(assembler.push(chunk("m", 0, 2, "a")).unwrap(), None);
+        assert!(rejected(assembler.push(chunk("m", 1, 3, "b"))).contains("changed from 2 to 3"));
+        assert!(assembler.pending.is_empty());
+        assert_eq!(assembler.pending_len, 0);
+    }
+
+    #[test]
+    fn test_pending_messages_limit() {
+        let mut assembler = ChunkAssembler::default();
+        for index in 0..MAX_PENDING_MESSAGES {
+            assert_eq!(
+                assembler
+                    .push(chunk(&index.to_string(), 0, 2, "a"))
+                    .unwrap(),
+                None
+            );
+        }
+        assert!(rejected(assembler.push(chunk("new", 0, 2, "a"))).contains("too many"));
+        // chunks of pending messages are still accepted
+        assert_eq!(
+            assembler.push(chunk("0", 1, 2, "b")).unwrap().as_deref(),
+            Some("ab")
+        );
+        assert_eq!(assembler.push(chunk("new", 0, 2, "a")).unwrap(), None);
+    }
+
+    #[test]
+    fn test_size_limits() {
+        let mut assembler = ChunkAssembler::default();
+        let half = "x".repeat(MAX_MESSAGE_LEN / 2);
+        assert_eq!(assembler.push(chunk("m", 0, 3, &half)).unwrap(), None);
+        assert_eq!(assembler.push(chunk("m", 1, 3, &half)).unwrap(), None);
+        assert!(rejected(assembler.push(chunk("m", 2, 3, "x"))).contains("size limit"));
+        assert!(assembler.pending.is_empty());
+        assert_eq!(assembler.pending_len, 0);
+
+        // messages within the limit that exceed the pending limit together
+        let large = "x".repeat(MAX_PENDING_LEN / 3 + 1);
+        for msg_id in ["a", "b"] {
+            assert_eq!(assembler.push(chunk(msg_id, 0, 2, &large)).unwrap(), None);
+        }
+        assert!(rejected(assembler.push(chunk("c", 0, 2, &large))).contains("size limit"));
+        assert_eq!(assembler.pending.len(), 2);
+        assert_eq!(assembler.pending_len, 2 * large.len());
+    }
+
+    #[test]
+    fn test_timeout() {
+        let mut assembler = ChunkAssembler::default();
+        let start = Instant::now();
+        assert_eq!(
+            assembler.push_at(chunk("m", 0, 2, "a"), start).unwrap(),
+            None
+        );
+
+        // the message is kept while chunks keep arriving
+        let later = start + CHUNK_TIMEOUT;
+        assert_eq!(
+            assembler.push_at(chunk("n", 0, 2, "c"), later).unwrap(),
+            None
+        );
+        assert_eq!(assembler.pending.len(), 2);
+
+        let expired = later + Duration::from_secs(1);
+        assert_eq!(
+            assembler.push_at(chunk("m", 1, 2, "b"), expired).unwrap(),
+            None
+        );
+        assert_eq!(assembler.pending.len(), 2);
+        assert_eq!(assembler.pending_len, 2);
+        assert_eq!(
+            assembler
+                .push_at(chunk("n", 1, 2, "d"), expired)
+                .unwrap()
+                .as_deref(),
+            Some("cd")
+        );
+    }
+}
@@ -1,23 +1,29 @@
 use actix_files as fs;
+use actix_web::http::{header, Uri};
 use actix_web::{web, App, Error, HttpRequest, HttpResponse, HttpServer};
-use actix_ws::{Message, Session};
+use actix_ws::Message;
 use futures::StreamExt;
-use script_gen_manager::script_component::script::ScriptModel;
+use script_gen_manager::catalog::Catalog;
+use script_gen_manager::script_component::script::ScriptWriter;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::sync::Arc;
-use tokio::sync::{broadcast, Mutex};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpListener};
+use std::sync::{Arc, OnceLock};
+use tokio::sync::Mutex;
 
 use tokio::io::{self, AsyncBufReadExt};
 use tokio::signal;
 use tokio::sync::watch;
 
 use std::fs::{self as other_fs};
-use std::path::Path;
 
-use super::data_model::DataModel;
-use crate::back_end::ipc_data::IpcData;
-use script_gen_manager::model::sweep_data::status_msg::{StatusMsg, StatusType};
+use super::chunking::{send_text, Chunk, ChunkAssembler};
+use super::protocol::{
+    self, ErrorCode, HostMessage, HostRequest, ProtocolError, Response, WireFormat,
+    DEFAULT_WORKSPACE,
+};
+use super::rest_api;
+use super::workspace::Workspace;
 
 #[derive(Serialize, Deserialize)]
 pub struct ScriptPath {
@@ -39,201 +45,301 @@ impl ScriptPath {
     }
 }
 
-#[derive(Clone)]
-pub struct AppState {
-    pub session: Arc<Mutex<Option<Session>>>,
-    pub data_model: Arc<Mutex<DataModel>>,
-    pub gen_script_tx: broadcast::Sender<()>,
-    pub work_folder: Arc<Mutex<Option<String>>>,
+/// Address the web server listens on.
+#[derive(Debug, Clone)]
+pub struct ServerConfig {
+    pub host: String,
+    /// Port to bind, 0 lets the OS pick a free port.
+    pub port: u16,
+    /// Bind a free port instead of failing when `port` is already in use.
+    pub auto_port: bool,
+    /// Writer of the generated scripts.
+    pub writer: ScriptWriter,
 }
 
-impl Default for AppState {
+impl Default for ServerConfig {
     fn default() -> Self {
-        Self::new()
+        ServerConfig {
+            host: "127.0.0.1".to_string(),
+            port: 27950,
+            auto_port: false,
+            writer: ScriptWriter::default(),
+        }
+    }
+}
+
+impl ServerConfig {
+    /// Binds a listener according to this configuration.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the address cannot be bound, or if the port is in use and
+    /// `auto_port` is not set.
+    pub fn bind(&self) -> std::io::Result<TcpListener> {
+        match TcpListener::bind((self.host.as_str(), self.port)) {
+            Err(e) if self.auto_port && e.kind() == std::io::ErrorKind::AddrInUse => {
+                tracing::warn!("Port {} is in use, binding a free port instead", self.port);
+                TcpListener::bind((self.host.as_str(), 0))
+            }
+            res => res,
+        }
     }
 }
 
+/// Returns the URL clients use to reach a server bound to `addr`.
+fn server_url(addr: SocketAddr) -> String {
+    let mut addr = addr;
+    // a server bound to all interfaces is reached through the loopback address
+    if addr.ip().is_unspecified() {
+        addr.set_ip(match addr.ip() {
+            IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::LOCALHOST),
+            IpAddr::V6(_) => IpAddr::V6(Ipv6Addr::LOCALHOST),
+        });
+    }
+    format!("http://{addr}")
+}
+
+pub struct AppState {
+    /// Open workspaces, keyed by workspace ID.
+    pub workspaces: Mutex<HashMap<String, Arc<Workspace>>>,
+    catalog: Catalog,
+    writer: ScriptWriter,
+    /// URL of the web server, set once the server is bound.
+    pub base_url: OnceLock<String>,
+    /// Address of the web server, set once the server is bound.
+    pub local_addr: OnceLock<SocketAddr>,
+}
+
 impl AppState {
-    pub fn new() -> Self {
-        let (gen_script_tx, _) = broadcast::channel(100); // Create a broadcast channel
+    /// Creates the server state with the default workspace open.
+    pub fn new(catalog: Catalog, writer: ScriptWriter) -> Self {
+        let default = Workspace::open(DEFAULT_WORKSPACE, &catalog, writer);
         AppState {
-            session: Arc::new(Mutex::new(None)),
-            data_model: Arc::new(Mutex::new(DataModel::new())),
-            gen_script_tx,
-            work_folder: Arc::new(Mutex::new(Option::None)),
+            workspaces: Mutex::new(HashMap::from([(DEFAULT_WORKSPACE.to_string(), default)])),
+            catalog,
+            writer,
+            base_url: OnceLock::new(),
+            local_addr: OnceLock::new(),
         }
     }
 
-    async fn update_script_name_from_json(&self, json_value: &str) {
-        // let new_wf: Script_Path = Script_Path::default();
-        // let pat = serde_json::to_string(&new_wf).unwrap();
-        // println!("{pat}");
-        println!("Updating work folder from JSON: {json_value}");
-        let mut work_folder_guard = self.work_folder.lock().await;
-        if let Ok(value) = serde_json::from_str::<ScriptPath>(json_value) {
-            let filename: String = format!("{}.tsp", value.session.clone());
-            let path_file = Path::new(&value.folder).join(filename);
-            let folder = path_file.parent().unwrap();
-            //println!("Updating work folder to: {:?}", folder.to_string_lossy().to_string());
-            // Check if the folder exists and is writable
-
-            if folder.exists() {
-                *work_folder_guard = Some(path_file.to_string_lossy().to_string());
-                println!("Work folder updated to: {work_folder_guard:?}");
-            } else {
-                let status_msg = StatusMsg::new(
-                    StatusType::Error,
-                    format!(
-                        "The folder is read-only (OR) Work folder does not exist: {:?}",
-                        path_file.to_string_lossy().to_string()
-                    ),
-                );
-                println!(
-                    "{}",
-                    serde_json::to_string(&status_msg)
-                        .unwrap_or_else(|_| status_msg.message.clone())
-                );
+    /// Returns whether a page of `origin` may use the server, see [`is_allowed_origin`].
+    fn allows_origin(&self, origin: &[u8]) -> bool {
+        self.local_addr
+            .get()
+            .is_some_and(|addr| is_allowed_origin(origin, *addr))
+    }
+
+    /// Returns the workspace with the given ID, if it is open.
+    pub async fn workspace(&self, id: &str) -> Option<Arc<Workspace>> {
+        self.workspaces.lock().await.get(id).cloned()
+    }
+
+    /// Opens the workspace with the given ID, or returns it if it is already open.
+    pub async fn open_workspace(&self, id: &str) -> Arc<Workspace> {
+        self.workspaces
+            .lock()
+            .await
+            .entry(id.to_string())
+            .or_insert_with(|| Workspace::open(id, &self.catalog, self.writer))
+            .clone()
+    }
+
+    /// Closes the workspace with the given ID. Returns false if it was not open.
+    pub async fn close_workspace(&self, id: &str) -> bool {
+        let workspace = self.workspaces.lock().await.remove(id);
+        match workspace {
+            Some(workspace) => {
+                workspace.close().await;
+                true
             }
-        } else {
-            println!("Failed to parse work folder from JSON: {json_value}");
+            None => false,
         }
     }
+
+    /// Handles a request received from the host on stdin. Returns `false` once the server
+    /// should shut down.
+    async fn handle_host_message(&self, message: HostMessage) -> bool {
+        let HostMessage {
+            request,
+            format,
+            workspace: id,
+        } = message;
+        let response = match request {
+            HostRequest::Shutdown => {
+                tracing::info!("Received shutdown command from stdin, shutting down...");
+                return false;
+            }
+            HostRequest::OpenWorkspace => {
+                self.open_workspace(&id).await;
+                Response::WorkspaceOpened
+            }
+            HostRequest::CloseWorkspace => {
+                if self.close_workspace(&id).await {
+                    Response::WorkspaceClosed
+                } else {
+                    unknown_workspace(&id)
+                }
+            }
+            request => match self.workspace(&id).await {
+                Some(workspace) => {
+                    workspace.handle_host_request(request, format).await;
+                    return true;
+                }
+                None => unknown_workspace(&id),
+            },
+        };
+        protocol::write_to_host(&response.to_host_json(format, &id));
+        true
+    }
+}
+
+/// Returns whether a browser page of `origin` may use the server bound to `addr`: pages
+/// served by the server itself and VS Code webviews. Other pages open in the user's browser
+/// must not reach the API or the WebSocket.
+fn is_allowed_origin(origin: &[u8], addr: SocketAddr) -> bool {
+    let Ok(origin) = std::str::from_utf8(origin) else {
+        return false;
+    };
+    if origin.starts_with("vscode-webview://") {
+        return true;
+    }
+    let Ok(uri) = origin.parse::<Uri>() else {
+        return false;
+    };
+    if uri.scheme_str() != Some("http") || uri.port_u16() != Some(addr.port()) {
+        return false;
+    }
+    match uri.host() {
+        Some("localhost") => true,
+        Some(host) => host
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .parse::<IpAddr>()
+            .is_ok_and(|ip| ip.is_loopback() || ip == addr.ip()),
+        None => false,
+    }
+}
+
+fn unknown_workspace(id: &str) -> Response {
+    Response::Error(ProtocolError::new(
+        ErrorCode::InvalidArgument,
+        format!("workspace '{id}' is not open"),
+    ))
+}
+
+/// Query parameters selecting a workspace, `/ws?workspace=<id>` or `/api/...?workspace=<id>`.
+#[derive(Deserialize, Debug)]
+pub struct WorkspaceQuery {
+    pub workspace: Option<String>,
+}
+
+impl WorkspaceQuery {
+    pub fn id(&self) -> &str {
+        self.workspace.as_deref().unwrap_or(DEFAULT_WORKSPACE)
+    }
 }
 
 async fn ws_index(
     req: HttpRequest,
     body: web::Payload,
     app_state: web::Data<Arc<AppState>>,
+    query: web::Query<WorkspaceQuery>,
 ) -> Result<HttpResponse, Error> {
+    // browsers let any page open a WebSocket, only the UI may
+    if let Some(origin) = req.headers().get(header::ORIGIN) {
+        if !app_state.allows_origin(origin.as_bytes()) {
+            tracing::warn!("Rejected WebSocket connection from {origin:?}");
+            return Ok(HttpResponse::Forbidden().finish());
+        }
+    }
+    let Some(workspace) = app_state.workspace(query.id()).await else {
+        return Ok(HttpResponse::NotFound().body(format!("Workspace '{}' is not open", query.id())));
+    };
     let (response, mut session, mut msg_stream) = actix_ws::handle(&req, body)?;
     //msg_stream = msg_stream.max_frame_size(50 * 1024 * 1024); // 50MB
 
-    // Use the app_state here
-    {
-        let mut session_guard = app_state.session.lock().await;
-        *session_guard = Some(session.clone());
-    }
-
-    let gen_script_tx = app_state.gen_script_tx.clone();
+    // a workspace belongs to one panel, a second connection is refused
+    let Some(connection) = workspace.attach(session.clone()).await else {
+        return Ok(HttpResponse::Conflict()
+            .body(format!("Workspace '{}' is already connected", query.id())));
+    };
 
-    let mut chunk_buffers: HashMap<String, Vec<Option<String>>> = HashMap::new();
+    let mut assembler = ChunkAssembler::default();
 
     actix_web::rt::spawn(async move {
         while let Some(Ok(msg)) = msg_stream.next().await {
             match msg {
                 Message::Ping(bytes) => {
                     if session.pong(&bytes).await.is_err() {
-                        return;
+                        break;
                     }
                 }
-                Message::Text(mut msg) => {
-                    let mut is_chunked = false;
-                    {
-                        use serde_json::Value;
-                        if let Ok(value) = serde_json::from_str::<Value>(&msg) {
-                            if let (
-                                Some(msg_id),
-                                Some(chunk_index),
-                                Some(total_chunks),
-                                Some(data),
-                            ) = (
-                                value.get("msg_id").and_then(|v| v.as_str()),
-                                value.get("chunk_index").and_then(|v| v.as_u64()),
-                                value.get("total_chunks").and_then(|v| v.as_u64()),
-                                value.get("data").and_then(|v| v.as_str()),
-                            ) {
-                                is_chunked = true;
-                                let entry = chunk_buffers
-                                    .entry(msg_id.to_string())
-                                    .or_insert_with(|| vec![None; total_chunks as usize]);
-                                entry[chunk_index as usize] = Some(data.to_string());
-                                if entry.iter().all(|c| c.is_some()) {
-                                    let full_msg = entry
-                                        .iter()
-                                        .map(|c| c.as_ref().unwrap().as_str())
-                                        .collect::<String>();
-                                    chunk_buffers.remove(msg_id);
-                                    println!(
-                                        "Received complete chunked message of size: {} bytes",
-                                        full_msg.len()
-                                    );
-                                    msg = full_msg.into();
-                                } else {
-                                    println!(
-                                        "Received chunk {}/{} for msg_id {}",
-                                        chunk_index + 1,
-                                        total_chunks,
-                                        msg_id
-                                    );
-                                    continue;
-                                }
+                Message::Text(msg) => {
+                    let msg = match Chunk::parse(&msg) {
+                        None => msg.to_string(),
+                        Some(chunk) => match chunk.and_then(|chunk| assembler.push(chunk)) {
+                            Ok(Some(msg)) => {
+                                tracing::debug!(
+                                    "Received complete chunked message of size: {} bytes",
+                                    msg.len()
+                                );
+                                msg
                             }
-                        }
-                    }
-                    // --- End chunked message reassembly logic ---
-                    // Only fall through to normal processing if not a chunked message or if chunk is complete
-                    if is_chunked && msg.is_empty() {
-                        continue;
-                    }
-                    match serde_json::from_str::<IpcData>(&msg) {
-                        Ok(ipc_data) => {
-                            if ipc_data.request_type == "get_data" {
-                                println!("instrument data requested");
-                            } else if ipc_data.request_type == "evaluate_data" {
-                                let mut data_model = app_state.data_model.lock().await;
-                                let response =
-                                    data_model.process_data_from_client(ipc_data.json_value);
-                                // Send generate script signal
-                                if let Err(e) = gen_script_tx.send(()) {
-                                    eprintln!("Failed to send signal: {e}");
-                                }
-                                session.text(response).await.unwrap();
-                            } else if ipc_data.request_type == "reallocation" {
-                                let mut data_model = app_state.data_model.lock().await;
-                                let response = data_model.add_remove_channel(ipc_data);
-                                println!("{response}");
-                                // Send generate script signal
-                                if let Err(e) = gen_script_tx.send(()) {
-                                    eprintln!("Failed to send signal: {e}");
-                                }
-                                session.text(response).await.unwrap();
-                            } else if ipc_data.request_type == "open_script" {
-                                // Generate script if needed
-                                if let Err(e) = gen_script_tx.send(()) {
-                                    eprintln!("Failed to send signal: {e}");
+                            Ok(None) => continue,
+                            Err(e) => {
+                                tracing::warn!(workspace = %workspace.id, "Rejected chunk from UI: {e}");
+                                let format = *workspace.client_format.lock().await;
+                                let reply = Response::Error(e).to_json(format);
+                                if send_text(&mut session, reply).await.is_err() {
+                                    break;
                                 }
-                                let res = IpcData {
-                                    request_type: "open_script".to_string(),
-                                    additional_info: "".to_string(),
-                                    json_value: "{}".to_string(),
-                                };
-                                let response = serde_json::to_string(&res)
-                                    .expect("Failed to serialize response");
-
-                                session.text(response).await.unwrap();
-                            } else {
-                                println!("Unknown request type: {}", ipc_data.request_type);
+                                continue;
                             }
+                        },
+                    };
+                    let (response, format) = match protocol::parse_client_request(&msg) {
+                        Ok((request, format)) => {
+                            *workspace.client_format.lock().await = format;
+                            (workspace.handle_client_request(request).await, format)
                         }
                         Err(e) => {
-                            eprintln!("Failed to deserialize IpcData: {e}");
+                            tracing::warn!(workspace = %workspace.id, "Rejected message from UI: {e}");
+                            let format = *workspace.client_format.lock().await;
+                            (Some(Response::Error(e)), format)
+                        }
+                    };
+                    if let Some(response) = response {
+                        if send_text(&mut session, response.to_json(format))
+                            .await
+                            .is_err()
+                        {
+                            break;
                         }
                     }
                 }
                 Message::Close(reason) => {
-                    println!("Connection closed: {reason:?}");
-                    return;
+                    tracing::info!(workspace = %workspace.id, "Connection closed: {reason:?}");
+                    break;
                 }
                 _ => (),
             }
         }
-        println!("WebSocket message loop ended - connection lost or closed");
+        tracing::info!(
+            workspace = %workspace.id,
+            "WebSocket message loop ended - connection lost or closed"
+        );
+        workspace.detach(connection).await;
     });
 
     Ok(response)
 }
 
-async fn serve_index_html() -> Result<HttpResponse, Error> {
+/// Serves the UI page, `/?workspace=<id>` opens it on the given workspace.
+async fn serve_index_html(
+    app_state: web::Data<Arc<AppState>>,
+    query: web::Query<WorkspaceQuery>,
+) -> Result<HttpResponse, Error> {
     // Get the path of the executable
     let exe_path =
         std::env::current_exe().expect("should be able to get path of server executable");
@@ -251,15 +357,15 @@ async fn serve_index_html() -> Result<HttpResponse, Error> {
 
     // Read the HTML file
     let html_content = other_fs::read_to_string(&html_path).map_err(|e| {
-        eprintln!("Failed to read HTML file: {e}");
+        tracing::error!("Failed to read HTML file: {e}");
         actix_web::error::ErrorInternalServerError("Failed to load HTML")
     })?;
 
-    // Rewrite resource URLs
-    let base_url = "http://127.0.0.1:27950";
-    let modified_html = html_content
-        .replace("src=\"", &format!("src=\"{base_url}/"))
-        .replace("href=\"", &format!("href=\"{base_url}/"));
+    let base_url = app_state
+        .base_url
+        .get()
+        .map_or("http://127.0.0.1:27950", String::as_str);
+    let modified_html = rewrite_index_html(&html_content, base_url, query.id());
 
     // Return the modified HTML
     Ok(HttpResponse::Ok()
@@ -267,10 +373,41 @@ async fn serve_index_html() -> Result<HttpResponse, Error> {
         .body(modified_html))
 }
 
+/// Name of the meta element that tells the UI which workspace to connect to.
+const WORKSPACE_META: &str = "kic-script-gen-workspace";
+
+/// Points the resource URLs of the UI page at `base_url` and adds the `workspace` the UI
+/// connects its WebSocket to.
+fn rewrite_index_html(html: &str, base_url: &str, workspace: &str) -> String {
+    let meta = format!(
+        "<head>\n  <meta name=\"{WORKSPACE_META}\" content=\"{}\">",
+        escape_attribute(workspace)
+    );
+    html.replace("src=\"", &format!("src=\"{base_url}/"))
+        .replace("href=\"", &format!("href=\"{base_url}/"))
+        .replacen("<head>", &meta, 1)
+}
+
+/// Escapes `value` for use in a double quoted HTML attribute.
+fn escape_attribute(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('"', "&quot;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
 pub async fn start_web_server(
     app_state: Arc<AppState>,
+    config: ServerConfig,
     mut shutdown_rx: watch::Receiver<()>,
 ) -> std::io::Result<()> {
+    let listener = config.bind()?;
+    let addr = listener.local_addr()?;
+    let url = server_url(addr);
+    let _ = app_state.base_url.set(url.clone());
+    let _ = app_state.local_addr.set(addr);
+
     let server = HttpServer::new(move || {
         let exe_path =
             std::env::current_exe().expect("should be able to get path of server executable");
@@ -282,58 +419,48 @@ pub async fn start_web_server(
             .app_data(web::Data::new(app_state.clone()))
             .route("/", web::get().to(serve_index_html))
             .route("/ws", web::get().to(ws_index))
+            .configure(rest_api::configure)
             .service(fs::Files::new("/", browser_dir).index_file("index.html"))
             .wrap(
                 actix_cors::Cors::default()
-                    .allow_any_origin()
-                    .allowed_methods(vec!["GET", "POST"])
+                    .allowed_origin_fn(move |origin, _| is_allowed_origin(origin.as_bytes(), addr))
+                    .allowed_methods(vec!["GET", "POST", "PUT"])
                     .allowed_headers(vec!["Content-Type"]),
             )
     })
-    .bind(("127.0.0.1", 27950))?
+    .listen(listener)?
     .run();
 
+    tracing::info!("Listening on {url}");
+    // announce the bound address so the host can connect to it
+    let started = Response::ServerStarted {
+        url,
+        host: addr.ip().to_string(),
+        port: addr.port(),
+    };
+    protocol::write_to_host(&started.to_json(WireFormat::Versioned));
+
     tokio::select! {
         res = server => res,
         _ = shutdown_rx.changed() => {
-            println!("Shutdown signal received, stopping server...");
+            tracing::info!("Shutdown signal received, stopping server...");
             Ok(())
         },
     }
 }
 
-pub async fn start(mut script_model: ScriptModel) -> anyhow::Result<()> {
-    let app_state = Arc::new(AppState::new());
+pub async fn start(catalog: Catalog, config: ServerConfig) -> anyhow::Result<()> {
+    let app_state = Arc::new(AppState::new(catalog, config.writer));
 
     let (shutdown_tx, shutdown_rx) = watch::channel(());
-    let server = start_web_server(app_state.clone(), shutdown_rx.clone());
-
-    // Clone the event receiver for generating script
-    let mut gen_script_rx = app_state.gen_script_tx.subscribe();
-
-    // Spawn a task to listen to generate script event
-    {
-        let app_state_clone = app_state.clone();
-        tokio::spawn(async move {
-            while let Ok(()) = gen_script_rx.recv().await {
-                println!("Signal received to start script generation!");
-                let data_model = app_state_clone.data_model.lock().await;
-                let work_folder_guard = app_state_clone.work_folder.lock().await;
-                let work_folder = work_folder_guard
-                    .as_ref()
-                    .map(|s| s.as_str())
-                    .unwrap_or("C:\\default.tsp");
-                script_model.to_script(&data_model.sweep_model.sweep_config, work_folder);
-            }
-        });
-    }
+    let server = start_web_server(app_state.clone(), config, shutdown_rx.clone());
 
     // Spawn a task to listen for shutdown signal (e.g., Ctrl+C)
     tokio::spawn({
         let shutdown_tx = shutdown_tx.clone();
         async move {
             signal::ctrl_c().await.expect("Failed to listen for event");
-            println!("Received Ctrl+C, shutting down...");
+            tracing::info!("Received Ctrl+C, shutting down...");
             let _ = shutdown_tx.send(());
         }
     });
@@ -346,97 +473,20 @@ pub async fn start(mut script_model: ScriptModel) -> anyhow::Result<()> {
         let app_state = app_state.clone();
 
         while let Some(line) = reader.next_line().await.unwrap() {
-            //println!("Received from stdin: {line}");
-            let trimmed_line = line.trim();
-            if trimmed_line.contains("shutdown") {
-                println!("Received shutdown command from stdin, shutting down...");
-                let _ = shutdown_tx.send(());
-                break;
-            } else if trimmed_line.contains("session") && trimmed_line.contains("folder") {
-                app_state.update_script_name_from_json(trimmed_line).await;
-            } else if trimmed_line.contains("systems") {
-                let mut data_model = app_state.data_model.lock().await;
-                let response = data_model.process_system_config(trimmed_line);
-                println!("{response}");
-                // Send generate script signal
-                if !response.contains("error") {
-                    if let Err(e) = app_state.gen_script_tx.send(()) {
-                        eprintln!("Failed to send signal: {e}");
-                    }
-                }
-                let mut session = app_state.session.lock().await;
-                if let Some(session) = session.as_mut() {
-                    session.text(response).await.unwrap();
-                }
-            } else if trimmed_line.contains("lineFrequency") {
-                println!("line frequency received {}", trimmed_line);
-                match serde_json::from_str::<serde_json::Value>(trimmed_line) {
-                    Ok(json_obj) => {
-                        if let Some(freq) = json_obj.get("lineFrequency").and_then(|v| v.as_f64()) {
-                            let mut data_model = app_state.data_model.lock().await;
-                            data_model
-                                .sweep_model
-                                .sweep_config
-                                .global_parameters
-                                .set_line_frequency(freq);
-                            println!("Set line frequency to {}", freq);
-                        } else {
-                            println!(
-                                "'lineFrequency' key not found or not a number in JSON: {}",
-                                trimmed_line
-                            );
-                        }
-                    }
-                    Err(e) => {
-                        println!(
-                            "Failed to parse line frequency JSON: {} | Error: {}",
-                            trimmed_line, e
-                        );
+            if line.trim().is_empty() {
+                continue;
+            }
+            match protocol::parse_host_request(&line) {
+                Ok(message) => {
+                    if !app_state.handle_host_message(message).await {
+                        let _ = shutdown_tx.send(());
+                        break;
                     }
                 }
-            } else if trimmed_line.contains("refresh") {
-                println!("instrument data requested"); // refreshing by initiating session again does not affect the JSON state
-            } else if trimmed_line.contains("reset") {
-                let mut data_model = app_state.data_model.lock().await;
-                let response = data_model.reset_sweep_config();
-                let mut session = app_state.session.lock().await;
-                if let Some(session) = session.as_mut() {
-                    session.text(response).await.unwrap();
-                }
-                println!("instrument data requested"); // getting the system configuration for new session
-            } else if trimmed_line.contains("request_type") {
-                // both the functions process_data_from_client and process_data_from_saved_config expect sweep_config, process_data_from_client receives sweep_config directly from client
-                // process_data_from_saved_config receives sweep_model from saved JSON, this sweep_model has the sweep_config
-                let mut data_model = app_state.data_model.lock().await;
-                match serde_json::from_str::<IpcData>(trimmed_line) {
-                    Ok(ipc_data) => {
-                        match serde_json::from_str::<serde_json::Value>(&ipc_data.json_value) {
-                            Ok(json_obj) => {
-                                if let Some(sweep_model) = json_obj.get("sweep_model") {
-                                    if let Ok(sweep_model_str) = serde_json::to_string(sweep_model)
-                                    {
-                                        let response = data_model
-                                            .process_data_from_saved_config(sweep_model_str);
-                                        println!("processed data from saved config {response}");
-                                        let mut session = app_state.session.lock().await;
-                                        if let Some(session) = session.as_mut() {
-                                            session.text(response).await.unwrap();
-                                        }
-                                    } else {
-                                        eprintln!("Failed to serialize sweep_model to string");
-                                    }
-                                } else {
-                                    eprintln!("'sweep_model' field not found in json_value");
-                                }
-                            }
-                            Err(e) => {
-                                eprintln!("Failed to parse json_value as JSON: {e}");
-                            }
-                        }
-                    }
-                    Err(e) => {
-                        eprintln!("Failed to deserialize IpcData from stdin: {e}");
-                    }
+                Err(e) => {
+                    tracing::warn!("Rejected message from stdin: {e}");
+                    let format = protocol::host_line_format(&line);
+                    protocol::write_to_host(&Response::Error(e).to_json(format));
                 }
             }
         }
@@ -446,3 +496,229 @@ pub async fn start(mut script_model: ScriptModel) -> anyhow::Result<()> {
     server.await?;
     Ok(())
 }
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod tests {
+    use std::time::Duration;
+
+    use std::pin::Pin;
+
+    use actix_web::dev;
+    use actix_web::error::PayloadError;
+    use actix_web::http::StatusCode;
+    use actix_web::test::TestRequest;
+    use actix_web::web::Bytes;
+    use actix_web::FromRequest;
+    use futures::channel::mpsc;
+    use futures::Stream;
+
+    use super::*;
+    use crate::back_end::protocol::{ClientRequest, Envelope};
+
+    /// Frames sent by the UI of a connection.
+    type Frames = mpsc::UnboundedSender<Result<Bytes, PayloadError>>;
+
+    fn app_state() -> web::Data<Arc<AppState>> {
+        let mut catalog = Catalog::new();
+        catalog.refresh_function_metadata().unwrap();
+        web::Data::new(Arc::new(AppState::new(catalog, ScriptWriter::default())))
+    }
+
+    /// Opens a WebSocket connection to `workspace`. The connection lasts until the returned
+    /// response and frame sender are dropped.
+    async fn connect(
+        app_state: &web::Data<Arc<AppState>>,
+        workspace: &str,
+    ) -> (HttpResponse, Frames) {
+        connect_from(app_state, workspace, None).await
+    }
+
+    /// Opens a WebSocket connection to `workspace` from a page of `origin`.
+    async fn connect_from(
+        app_state: &web::Data<Arc<AppState>>,
+        workspace: &str,
+        origin: Option<&str>,
+    ) -> (HttpResponse, Frames) {
+        let (frames, rx) = mpsc::unbounded();
+        let mut req = TestRequest::get();
+        if let Some(origin) = origin {
+            req = req.insert_header((header::ORIGIN, origin));
+        }
+        let req = req
+            .uri(&format!("/ws?workspace={workspace}"))
+            .insert_header(("upgrade", "websocket"))
+            .insert_header(("connection", "upgrade"))
+            .insert_header(("sec-websocket-version", "13"))
+            .insert_header(("sec-websocket-key", "dGhlIHNhbXBsZSBub25jZQ=="))
+            .to_http_request();
+        let stream: Pin<Box<dyn Stream<Item = Result<Bytes, PayloadError>>>> = Box::pin(rx);
+        let mut payload = dev::Payload::from(stream);
+        let body = web::Payload::from_request(&req, &mut payload)
+            .await
+            .unwrap();
+        let query = web::Query::<WorkspaceQuery>::from_query(req.query_string()).unwrap();
+        let response = ws_index(req, body, app_state.clone(), query).await.unwrap();
+        (response, frames)
+    }
+
+    /// Returns a text frame as sent by a browser, masked with a zero key.
+    fn text_frame(text: &str) -> Bytes {
+        let mut frame = vec![0x81];
+        let len = text.len();
+        if len < 126 {
+            frame.push(0x80 | u8::try_from(len).unwrap());
+        } else {
+            frame.push(0x80 | 126);
+            frame.extend_from_slice(&u16::try_from(len).unwrap().to_be_bytes());
+        }
+        frame.extend_from_slice(&[0; 4]);
+        frame.extend_from_slice(text.as_bytes());
+        Bytes::from(frame)
+    }
+
+    async fn sweep_points(workspace: &Workspace) -> i32 {
+        workspace
+            .data_model
+            .lock()
+            .await
+            .sweep_model
+            .sweep_config
+            .sweep_global_parameters
+            .sweep_points
+            .value
+    }
+
+    /// Waits until `condition` holds, giving up after a few seconds.
+    async fn wait_for<F: std::future::Future<Output = bool>>(mut condition: impl FnMut() -> F) {
+        for _ in 0..500 {
+            if condition().await {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("condition not met in time");
+    }
+
+    #[actix_web::test]
+    async fn test_workspaces_keep_separate_models_and_paths() {
+        let app_state = app_state();
+        let a = app_state.open_workspace("a").await;
+        let b = app_state.open_workspace("b").await;
+
+        let (response_a, frames_a) = connect(&app_state, "a").await;
+        assert_eq!(response_a.status(), StatusCode::SWITCHING_PROTOCOLS);
+        let (response, _) = connect(&app_state, "a").await;
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+        let (response_b, frames_b) = connect(&app_state, "b").await;
+        assert_eq!(response_b.status(), StatusCode::SWITCHING_PROTOCOLS);
+        let (response, _) = connect(&app_state, "c").await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        // each UI edits the model of its own workspace
+        for (workspace, frames, points) in [(&a, &frames_a, 20), (&b, &frames_b, 30)] {
+            let mut sweep_model = workspace.data_model.lock().await.sweep_model.clone();
+            sweep_model
+                .sweep_config
+                .sweep_global_parameters
+                .sweep_points
+                .value = points;
+            let message =
+                serde_json::to_string(&Envelope::new(ClientRequest::EvaluateData { sweep_model }))
+                    .unwrap();
+            frames.unbounded_send(Ok(text_frame(&message))).unwrap();
+        }
+        wait_for(|| async { sweep_points(&a).await == 20 && sweep_points(&b).await == 30 }).await;
+        assert_eq!(
+            sweep_points(&app_state.workspace(DEFAULT_WORKSPACE).await.unwrap()).await,
+            10
+        );
+
+        // the host sets a separate output path per workspace
+        let folder = std::env::temp_dir().join(format!("kic-script-gen-ws-{}", std::process::id()));
+        std::fs::create_dir_all(&folder).unwrap();
+        for id in ["a", "b"] {
+            let request = HostRequest::ScriptPath {
+                session: id.to_string(),
+                folder: folder.to_string_lossy().into_owned(),
+            };
+            let line = serde_json::to_string(&Envelope::for_workspace(request, id)).unwrap();
+            let message = protocol::parse_host_request(&line).unwrap();
+            assert!(app_state.handle_host_message(message).await);
+        }
+        for (workspace, id) in [(&a, "a"), (&b, "b")] {
+            let work_folder = workspace.work_folder.lock().await.clone();
+            assert_eq!(
+                work_folder,
+                Some(
+                    folder
+                        .join(format!("{id}.tsp"))
+                        .to_string_lossy()
+                        .into_owned()
+                )
+            );
+        }
+
+        // once the UI of a disconnects, a new one can connect
+        drop((response_a, frames_a));
+        wait_for(|| async {
+            connect(&app_state, "a").await.0.status() == StatusCode::SWITCHING_PROTOCOLS
+        })
+        .await;
+
+        drop((response_b, frames_b));
+        app_state.close_workspace("a").await;
+        app_state.close_workspace("b").await;
+        let _ = std::fs::remove_dir_all(&folder);
+    }
+
+    #[test]
+    fn test_allowed_origins() {
+        let addr: SocketAddr = "127.0.0.1:27950".parse().unwrap();
+        for origin in [
+            "http://127.0.0.1:27950",
+            "http://localhost:27950",
+            "http://[::1]:27950",
+            "vscode-webview://1234abcd",
+        ] {
+            assert!(is_allowed_origin(origin.as_bytes(), addr), "{origin}");
+        }
+        for origin in [
+            "https://example.com",
+            "http://127.0.0.1:8080",
+            "http://localhost",
+            "https://127.0.0.1:27950",
+            "http://192.168.1.2:27950",
+            "null",
+        ] {
+            assert!(!is_allowed_origin(origin.as_bytes(), addr), "{origin}");
+        }
+        let all: SocketAddr = "0.0.0.0:27950".parse().unwrap();
+        assert!(is_allowed_origin(b"http://127.0.0.1:27950", all));
+    }
+
+    #[actix_web::test]
+    async fn test_foreign_page_cannot_connect() {
+        let app_state = app_state();
+        let _ = app_state.local_addr.set("127.0.0.1:27950".parse().unwrap());
+        let (response, _) =
+            connect_from(&app_state, DEFAULT_WORKSPACE, Some("https://example.com")).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let (response, _frames) = connect_from(
+            &app_state,
+            DEFAULT_WORKSPACE,
+            Some("http://127.0.0.1:27950"),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::SWITCHING_PROTOCOLS);
+    }
+
+    #[test]
+    fn test_index_names_workspace() {
+        let html = "<html>\n<head>\n  <base href=\"/\">\n  <script src=\"main.js\"></script>\n";
+        let html = rewrite_index_html(html, "http://127.0.0.1:1234", "panel \"1\"");
+        assert!(html
+            .contains("<meta name=\"kic-script-gen-workspace\" content=\"panel &quot;1&quot;\">"));
+        assert!(html.contains("src=\"http://127.0.0.1:1234/main.js\""));
+    }
+}
@@ -1,11 +1,39 @@
-use script_gen_manager::model::sweep_data::sweep_model::SweepModel;
-use serde_json::json;
+use std::collections::VecDeque;
 
-use crate::back_end::ipc_data::IpcData;
+use script_gen_manager::device::Device;
+use script_gen_manager::model::{
+    sweep_data::{
+        list_csv::{ListCsvError, ListImport, ListTarget},
+        sweep_config::SweepConfig,
+        sweep_limits::SweepLimits,
+        sweep_model::SweepModel,
+    },
+    system_info::Root,
+};
+
+use crate::back_end::protocol::{ChannelChange, ErrorCode, ProtocolError, Response};
+
+/// Maximum number of snapshots kept for undo.
+pub const HISTORY_LIMIT: usize = 50;
+
+/// A sweep model together with the edit that replaced it.
+#[derive(Clone)]
+struct Snapshot {
+    label: String,
+    sweep_model: SweepModel,
+}
 
 #[derive(Clone)]
 pub struct DataModel {
     pub sweep_model: SweepModel,
+    /// Most recent system configuration received from the host.
+    pub system_info: Option<Root>,
+    /// Models before the most recent edits, oldest first.
+    undo_stack: VecDeque<Snapshot>,
+    /// Models undone since the last edit, most recently undone last.
+    redo_stack: Vec<Snapshot>,
+    /// Limits the sweep model is evaluated with.
+    limits: SweepLimits,
 }
 
 impl Default for DataModel {
@@ -16,200 +44,469 @@ impl Default for DataModel {
 
 impl DataModel {
     pub fn new() -> Self {
+        Self::with_limits(SweepLimits::default())
+    }
+
+    /// Creates a data model whose sweep model is evaluated with `limits`, usually the
+    /// [`Catalog::sweep_limits`](script_gen_manager::catalog::Catalog::sweep_limits).
+    pub fn with_limits(limits: SweepLimits) -> Self {
         DataModel {
             sweep_model: SweepModel::new(),
+            system_info: None,
+            undo_stack: VecDeque::new(),
+            redo_stack: Vec::new(),
+            limits,
         }
     }
 
-    pub fn process_system_config(&mut self, system_info: &str) -> String {
+    pub fn process_system_config(&mut self, system_info: &str) -> Response {
+        let previous = self.sweep_model.clone();
+        let response = self.apply_system_config(system_info);
+        if !response.is_error() {
+            self.system_info = serde_json::from_str(system_info).ok();
+            self.record("Update system configuration", previous);
+        }
+        response
+    }
+
+    fn apply_system_config(&mut self, system_info: &str) -> Response {
         if self.sweep_model.sweep_config.device_list.is_empty() {
             if self
                 .sweep_model
                 .sweep_config
                 .create_device_list(system_info)
             {
-                self.sweep_model.sweep_config.auto_configure();
-                self.serialize_sweep_model(
-                    &self.sweep_model,
-                    "initial_response",
-                    "Initialized sweep model",
-                )
+                let sweep_config = &mut self.sweep_model.sweep_config;
+                sweep_config.apply_defaults(&self.limits);
+                sweep_config.auto_configure();
+                Response::InitialResponse {
+                    message: "Initialized sweep model".to_string(),
+                    sweep_model: self.sweep_model.clone(),
+                }
             } else {
-                self.serialize_empty_response("empty_system_config_error", "No devices found")
+                Response::EmptySystemConfigError {
+                    message: "No devices found".to_string(),
+                }
             }
         } else {
             self.sweep_model
                 .sweep_config
                 .update_devices_for_changed_slots(system_info);
-            self.serialize_sweep_model(
-                &self.sweep_model,
-                "evaluated_response",
-                "Updated sweep model with new system configuration",
-            )
+            Response::EvaluatedResponse {
+                message: "Updated sweep model with new system configuration".to_string(),
+                sweep_model: self.sweep_model.clone(),
+            }
         }
     }
 
-    /// Processes data received from the client by deserializing it into a `SweepModel`,
-    /// evaluating its configuration, and serializing the result into a response.
+    /// Processes a sweep model received from the client by evaluating its configuration
+    /// and making it the current model.
     ///
     /// # Arguments
-    /// * `data` - A JSON string representing the `SweepModel` to be processed.
+    /// * `sweep_model` - The `SweepModel` to be processed.
     ///
     /// # Returns
-    /// A JSON string containing the serialized response. If the deserialization or processing
-    /// fails, an error response is returned.
-    ///
-    /// # Behavior
-    /// 1. Attempts to deserialize the input JSON string into a `SweepModel`.
-    /// 2. If successful:
-    ///    - Evaluates the sweep configuration.
-    ///    - Updates the `sweep` field of the `DataModel` with the processed `SweepModel`.
-    ///    - Serializes the processed `SweepModel` into a response.
-    /// 3. If deserialization fails:
-    ///    - Logs the error.
-    ///    - Returns an error response.
-    pub fn process_data_from_client(&mut self, data: String) -> String {
-        // Deserialize the JSON string into a serde_json::Value
-        match serde_json::from_str::<SweepModel>(&data) {
-            Ok(mut sweep_model) => {
-                //println!("Successfully deserialized JSON in server: {sweep_model:?}");
-                sweep_model.sweep_config.evaluate();
-
-                //update sweep variable - required for actual script generation
-                self.sweep_model = sweep_model.clone();
-                self.serialize_sweep_model(
-                    &sweep_model,
-                    "evaluated_response",
-                    "Processed sweep model",
-                )
-            }
-            Err(e) => {
-                println!("Failed to deserialize JSON: {e}");
-                self.serialize_empty_response("error", "Failed to process sweep model")
-            }
+    /// An `evaluated_response` containing the processed `SweepModel`.
+    pub fn process_data_from_client(&mut self, mut sweep_model: SweepModel) -> Response {
+        sweep_model.sweep_config.evaluate(&self.limits);
+
+        //update sweep variable - required for actual script generation
+        self.replace("Edit sweep model", sweep_model.clone());
+        Response::EvaluatedResponse {
+            message: "Processed sweep model".to_string(),
+            sweep_model,
         }
     }
 
-    pub fn process_data_from_saved_config(&mut self, data: String) -> String {
-        match serde_json::from_str::<SweepModel>(&data) {
-            Ok(mut sweep_model) => {
-                //println!("Successfully deserialized saved JSON in server: {sweep_model:?}");
-                sweep_model.sweep_config.evaluate();
+    pub fn process_data_from_saved_config(&mut self, mut sweep_model: SweepModel) -> Response {
+        sweep_model.sweep_config.evaluate(&self.limits);
 
-                self.sweep_model = sweep_model.clone();
-                self.serialize_sweep_model(
-                    &sweep_model,
-                    "evaluated_response",
-                    "Processed saved sweep model",
-                )
-            }
-            Err(e) => {
-                println!("Failed to deserialize saved JSON: {e}");
-                self.serialize_empty_response("error", "Failed to process saved sweep model")
-            }
+        self.replace("Open saved configuration", sweep_model.clone());
+        Response::EvaluatedResponse {
+            message: "Processed saved sweep model".to_string(),
+            sweep_model,
+        }
+    }
+
+    /// Returns the devices of the most recent system configuration, `None` if no system
+    /// configuration was received yet.
+    pub fn connected_devices(&self) -> Option<Vec<Device>> {
+        let system_info = serde_json::to_string(self.system_info.as_ref()?).ok()?;
+        let mut sweep_config = SweepConfig::new();
+        sweep_config.create_device_list(&system_info);
+        Some(sweep_config.device_list)
+    }
+
+    /// Makes the sweep model of an opened project the current model.
+    pub fn process_data_from_project(&mut self, mut sweep_model: SweepModel) -> Response {
+        sweep_model.sweep_config.evaluate(&self.limits);
+
+        self.replace("Open project", sweep_model.clone());
+        Response::EvaluatedResponse {
+            message: "Opened project".to_string(),
+            sweep_model,
         }
     }
 
-    /// Adds, removes, or updates a channel in the `SweepModel` based on the provided `ipc_data`.
+    /// Replaces the step or sweep lists with the values of a CSV/TSV file.
+    ///
+    /// # Returns
+    /// An `evaluated_response` containing the updated `SweepModel` together with the import
+    /// report. The sweep model is unchanged if the file cannot be imported.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be parsed or does not match the channels.
+    pub fn import_lists(
+        &mut self,
+        target: ListTarget,
+        csv: &str,
+    ) -> Result<(Response, ListImport), ListCsvError> {
+        let mut sweep_model = self.sweep_model.clone();
+        let import = sweep_model
+            .sweep_config
+            .import_lists(target, csv, &self.limits)?;
+
+        self.replace(
+            &format!("Import {} lists", target.as_str()),
+            sweep_model.clone(),
+        );
+        let response = Response::EvaluatedResponse {
+            message: "Imported list values".to_string(),
+            sweep_model,
+        };
+        Ok((response, import))
+    }
+
+    /// Adds, removes, or updates a channel in the `SweepModel` based on the provided `change`.
     ///
     /// # Arguments
-    /// * `ipc_data` - An `IpcData` object containing the JSON representation of the `SweepModel`
-    ///   and additional information specifying the operation to perform.
+    /// * `sweep_model` - The `SweepModel` as currently shown by the client.
+    /// * `change` - The channel allocation change to apply.
     ///
     /// # Returns
-    /// A JSON string containing the serialized response. If the deserialization or processing
-    /// fails, an error response is returned.
+    /// An `evaluated_response` containing the modified `SweepModel`.
     ///
     /// # Behavior
-    /// 1. Attempts to deserialize the `ipc_data.json_value` into a `SweepModel`.
-    /// 2. If successful:
-    ///    - Parses the `ipc_data.additional_info` to determine the operation:
-    ///      - `"remove"`: Removes a channel specified by the third value in `additional_info`.
-    ///      - `"add"`: Adds a channel specified by the second value in `additional_info`.
-    ///      - `"update"`: Updates a channel using the second, third, and fourth values in `additional_info`.
-    ///    - Updates the `sweep` field of the `DataModel` with the modified `SweepModel`.
-    ///    - Serializes the modified `SweepModel` into a response.
-    /// 3. If deserialization fails:
-    ///    - Logs the error.
-    ///    - Returns an error response.
-    pub fn add_remove_channel(&mut self, ipc_data: IpcData) -> String {
-        match serde_json::from_str::<SweepModel>(ipc_data.json_value.as_str()) {
-            Ok(mut sweep_model) => {
-                //println!("Successfully deserialized JSON in server: {sweep_model:?}");
-
-                sweep_model.sweep_config.update_channel_devices();
-                let res: Vec<&str> = ipc_data.additional_info.split(',').collect();
-                if res[0] == "remove" {
-                    sweep_model.sweep_config.remove_channel(res[2].to_string());
-                } else if res[0] == "add" {
-                    sweep_model.sweep_config.add_channel(res[1].to_string());
-                } else if res[0] == "update" {
-                    sweep_model.sweep_config.update_channel(
-                        res[1].to_string(),
-                        res[2].to_string(),
-                        res[3].to_string(),
-                    );
-                }
-
-                // remove unused and invalid channels
-                sweep_model.sweep_config.remove_unused_invalid_channels();
-
-                sweep_model.sweep_config.evaluate();
+    /// 1. Updates the devices used by the channels of `sweep_model`.
+    /// 2. Applies the change:
+    ///    - `Remove`: Removes the channel using the device `chan_id`.
+    ///    - `Add`: Adds a channel of type `chan_type`.
+    ///    - `Update`: Moves a channel from device `old_chan_id` to `new_chan_id`.
+    /// 3. Removes unused and invalid channels, evaluates the configuration and updates the
+    ///    `sweep_model` field of the `DataModel`.
+    pub fn add_remove_channel(
+        &mut self,
+        mut sweep_model: SweepModel,
+        change: &ChannelChange,
+    ) -> Response {
+        let label = match change {
+            ChannelChange::Add { chan_type } => format!("Add {} channel", chan_type.as_str()),
+            ChannelChange::Remove { chan_type, .. } => {
+                format!("Remove {} channel", chan_type.as_str())
+            }
+            ChannelChange::Update { chan_type, .. } => {
+                format!("Change {} channel device", chan_type.as_str())
+            }
+        };
+        let sweep_config = &mut sweep_model.sweep_config;
+        sweep_config.update_channel_devices();
 
-                self.sweep_model = sweep_model.clone();
-                self.serialize_sweep_model(
-                    &sweep_model,
-                    "evaluated_response",
-                    "Processed sweep model",
-                )
+        match change {
+            ChannelChange::Remove { chan_id, .. } => {
+                sweep_config.remove_channel(chan_id.clone());
+            }
+            ChannelChange::Add { chan_type } => {
+                sweep_config.add_channel(chan_type.as_str().to_string());
             }
-            Err(e) => {
-                println!("Failed to deserialize JSON: {e}");
-                self.serialize_empty_response("error", "Failed to process sweep model")
+            ChannelChange::Update {
+                chan_type,
+                old_chan_id,
+                new_chan_id,
+            } => {
+                sweep_config.update_channel(
+                    chan_type.as_str().to_string(),
+                    old_chan_id.clone(),
+                    new_chan_id.clone(),
+                );
             }
         }
+
+        // remove unused and invalid channels
+        sweep_config.remove_unused_invalid_channels();
+
+        sweep_config.evaluate(&self.limits);
+
+        self.replace(&label, sweep_model.clone());
+        Response::EvaluatedResponse {
+            message: "Processed sweep model".to_string(),
+            sweep_model,
+        }
     }
 
-    fn serialize_sweep_model(
-        &self,
-        sweep_model: &SweepModel,
-        request_type: &str,
-        additional_info: &str,
-    ) -> String {
-        match serde_json::to_string(&json!({"sweep_model": sweep_model})) {
-            Ok(json_str) => {
-                let ipc_data = IpcData {
-                    request_type: request_type.to_string(),
-                    additional_info: additional_info.to_string(),
-                    json_value: json_str,
-                };
-                serde_json::to_string(&ipc_data).unwrap_or_else(|e| {
-                    println!("Failed to serialize IpcData: {e}");
-                    self.serialize_empty_response("error", "Serialization error")
-                })
-            }
-            Err(e) => {
-                println!("Failed to serialize sweep model: {e}");
-                self.serialize_empty_response("error", "Serialization error")
-            }
+    pub fn reset_sweep_config(&mut self) -> Response {
+        let previous = self.sweep_model.clone();
+        self.sweep_model.sweep_config.reset();
+        self.sweep_model.sweep_config.apply_defaults(&self.limits);
+        self.record("Reset sweep configuration", previous);
+        Response::ResetResponse {
+            message: "Sweep config reset".to_string(),
+            sweep_model: self.sweep_model.clone(),
         }
     }
 
-    fn serialize_empty_response(&self, request_type: &str, additional_info: &str) -> String {
-        let ipc_data = IpcData {
-            request_type: request_type.to_string(),
-            additional_info: additional_info.to_string(),
-            json_value: "{}".to_string(),
+    /// Restores the sweep model as it was before the most recent edit.
+    ///
+    /// # Returns
+    /// An `evaluated_response` containing the restored `SweepModel`, or an error response if
+    /// there is nothing to undo.
+    pub fn undo(&mut self) -> Response {
+        let Some(snapshot) = self.undo_stack.pop_back() else {
+            return nothing_to("undo");
         };
-        serde_json::to_string(&ipc_data).unwrap_or_else(|e| {
-            println!("Failed to serialize empty IpcData: {e}");
-            "{}".to_string()
-        })
+        let current = std::mem::replace(&mut self.sweep_model, snapshot.sweep_model);
+        self.redo_stack.push(Snapshot {
+            label: snapshot.label.clone(),
+            sweep_model: current,
+        });
+        self.restored(format!("Undid {}", snapshot.label))
     }
 
-    pub fn reset_sweep_config(&mut self) -> String {
-        self.sweep_model.sweep_config.reset();
-        let sweep_model_clone = self.sweep_model.clone();
-        self.serialize_sweep_model(&sweep_model_clone, "reset_response", "Sweep config reset")
+    /// Reapplies the most recently undone edit.
+    ///
+    /// # Returns
+    /// An `evaluated_response` containing the restored `SweepModel`, or an error response if
+    /// there is nothing to redo.
+    pub fn redo(&mut self) -> Response {
+        let Some(snapshot) = self.redo_stack.pop() else {
+            return nothing_to("redo");
+        };
+        let current = std::mem::replace(&mut self.sweep_model, snapshot.sweep_model);
+        self.push_undo(Snapshot {
+            label: snapshot.label.clone(),
+            sweep_model: current,
+        });
+        self.restored(format!("Redid {}", snapshot.label))
+    }
+
+    /// Lists the edits that can be undone (oldest first) and redone (next first).
+    pub fn history(&self) -> Response {
+        Response::History {
+            undo: self.undo_stack.iter().map(|s| s.label.clone()).collect(),
+            redo: self
+                .redo_stack
+                .iter()
+                .rev()
+                .map(|s| s.label.clone())
+                .collect(),
+        }
+    }
+
+    /// Makes `sweep_model` the current model, recording the previous one for undo.
+    fn replace(&mut self, label: &str, sweep_model: SweepModel) {
+        let previous = std::mem::replace(&mut self.sweep_model, sweep_model);
+        self.record(label, previous);
+    }
+
+    /// Records `previous` for undo unless it is identical to the current model.
+    fn record(&mut self, label: &str, previous: SweepModel) {
+        if previous == self.sweep_model {
+            return;
+        }
+        self.redo_stack.clear();
+        self.push_undo(Snapshot {
+            label: label.to_string(),
+            sweep_model: previous,
+        });
+    }
+
+    fn push_undo(&mut self, snapshot: Snapshot) {
+        if self.undo_stack.len() == HISTORY_LIMIT {
+            self.undo_stack.pop_front();
+        }
+        self.undo_stack.push_back(snapshot);
+    }
+
+    /// Re-synchronizes the device usage of a restored model and evaluates it.
+    fn restored(&mut self, message: String) -> Response {
+        let sweep_config = &mut self.sweep_model.sweep_config;
+        sweep_config.sync_device_usage();
+        sweep_config.evaluate(&self.limits);
+        Response::EvaluatedResponse {
+            message,
+            sweep_model: self.sweep_model.clone(),
+        }
+    }
+}
+
+fn nothing_to(action: &str) -> Response {
+    Response::Error(ProtocolError::new(
+        ErrorCode::ProcessingFailed,
+        format!("nothing to {action}"),
+    ))
+}
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod tests {
+    use super::*;
+    use crate::back_end::protocol::ChannelType;
+
+    fn sweep_points(sweep_model: &SweepModel) -> i32 {
+        sweep_model
+            .sweep_config
+            .sweep_global_parameters
+            .sweep_points
+            .value
+    }
+
+    /// Edits the sweep points of the current model like the UI does.
+    fn edit(data_model: &mut DataModel, points: i32) {
+        let mut sweep_model = data_model.sweep_model.clone();
+        sweep_model
+            .sweep_config
+            .sweep_global_parameters
+            .sweep_points
+            .value = points;
+        data_model.process_data_from_client(sweep_model);
+        assert_eq!(sweep_points(&data_model.sweep_model), points);
+    }
+
+    fn restored_points(response: &Response) -> i32 {
+        match response {
+            Response::EvaluatedResponse { sweep_model, .. } => sweep_points(sweep_model),
+            other => panic!("unexpected response {other:?}"),
+        }
+    }
+
+    fn history(data_model: &DataModel) -> (usize, usize) {
+        match data_model.history() {
+            Response::History { undo, redo } => (undo.len(), redo.len()),
+            other => panic!("unexpected response {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_undo_redo_order() {
+        let mut data_model = DataModel::new();
+        let initial = sweep_points(&data_model.sweep_model);
+        for points in [20, 30, 40] {
+            edit(&mut data_model, points);
+        }
+        assert_eq!(history(&data_model), (3, 0));
+
+        assert_eq!(restored_points(&data_model.undo()), 30);
+        assert_eq!(restored_points(&data_model.undo()), 20);
+        assert_eq!(history(&data_model), (1, 2));
+        assert_eq!(restored_points(&data_model.redo()), 30);
+        assert_eq!(restored_points(&data_model.undo()), 20);
+        assert_eq!(restored_points(&data_model.undo()), initial);
+        assert!(data_model.undo().is_error());
+        assert_eq!(sweep_points(&data_model.sweep_model), initial);
+
+        assert_eq!(restored_points(&data_model.redo()), 20);
+        assert_eq!(restored_points(&data_model.redo()), 30);
+        assert_eq!(restored_points(&data_model.redo()), 40);
+        assert!(data_model.redo().is_error());
+        assert_eq!(history(&data_model), (3, 0));
+    }
+
+    #[test]
+    fn test_edit_clears_redo() {
+        let mut data_model = DataModel::new();
+        edit(&mut data_model, 20);
+        edit(&mut data_model, 30);
+        data_model.undo();
+        assert_eq!(history(&data_model), (1, 1));
+
+        edit(&mut data_model, 50);
+        assert_eq!(history(&data_model), (2, 0));
+        assert!(data_model.redo().is_error());
+        assert_eq!(restored_points(&data_model.undo()), 20);
+    }
+
+    #[test]
+    fn test_no_op_edit_not_recorded() {
+        let mut data_model = DataModel::new();
+        edit(&mut data_model, 20);
+        data_model.undo();
+        edit(&mut data_model, 20);
+        assert_eq!(history(&data_model), (1, 0));
+
+        // the same model again, it does not clear the redo stack either
+        data_model.undo();
+        let sweep_model = data_model.sweep_model.clone();
+        data_model.process_data_from_client(sweep_model);
+        assert_eq!(history(&data_model), (0, 1));
+    }
+
+    #[test]
+    fn test_remove_channel() {
+        const SYSTEM: &str = r#"{"systems":[{"name":"sys1","isActive":true,"localNode":"MP5103",
+            "slots":[{"slotId":"slot[1]","module":"MSMU60-2"},
+            {"slotId":"slot[2]","module":"MSMU60-2"}],"nodes":[]}]}"#;
+
+        let mut data_model = DataModel::new();
+        assert!(!data_model.process_system_config(SYSTEM).is_error());
+        let sweep_model = data_model.sweep_model.clone();
+        data_model.add_remove_channel(
+            sweep_model,
+            &ChannelChange::Add {
+                chan_type: ChannelType::Bias,
+            },
+        );
+        let bias_channels = &data_model.sweep_model.sweep_config.bias_channels;
+        let count = bias_channels.len();
+        let chan_id = bias_channels[count - 1]
+            .common_chan_attributes
+            .device_id
+            .clone();
+        let remove = ChannelChange::Remove {
+            chan_type: ChannelType::Bias,
+            chan_id: chan_id.clone(),
+        };
+
+        // the REST API passes the model with the channel, the UI drops it beforehand
+        let with_channel = data_model.sweep_model.clone();
+        let mut without_channel = with_channel.clone();
+        without_channel.sweep_config.bias_channels.pop();
+        let mut removed = Vec::new();
+        for sweep_model in [with_channel, without_channel] {
+            let mut data_model = DataModel::new();
+            data_model.sweep_model = sweep_model.clone();
+            data_model.add_remove_channel(sweep_model, &remove);
+            let sweep_config = &data_model.sweep_model.sweep_config;
+            assert_eq!(sweep_config.bias_channels.len(), count - 1);
+            assert!(sweep_config
+                .bias_channels
+                .iter()
+                .all(|c| c.common_chan_attributes.device_id != chan_id));
+            assert!(sweep_config
+                .device_list
+                .iter()
+                .any(|device| device._id == chan_id && !device.in_use));
+            removed.push(data_model.sweep_model);
+        }
+        assert!(removed[0] == removed[1]);
+    }
+
+    #[test]
+    fn test_history_limit() {
+        let mut data_model = DataModel::new();
+        let edits = HISTORY_LIMIT + 5;
+        for points in 0..edits {
+            edit(&mut data_model, 100 + i32::try_from(points).unwrap());
+        }
+        assert_eq!(history(&data_model), (HISTORY_LIMIT, 0));
+
+        for _ in 0..HISTORY_LIMIT {
+            assert!(!data_model.undo().is_error());
+        }
+        assert!(data_model.undo().is_error());
+        // the oldest models were evicted
+        assert_eq!(
+            sweep_points(&data_model.sweep_model),
+            100 + i32::try_from(edits - HISTORY_LIMIT - 1).unwrap()
+        );
     }
 }
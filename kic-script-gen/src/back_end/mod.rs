@@ -1,3 +1,8 @@
+mod chunking;
 pub mod client_server;
 mod data_model;
 mod ipc_data;
+mod preview;
+pub mod protocol;
+pub mod rest_api;
+pub mod workspace;
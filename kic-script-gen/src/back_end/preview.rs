@@ -0,0 +1,192 @@
+use similar::{DiffOp, TextDiff};
+
+use super::protocol::{LineEdit, PreviewMode, Response};
+
+/// Tracks the script preview sent to a client, so that later renders can be sent as a
+/// line diff against it.
+#[derive(Debug, Default)]
+pub struct Preview {
+    pub mode: PreviewMode,
+    revision: u64,
+    /// Script of `revision` as last sent to the client.
+    last: Option<String>,
+}
+
+impl Preview {
+    /// Changes the preview mode, the next render is sent in full.
+    pub fn set_mode(&mut self, mode: PreviewMode) {
+        self.mode = mode;
+        self.last = None;
+    }
+
+    /// Forgets the last sent preview, e.g. because the client reconnected.
+    pub fn reset(&mut self) {
+        self.last = None;
+    }
+
+    /// Returns the message carrying `script` to the client, or `None` if the preview is off
+    /// or the script did not change.
+    pub fn update(&mut self, script: &str) -> Option<Response> {
+        let response = match (self.mode, self.last.as_deref()) {
+            (PreviewMode::Off, _) => return None,
+            (_, Some(last)) if last == script => return None,
+            (PreviewMode::Diff, Some(last)) => Response::ScriptPreviewDiff {
+                revision: self.revision.wrapping_add(1),
+                base_revision: self.revision,
+                edits: line_edits(last, script),
+            },
+            _ => Response::ScriptPreview {
+                revision: self.revision.wrapping_add(1),
+                script: script.to_string(),
+            },
+        };
+        self.revision = self.revision.wrapping_add(1);
+        self.last = Some(script.to_string());
+        Some(response)
+    }
+}
+
+/// Computes the line edits turning `old` into `new`. Edits are sorted by line and refer
+/// to line numbers of `old`.
+pub fn line_edits(old: &str, new: &str) -> Vec<LineEdit> {
+    let diff = TextDiff::from_lines(old, new);
+    let new_lines: Vec<&str> = diff.new_slices().to_vec();
+    diff.ops()
+        .iter()
+        .filter_map(|op| {
+            let (start, delete, inserted) = match *op {
+                DiffOp::Equal { .. } => return None,
+                DiffOp::Delete {
+                    old_index, old_len, ..
+                } => (old_index, old_len, 0..0),
+                DiffOp::Insert {
+                    old_index,
+                    new_index,
+                    new_len,
+                } => (old_index, 0, new_index..new_index + new_len),
+                DiffOp::Replace {
+                    old_index,
+                    old_len,
+                    new_index,
+                    new_len,
+                } => (old_index, old_len, new_index..new_index + new_len),
+            };
+            Some(LineEdit {
+                start,
+                delete,
+                insert: new_lines[inserted].concat(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod tests {
+    use super::*;
+
+    /// Applies `edits` to `old` as described on [`LineEdit`], from the last edit to the first so
+    /// that line numbers of `old` stay valid.
+    fn apply(old: &str, edits: &[LineEdit]) -> String {
+        let mut lines: Vec<String> = old.split_inclusive('\n').map(String::from).collect();
+        for edit in edits.iter().rev() {
+            lines.splice(edit.start..edit.start + edit.delete, [edit.insert.clone()]);
+        }
+        lines.concat()
+    }
+
+    fn check(old: &str, new: &str) -> Vec<LineEdit> {
+        let edits = line_edits(old, new);
+        assert!(edits.windows(2).all(|w| w[0].start < w[1].start));
+        assert_eq!(apply(old, &edits), new);
+        edits
+    }
+
+    #[test]
+    fn test_insert() {
+        let edits = check("a\nb\nc\n", "a\nb\nx\ny\nc\n");
+        assert_eq!(
+            edits,
+            vec![LineEdit {
+                start: 2,
+                delete: 0,
+                insert: "x\ny\n".to_string()
+            }]
+        );
+        check("a\n", "x\na\n");
+        check("a\n", "a\nx\n");
+    }
+
+    #[test]
+    fn test_delete() {
+        let edits = check("a\nb\nc\nd\n", "a\nd\n");
+        assert_eq!(
+            edits,
+            vec![LineEdit {
+                start: 1,
+                delete: 2,
+                insert: String::new()
+            }]
+        );
+        check("a\nb\n", "");
+    }
+
+    #[test]
+    fn test_replace() {
+        let edits = check("a\nb\nc\n", "a\nx\nc\n");
+        assert_eq!(
+            edits,
+            vec![LineEdit {
+                start: 1,
+                delete: 1,
+                insert: "x\n".to_string()
+            }]
+        );
+        check("a\nb\nc\nd\ne\n", "x\nb\nc\ny\nz\ne\nf\n");
+    }
+
+    #[test]
+    fn test_empty_old() {
+        let edits = check("", "a\nb\n");
+        assert_eq!(
+            edits,
+            vec![LineEdit {
+                start: 0,
+                delete: 0,
+                insert: "a\nb\n".to_string()
+            }]
+        );
+        assert!(check("", "").is_empty());
+    }
+
+    #[test]
+    fn test_trailing_newline() {
+        check("a\nb", "a\nb\n");
+        check("a\nb\n", "a\nb");
+        check("a\r\nb\r\n", "a\r\nb");
+    }
+
+    #[test]
+    fn test_update() {
+        let mut preview = Preview {
+            mode: PreviewMode::Diff,
+            ..Default::default()
+        };
+        assert!(matches!(
+            preview.update("a\n"),
+            Some(Response::ScriptPreview { revision: 1, .. })
+        ));
+        assert!(preview.update("a\n").is_none());
+        match preview.update("a\nb\n") {
+            Some(Response::ScriptPreviewDiff {
+                revision,
+                base_revision,
+                edits,
+            }) => {
+                assert_eq!((revision, base_revision), (2, 1));
+                assert_eq!(apply("a\n", &edits), "a\nb\n");
+            }
+            other => panic!("unexpected response {other:?}"),
+        }
+    }
+}
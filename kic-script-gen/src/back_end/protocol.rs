@@ -0,0 +1,1142 @@
+//! Typed messages exchanged with the script generation UI (over the WebSocket) and with
+//! the host extension (over stdin/stdout).
+//!
+//! Every message is wrapped in an envelope carrying [`PROTOCOL_VERSION`]. Messages without a
+//! `protocol_version` field are treated as the legacy `IpcData`/stdin shapes and converted
+//! into the typed requests, and replies to such clients are sent back as `IpcData`.
+
+use std::io::Write;
+
+use schemars::JsonSchema;
+use script_gen_manager::device::Device;
+use script_gen_manager::error::ScriptGenError;
+use script_gen_manager::model::sweep_data::list_csv::{ClampedValue, ListTarget};
+use script_gen_manager::model::{sweep_data::sweep_model::SweepModel, system_info::SystemInfo};
+use script_gen_manager::project::DeviceMismatch;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use xml_handler::templates::LoadedResource;
+
+use super::{client_server::ScriptPath, ipc_data::IpcData};
+use crate::logging::LogRecord;
+
+/// Version of the message protocol implemented by this server.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// Workspace addressed by messages that do not name one.
+pub const DEFAULT_WORKSPACE: &str = "default";
+
+/// The shape in which a client talks to the server, so replies can be sent the same way.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WireFormat {
+    /// Untyped `IpcData` messages and ad hoc stdin lines.
+    Legacy,
+    /// [`Envelope`] messages carrying a protocol version.
+    Versioned,
+}
+
+/// Wraps a request or response together with the protocol version.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+pub struct Envelope<T> {
+    pub protocol_version: u32,
+    /// Workspace the message is addressed to or originates from. Host messages without a
+    /// workspace address [`DEFAULT_WORKSPACE`], WebSocket messages belong to the workspace
+    /// of their connection.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub workspace: Option<String>,
+    #[serde(flatten)]
+    pub message: T,
+}
+
+impl<T> Envelope<T> {
+    pub fn new(message: T) -> Self {
+        Envelope {
+            protocol_version: PROTOCOL_VERSION,
+            workspace: None,
+            message,
+        }
+    }
+
+    pub fn for_workspace(message: T, workspace: &str) -> Self {
+        Envelope {
+            workspace: Some(workspace.to_string()),
+            ..Envelope::new(message)
+        }
+    }
+}
+
+/// Type of a channel in the sweep configuration.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ChannelType {
+    Bias,
+    Step,
+    Sweep,
+}
+
+impl ChannelType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ChannelType::Bias => "bias",
+            ChannelType::Step => "step",
+            ChannelType::Sweep => "sweep",
+        }
+    }
+
+    fn parse(value: &str) -> Result<Self, ProtocolError> {
+        match value {
+            "bias" => Ok(ChannelType::Bias),
+            "step" => Ok(ChannelType::Step),
+            "sweep" => Ok(ChannelType::Sweep),
+            _ => Err(ProtocolError::new(
+                ErrorCode::InvalidArgument,
+                format!("unknown channel type '{value}'"),
+            )),
+        }
+    }
+}
+
+/// A channel allocation change.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+#[serde(rename_all = "snake_case", tag = "action")]
+pub enum ChannelChange {
+    /// Adds a channel of the given type on the first free device.
+    Add { chan_type: ChannelType },
+    /// Releases the device with ID `chan_id` used by a channel of the given type.
+    Remove {
+        chan_type: ChannelType,
+        chan_id: String,
+    },
+    /// Moves a channel from device `old_chan_id` to device `new_chan_id`.
+    Update {
+        chan_type: ChannelType,
+        old_chan_id: String,
+        new_chan_id: String,
+    },
+}
+
+impl ChannelChange {
+    /// Parses the legacy comma separated `additional_info` of a `reallocation` request,
+    /// e.g. `add,bias`, `remove,step,<chan_id>` or `update,sweep,<old_chan_id>,<new_chan_id>`.
+    pub fn from_legacy(additional_info: &str) -> Result<Self, ProtocolError> {
+        let parts: Vec<&str> = additional_info.split(',').map(str::trim).collect();
+        let invalid = || {
+            ProtocolError::new(
+                ErrorCode::InvalidArgument,
+                format!("malformed channel change '{additional_info}'"),
+            )
+        };
+        match parts.as_slice() {
+            ["add", chan_type] => Ok(ChannelChange::Add {
+                chan_type: ChannelType::parse(chan_type)?,
+            }),
+            ["remove", chan_type, chan_id] if !chan_id.is_empty() => Ok(ChannelChange::Remove {
+                chan_type: ChannelType::parse(chan_type)?,
+                chan_id: chan_id.to_string(),
+            }),
+            ["update", chan_type, old_chan_id, new_chan_id]
+                if !old_chan_id.is_empty() && !new_chan_id.is_empty() =>
+            {
+                Ok(ChannelChange::Update {
+                    chan_type: ChannelType::parse(chan_type)?,
+                    old_chan_id: old_chan_id.to_string(),
+                    new_chan_id: new_chan_id.to_string(),
+                })
+            }
+            _ => Err(invalid()),
+        }
+    }
+}
+
+/// Which script previews are pushed to the UI after each generation.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PreviewMode {
+    /// No previews are sent.
+    #[default]
+    Off,
+    /// The full script is sent every time it changes.
+    Full,
+    /// The full script is sent once, then line diffs against the previous preview.
+    Diff,
+}
+
+/// Replaces `delete` lines starting at line `start` (0-based) of the previous preview with
+/// `insert`, which holds complete lines including their line endings.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq, Eq)]
+pub struct LineEdit {
+    pub start: usize,
+    pub delete: usize,
+    pub insert: String,
+}
+
+/// A script template loaded by the catalog and where it came from: `embedded`, or the path of
+/// the file that overrides it.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq, Eq)]
+pub struct TemplateSource {
+    pub name: String,
+    pub origin: String,
+}
+
+impl From<&LoadedResource> for TemplateSource {
+    fn from(resource: &LoadedResource) -> Self {
+        TemplateSource {
+            name: resource.name.clone(),
+            origin: resource.origin.to_string(),
+        }
+    }
+}
+
+/// Requests sent by the UI over the WebSocket.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+#[serde(rename_all = "snake_case", tag = "request_type")]
+pub enum ClientRequest {
+    /// Asks for the instrument data.
+    GetData,
+    /// Evaluates the given sweep model and makes it the current one.
+    EvaluateData { sweep_model: SweepModel },
+    /// Applies a channel allocation change to the given sweep model.
+    Reallocation {
+        sweep_model: SweepModel,
+        change: ChannelChange,
+    },
+    /// Generates the script (if needed) and asks the host to open it.
+    OpenScript,
+    /// Restores the sweep model as it was before the most recent edit.
+    Undo,
+    /// Reapplies the most recently undone edit.
+    Redo,
+    /// Lists the edits that can be undone and redone.
+    History,
+    /// Selects which script previews are pushed after each generation.
+    Preview { mode: PreviewMode },
+    /// Asks for the server version, loaded templates, devices and recent errors.
+    GetDiagnostics,
+    /// Saves the workspace as a project file at `path`.
+    SaveProject { path: String },
+    /// Opens the project file, or the configuration embedded in the generated script, at `path`.
+    OpenProject { path: String },
+    /// Replaces the step or sweep lists with the values of a CSV/TSV file.
+    ImportLists { target: ListTarget, csv: String },
+    /// Asks for the step or sweep lists as CSV.
+    ExportLists { target: ListTarget },
+}
+
+impl TryFrom<IpcData> for ClientRequest {
+    type Error = ProtocolError;
+
+    fn try_from(ipc_data: IpcData) -> Result<Self, Self::Error> {
+        match ipc_data.request_type.as_str() {
+            "get_data" => Ok(ClientRequest::GetData),
+            "evaluate_data" => Ok(ClientRequest::EvaluateData {
+                sweep_model: parse_sweep_model(&ipc_data.json_value)?,
+            }),
+            "reallocation" => Ok(ClientRequest::Reallocation {
+                change: ChannelChange::from_legacy(&ipc_data.additional_info)?,
+                sweep_model: parse_sweep_model(&ipc_data.json_value)?,
+            }),
+            "open_script" => Ok(ClientRequest::OpenScript),
+            "undo" => Ok(ClientRequest::Undo),
+            "redo" => Ok(ClientRequest::Redo),
+            "history" => Ok(ClientRequest::History),
+            "get_diagnostics" => Ok(ClientRequest::GetDiagnostics),
+            "save_project" => Ok(ClientRequest::SaveProject {
+                path: ipc_data.additional_info,
+            }),
+            "open_project" => Ok(ClientRequest::OpenProject {
+                path: ipc_data.additional_info,
+            }),
+            "import_lists" => Ok(ClientRequest::ImportLists {
+                target: parse_list_target(&ipc_data.additional_info)?,
+                csv: ipc_data.json_value,
+            }),
+            "export_lists" => Ok(ClientRequest::ExportLists {
+                target: parse_list_target(&ipc_data.additional_info)?,
+            }),
+            "preview" => Ok(ClientRequest::Preview {
+                mode: serde_json::from_value(Value::String(ipc_data.additional_info.clone()))
+                    .map_err(|_| {
+                        ProtocolError::new(
+                            ErrorCode::InvalidArgument,
+                            format!("unknown preview mode '{}'", ipc_data.additional_info),
+                        )
+                    })?,
+            }),
+            other => Err(ProtocolError::new(
+                ErrorCode::UnknownRequest,
+                format!("unknown request type '{other}'"),
+            )),
+        }
+    }
+}
+
+/// Requests sent by the host extension on stdin.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+#[serde(rename_all = "snake_case", tag = "request_type")]
+pub enum HostRequest {
+    /// Stops the server.
+    Shutdown,
+    /// Creates the workspace named in the envelope. Its UI is served at
+    /// `<url>/?workspace=<id>`, a workspace takes a single UI connection.
+    OpenWorkspace,
+    /// Closes the workspace named in the envelope and its UI connection.
+    CloseWorkspace,
+    /// Sets the session name and folder the script is written to.
+    ScriptPath { session: String, folder: String },
+    /// Provides the connected system configuration.
+    SystemConfig { systems: Vec<SystemInfo> },
+    /// Provides the line frequency of the instrument in Hz.
+    LineFrequency { line_frequency: f64 },
+    /// Refreshes the instrument data.
+    Refresh,
+    /// Resets the sweep configuration.
+    Reset,
+    /// Restores a previously saved sweep model.
+    SavedConfig { sweep_model: Box<SweepModel> },
+    /// Asks for the server version, loaded templates, devices and recent errors.
+    GetDiagnostics,
+    /// Saves the workspace as a project file at `path`.
+    SaveProject { path: String },
+    /// Opens the project file, or the configuration embedded in the generated script, at `path`.
+    OpenProject { path: String },
+}
+
+impl HostRequest {
+    /// Converts a legacy stdin line into a typed request.
+    ///
+    /// Plain commands (`shutdown`, `refresh`, `reset`, `get_diagnostics`) may be sent bare or
+    /// as JSON strings.
+    /// Structured messages are recognized by their keys rather than by searching the text,
+    /// so that a value which happens to contain a command name is not misrouted.
+    fn from_legacy(line: &str, value: Option<Value>) -> Result<Self, ProtocolError> {
+        let command = match &value {
+            Some(Value::String(s)) => Some(s.trim()),
+            None => Some(line),
+            _ => None,
+        };
+        if let Some(command) = command {
+            return match command {
+                "shutdown" => Ok(HostRequest::Shutdown),
+                "refresh" => Ok(HostRequest::Refresh),
+                "reset" => Ok(HostRequest::Reset),
+                "get_diagnostics" => Ok(HostRequest::GetDiagnostics),
+                _ => Err(ProtocolError::new(
+                    ErrorCode::UnknownRequest,
+                    format!("unknown command '{command}'"),
+                )),
+            };
+        }
+
+        let Some(Value::Object(map)) = value else {
+            return Err(ProtocolError::new(
+                ErrorCode::MalformedMessage,
+                "expected a JSON object or a command",
+            ));
+        };
+
+        if map.contains_key("session") && map.contains_key("folder") {
+            let path: ScriptPath = from_value(Value::Object(map))?;
+            Ok(HostRequest::ScriptPath {
+                session: path.session,
+                folder: path.folder,
+            })
+        } else if let Some(systems) = map.get("systems") {
+            Ok(HostRequest::SystemConfig {
+                systems: from_value(systems.clone())?,
+            })
+        } else if let Some(freq) = map.get("lineFrequency") {
+            let line_frequency = freq.as_f64().ok_or_else(|| {
+                ProtocolError::new(
+                    ErrorCode::InvalidArgument,
+                    "'lineFrequency' must be a number",
+                )
+            })?;
+            Ok(HostRequest::LineFrequency { line_frequency })
+        } else if map.contains_key("request_type") {
+            // both process_data_from_client and process_data_from_saved_config expect the
+            // sweep_config, the saved JSON wraps it as {"sweep_model": {"sweep_config": ...}}
+            let ipc_data: IpcData = from_value(Value::Object(map))?;
+            let json_obj: Value = serde_json::from_str(&ipc_data.json_value).map_err(|e| {
+                ProtocolError::new(
+                    ErrorCode::MalformedMessage,
+                    format!("invalid json_value: {e}"),
+                )
+            })?;
+            let sweep_model = json_obj.get("sweep_model").cloned().ok_or_else(|| {
+                ProtocolError::new(
+                    ErrorCode::InvalidArgument,
+                    "'sweep_model' field not found in json_value",
+                )
+            })?;
+            Ok(HostRequest::SavedConfig {
+                sweep_model: from_value(sweep_model)?,
+            })
+        } else {
+            Err(ProtocolError::new(
+                ErrorCode::UnknownRequest,
+                "unrecognized message",
+            ))
+        }
+    }
+}
+
+/// Responses and events sent by the server.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+#[serde(rename_all = "snake_case", tag = "response_type")]
+pub enum Response {
+    /// The sweep model was created from a new system configuration.
+    InitialResponse {
+        message: String,
+        sweep_model: SweepModel,
+    },
+    /// The sweep model was evaluated.
+    EvaluatedResponse {
+        message: String,
+        sweep_model: SweepModel,
+    },
+    /// The sweep model was reset.
+    ResetResponse {
+        message: String,
+        sweep_model: SweepModel,
+    },
+    /// The script is ready to be opened.
+    OpenScript,
+    /// Labels of the edits that can be undone (oldest first) and redone (next first).
+    History {
+        undo: Vec<String>,
+        redo: Vec<String>,
+    },
+    /// No devices were found in the system configuration.
+    EmptySystemConfigError { message: String },
+    /// The request could not be processed.
+    Error(ProtocolError),
+    /// The workspace named in the envelope was created.
+    WorkspaceOpened,
+    /// The workspace named in the envelope was closed.
+    WorkspaceClosed,
+    /// A script generation finished. `hash` is the SHA-256 of the script, `unchanged` is set
+    /// if the file already had the same content, `error` is set if the script could not be
+    /// generated or written.
+    ScriptGenerated {
+        path: Option<String>,
+        hash: Option<String>,
+        unchanged: bool,
+        duration_ms: u64,
+        error: Option<ProtocolError>,
+    },
+    /// The full generated script.
+    ScriptPreview { revision: u64, script: String },
+    /// Changes turning preview `base_revision` into `revision`, in line order and relative
+    /// to the lines of `base_revision`.
+    ScriptPreviewDiff {
+        revision: u64,
+        base_revision: u64,
+        edits: Vec<LineEdit>,
+    },
+    /// The web server is listening at `url`.
+    ServerStarted {
+        url: String,
+        host: String,
+        port: u16,
+    },
+    /// The workspace was saved as a project file.
+    ProjectSaved { path: String },
+    /// A project file was opened, the sweep model is sent as an `evaluated_response`.
+    /// `device_mismatches` lists the devices of the project that do not match the connected
+    /// hardware, it is empty while no system configuration was received.
+    ProjectOpened {
+        path: String,
+        generator_version: String,
+        device_mismatches: Vec<DeviceMismatch>,
+    },
+    /// List values were imported, the sweep model is sent as an `evaluated_response`.
+    /// `clamped` lists the values that were changed to fit the source range or limits.
+    ListsImported {
+        target: ListTarget,
+        points: usize,
+        channels: Vec<String>,
+        clamped: Vec<ClampedValue>,
+    },
+    /// The step or sweep lists as CSV.
+    ListsExported { target: ListTarget, csv: String },
+    /// State of the server for troubleshooting. `template_groups` lists the script template
+    /// groups loaded from the catalog, `templates` where each template was loaded from,
+    /// `recent_errors` the last warnings and errors logged (oldest first). The protocol
+    /// version is the one of the envelope.
+    Diagnostics {
+        version: String,
+        template_groups: Vec<String>,
+        templates: Vec<TemplateSource>,
+        devices: Vec<Device>,
+        recent_errors: Vec<LogRecord>,
+    },
+}
+
+impl Response {
+    /// Returns true for responses reporting a failure.
+    pub fn is_error(&self) -> bool {
+        matches!(
+            self,
+            Response::Error(_) | Response::EmptySystemConfigError { .. }
+        )
+    }
+
+    /// Converts the response into the legacy `IpcData` shape.
+    pub fn to_ipc_data(&self) -> IpcData {
+        let (request_type, additional_info, json_value) = match self {
+            Response::InitialResponse {
+                message,
+                sweep_model,
+            } => ("initial_response", message.clone(), wrap(sweep_model)),
+            Response::EvaluatedResponse {
+                message,
+                sweep_model,
+            } => ("evaluated_response", message.clone(), wrap(sweep_model)),
+            Response::ResetResponse {
+                message,
+                sweep_model,
+            } => ("reset_response", message.clone(), wrap(sweep_model)),
+            Response::OpenScript => ("open_script", String::new(), "{}".to_string()),
+            Response::History { undo, redo } => (
+                "history",
+                String::new(),
+                json!({ "undo": undo, "redo": redo }).to_string(),
+            ),
+            Response::WorkspaceOpened => ("workspace_opened", String::new(), "{}".to_string()),
+            Response::WorkspaceClosed => ("workspace_closed", String::new(), "{}".to_string()),
+            Response::EmptySystemConfigError { message } => (
+                "empty_system_config_error",
+                message.clone(),
+                "{}".to_string(),
+            ),
+            Response::Error(error) => ("error", error.message.clone(), "{}".to_string()),
+            Response::ScriptGenerated {
+                path,
+                hash,
+                unchanged,
+                duration_ms,
+                error,
+            } => (
+                "script_generated",
+                path.clone().unwrap_or_default(),
+                json!({
+                    "hash": hash,
+                    "unchanged": unchanged,
+                    "duration_ms": duration_ms,
+                    "error": error,
+                })
+                .to_string(),
+            ),
+            Response::ScriptPreview { revision, script } => (
+                "script_preview",
+                revision.to_string(),
+                json!({ "script": script }).to_string(),
+            ),
+            Response::ScriptPreviewDiff {
+                revision,
+                base_revision,
+                edits,
+            } => (
+                "script_preview_diff",
+                revision.to_string(),
+                json!({ "base_revision": base_revision, "edits": edits }).to_string(),
+            ),
+            Response::ServerStarted { url, host, port } => (
+                "server_started",
+                url.clone(),
+                json!({ "host": host, "port": port }).to_string(),
+            ),
+            Response::ProjectSaved { path } => ("project_saved", path.clone(), "{}".to_string()),
+            Response::ProjectOpened {
+                path,
+                generator_version,
+                device_mismatches,
+            } => (
+                "project_opened",
+                path.clone(),
+                json!({
+                    "generator_version": generator_version,
+                    "device_mismatches": device_mismatches,
+                })
+                .to_string(),
+            ),
+            Response::ListsImported {
+                target,
+                points,
+                channels,
+                clamped,
+            } => (
+                "lists_imported",
+                target.as_str().to_string(),
+                json!({ "points": points, "channels": channels, "clamped": clamped }).to_string(),
+            ),
+            Response::ListsExported { target, csv } => {
+                ("lists_exported", target.as_str().to_string(), csv.clone())
+            }
+            Response::Diagnostics {
+                version,
+                template_groups,
+                templates,
+                devices,
+                recent_errors,
+            } => (
+                "diagnostics",
+                version.clone(),
+                json!({
+                    "protocol_version": PROTOCOL_VERSION,
+                    "template_groups": template_groups,
+                    "templates": templates,
+                    "devices": devices,
+                    "recent_errors": recent_errors,
+                })
+                .to_string(),
+            ),
+        };
+        IpcData {
+            request_type: request_type.to_string(),
+            additional_info,
+            json_value,
+        }
+    }
+
+    /// Serializes the response in the given wire format.
+    pub fn to_json(&self, format: WireFormat) -> String {
+        let res = match format {
+            WireFormat::Legacy => serde_json::to_string(&self.to_ipc_data()),
+            WireFormat::Versioned => serde_json::to_string(&Envelope::new(self)),
+        };
+        res.unwrap_or_else(serialization_error)
+    }
+
+    /// Serializes a response sent to the host about `workspace` in the given wire format.
+    pub fn to_host_json(&self, format: WireFormat, workspace: &str) -> String {
+        let res = match format {
+            WireFormat::Legacy => serde_json::to_string(&self.to_ipc_data()),
+            WireFormat::Versioned => {
+                serde_json::to_string(&Envelope::for_workspace(self, workspace))
+            }
+        };
+        res.unwrap_or_else(serialization_error)
+    }
+}
+
+/// Category of a [`ProtocolError`].
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    /// The message is not valid JSON or does not have the expected shape.
+    MalformedMessage,
+    /// The message uses a protocol version this server does not implement.
+    UnsupportedVersion,
+    /// The request type is not known.
+    UnknownRequest,
+    /// A field of the request has an invalid value.
+    InvalidArgument,
+    /// The request was well formed but could not be carried out.
+    ProcessingFailed,
+    /// Script template groups are missing from the catalog.
+    MissingTemplates,
+    /// A script template cannot be loaded or rendered.
+    InvalidTemplate,
+    /// No output path is set for the script.
+    NoOutputPath,
+    /// The script could not be written.
+    WriteFailed,
+}
+
+/// A structured error reply.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+pub struct ProtocolError {
+    pub code: ErrorCode,
+    pub message: String,
+}
+
+impl ProtocolError {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        ProtocolError {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+impl From<&ScriptGenError> for ProtocolError {
+    fn from(error: &ScriptGenError) -> Self {
+        let code = match error {
+            ScriptGenError::IOError { .. } => ErrorCode::WriteFailed,
+            ScriptGenError::MissingTemplateGroups { .. } => ErrorCode::MissingTemplates,
+            ScriptGenError::CatalogError { .. } | ScriptGenError::TemplateError { .. } => {
+                ErrorCode::InvalidTemplate
+            }
+            ScriptGenError::NoOutputPath => ErrorCode::NoOutputPath,
+            ScriptGenError::SerializationError { .. } => ErrorCode::ProcessingFailed,
+        };
+        ProtocolError::new(code, error.to_string())
+    }
+}
+
+impl std::fmt::Display for ProtocolError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}: {}", self.code, self.message)
+    }
+}
+
+/// Parses a WebSocket message from the UI.
+///
+/// # Errors
+///
+/// Returns a [`ProtocolError`] if the message is malformed, uses an unsupported protocol
+/// version or names an unknown request.
+pub fn parse_client_request(text: &str) -> Result<(ClientRequest, WireFormat), ProtocolError> {
+    let value: Value = serde_json::from_str(text).map_err(|e| {
+        ProtocolError::new(ErrorCode::MalformedMessage, format!("invalid JSON: {e}"))
+    })?;
+    if value.get("protocol_version").is_some() {
+        check_version(&value)?;
+        let envelope: Envelope<ClientRequest> = from_value(value)?;
+        Ok((envelope.message, WireFormat::Versioned))
+    } else {
+        let ipc_data: IpcData = from_value(value)?;
+        Ok((ClientRequest::try_from(ipc_data)?, WireFormat::Legacy))
+    }
+}
+
+/// A request received from the host together with the workspace it addresses.
+#[derive(Debug, Clone)]
+pub struct HostMessage {
+    pub request: HostRequest,
+    pub format: WireFormat,
+    pub workspace: String,
+}
+
+/// Parses a line received from the host on stdin.
+///
+/// # Errors
+///
+/// Returns a [`ProtocolError`] if the line is malformed, uses an unsupported protocol
+/// version or names an unknown request.
+pub fn parse_host_request(line: &str) -> Result<HostMessage, ProtocolError> {
+    let line = line.trim();
+    let value = serde_json::from_str::<Value>(line).ok();
+    match value {
+        Some(value) if is_versioned(&value) => {
+            check_version(&value)?;
+            let envelope: Envelope<HostRequest> = from_value(value)?;
+            Ok(HostMessage {
+                request: envelope.message,
+                format: WireFormat::Versioned,
+                workspace: envelope
+                    .workspace
+                    .unwrap_or_else(|| DEFAULT_WORKSPACE.to_string()),
+            })
+        }
+        value => Ok(HostMessage {
+            request: HostRequest::from_legacy(line, value)?,
+            format: WireFormat::Legacy,
+            workspace: DEFAULT_WORKSPACE.to_string(),
+        }),
+    }
+}
+
+/// Returns the wire format of a line received from the host, so that a reply to a line that
+/// cannot be parsed uses the format of the sender.
+pub fn host_line_format(line: &str) -> WireFormat {
+    match serde_json::from_str::<Value>(line.trim()) {
+        Ok(value) if is_versioned(&value) => WireFormat::Versioned,
+        _ => WireFormat::Legacy,
+    }
+}
+
+fn is_versioned(value: &Value) -> bool {
+    value.get("protocol_version").is_some()
+}
+
+/// Returns the JSON Schema of every message of the protocol.
+pub fn json_schema() -> Value {
+    json!({
+        "protocol_version": PROTOCOL_VERSION,
+        "client_request": schemars::schema_for!(Envelope<ClientRequest>),
+        "host_request": schemars::schema_for!(Envelope<HostRequest>),
+        "response": schemars::schema_for!(Envelope<Response>),
+    })
+}
+
+fn check_version(value: &Value) -> Result<(), ProtocolError> {
+    match value.get("protocol_version").and_then(Value::as_u64) {
+        Some(version) if version == u64::from(PROTOCOL_VERSION) => Ok(()),
+        Some(version) => Err(ProtocolError::new(
+            ErrorCode::UnsupportedVersion,
+            format!("protocol version {version} is not supported, expected {PROTOCOL_VERSION}"),
+        )),
+        None => Err(ProtocolError::new(
+            ErrorCode::MalformedMessage,
+            "'protocol_version' must be an unsigned integer",
+        )),
+    }
+}
+
+/// Writes a protocol line for the host to stdout.
+///
+/// Stdout is reserved for messages read by the host, everything else is logged.
+pub fn write_to_host(line: &str) {
+    let mut stdout = std::io::stdout().lock();
+    if let Err(e) = writeln!(stdout, "{line}").and_then(|()| stdout.flush()) {
+        tracing::error!("Failed to write to stdout: {e}");
+    }
+}
+
+fn serialization_error(e: serde_json::Error) -> String {
+    tracing::error!("Failed to serialize response: {e}");
+    json!({"request_type": "error", "additional_info": "Serialization error", "json_value": "{}"})
+        .to_string()
+}
+
+fn from_value<T: serde::de::DeserializeOwned>(value: Value) -> Result<T, ProtocolError> {
+    serde_json::from_value(value)
+        .map_err(|e| ProtocolError::new(ErrorCode::MalformedMessage, e.to_string()))
+}
+
+fn parse_sweep_model(json_value: &str) -> Result<SweepModel, ProtocolError> {
+    serde_json::from_str(json_value).map_err(|e| {
+        ProtocolError::new(
+            ErrorCode::InvalidArgument,
+            format!("invalid sweep model: {e}"),
+        )
+    })
+}
+
+fn parse_list_target(target: &str) -> Result<ListTarget, ProtocolError> {
+    match target {
+        "step" => Ok(ListTarget::Step),
+        "sweep" => Ok(ListTarget::Sweep),
+        other => Err(ProtocolError::new(
+            ErrorCode::InvalidArgument,
+            format!("unknown list target '{other}', expected 'step' or 'sweep'"),
+        )),
+    }
+}
+
+fn wrap(sweep_model: &SweepModel) -> String {
+    json!({ "sweep_model": sweep_model }).to_string()
+}
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod tests {
+    use super::*;
+
+    fn legacy(request_type: &str, additional_info: &str, json_value: &str) -> String {
+        serde_json::to_string(&IpcData {
+            request_type: request_type.to_string(),
+            additional_info: additional_info.to_string(),
+            json_value: json_value.to_string(),
+        })
+        .unwrap()
+    }
+
+    fn client_error(text: &str) -> ProtocolError {
+        parse_client_request(text).unwrap_err()
+    }
+
+    fn host_error(line: &str) -> ProtocolError {
+        parse_host_request(line).unwrap_err()
+    }
+
+    #[test]
+    fn test_channel_change_from_legacy() {
+        assert!(matches!(
+            ChannelChange::from_legacy("add,bias"),
+            Ok(ChannelChange::Add {
+                chan_type: ChannelType::Bias
+            })
+        ));
+        assert!(matches!(
+            ChannelChange::from_legacy(" remove , step , smu1 "),
+            Ok(ChannelChange::Remove { chan_type: ChannelType::Step, ref chan_id }) if chan_id == "smu1"
+        ));
+        assert!(matches!(
+            ChannelChange::from_legacy("update,sweep,smu1,smu2"),
+            Ok(ChannelChange::Update {
+                chan_type: ChannelType::Sweep,
+                ref old_chan_id,
+                ref new_chan_id,
+            }) if old_chan_id == "smu1" && new_chan_id == "smu2"
+        ));
+
+        for malformed in [
+            "",
+            "add",
+            "add,bias,smu1",
+            "remove,step,",
+            "update,sweep,smu1,",
+            "move,bias",
+        ] {
+            let error = ChannelChange::from_legacy(malformed).unwrap_err();
+            assert_eq!(error.code, ErrorCode::InvalidArgument, "{malformed}");
+            assert!(error.message.contains("malformed channel change"));
+        }
+        let error = ChannelChange::from_legacy("add,pulse").unwrap_err();
+        assert_eq!(error.message, "unknown channel type 'pulse'");
+    }
+
+    #[test]
+    fn test_parse_legacy_client_request() {
+        let (request, format) = parse_client_request(&legacy("get_data", "", "")).unwrap();
+        assert!(matches!(request, ClientRequest::GetData));
+        assert_eq!(format, WireFormat::Legacy);
+
+        let sweep_model = serde_json::to_string(&SweepModel::new()).unwrap();
+        let (request, _) =
+            parse_client_request(&legacy("reallocation", "add,step", &sweep_model)).unwrap();
+        assert!(matches!(
+            request,
+            ClientRequest::Reallocation {
+                change: ChannelChange::Add {
+                    chan_type: ChannelType::Step
+                },
+                ..
+            }
+        ));
+        let (request, _) = parse_client_request(&legacy("preview", "diff", "")).unwrap();
+        assert!(matches!(
+            request,
+            ClientRequest::Preview {
+                mode: PreviewMode::Diff
+            }
+        ));
+        let (request, _) = parse_client_request(&legacy("import_lists", "sweep", "1\n2")).unwrap();
+        assert!(matches!(
+            request,
+            ClientRequest::ImportLists { target: ListTarget::Sweep, ref csv } if csv == "1\n2"
+        ));
+
+        assert_eq!(client_error("{").code, ErrorCode::MalformedMessage);
+        assert_eq!(
+            client_error(r#"{"request_type":"get_data"}"#).code,
+            ErrorCode::MalformedMessage
+        );
+        assert_eq!(
+            client_error(&legacy("launch", "", "")).code,
+            ErrorCode::UnknownRequest
+        );
+        assert_eq!(
+            client_error(&legacy("evaluate_data", "", "{")).code,
+            ErrorCode::InvalidArgument
+        );
+        assert_eq!(
+            client_error(&legacy("reallocation", "add", &sweep_model)).code,
+            ErrorCode::InvalidArgument
+        );
+        assert_eq!(
+            client_error(&legacy("preview", "sometimes", "")).code,
+            ErrorCode::InvalidArgument
+        );
+        assert_eq!(
+            client_error(&legacy("export_lists", "bias", "")).code,
+            ErrorCode::InvalidArgument
+        );
+    }
+
+    #[test]
+    fn test_parse_legacy_host_request() {
+        let parse = |line: &str| parse_host_request(line).unwrap();
+        assert!(matches!(parse("shutdown").request, HostRequest::Shutdown));
+        assert!(matches!(
+            parse(r#"" refresh ""#).request,
+            HostRequest::Refresh
+        ));
+        let message = parse(r#"{"session":"s","folder":"/tmp/s.tsp"}"#);
+        assert!(matches!(
+            message.request,
+            HostRequest::ScriptPath { ref session, ref folder } if session == "s" && folder == "/tmp/s.tsp"
+        ));
+        assert_eq!(message.format, WireFormat::Legacy);
+        assert_eq!(message.workspace, DEFAULT_WORKSPACE);
+        assert!(matches!(
+            parse(r#"{"systems":[]}"#).request,
+            HostRequest::SystemConfig { ref systems } if systems.is_empty()
+        ));
+        assert!(matches!(
+            parse(r#"{"lineFrequency":50}"#).request,
+            HostRequest::LineFrequency { line_frequency } if line_frequency == 50.0
+        ));
+        let saved = json!({ "sweep_model": SweepModel::new() }).to_string();
+        assert!(matches!(
+            parse(&legacy("saved_config", "", &saved)).request,
+            HostRequest::SavedConfig { .. }
+        ));
+
+        // a value containing a command name is not a command
+        assert_eq!(
+            host_error(r#"{"note":"shutdown"}"#).code,
+            ErrorCode::UnknownRequest
+        );
+        assert_eq!(host_error("launch").code, ErrorCode::UnknownRequest);
+        assert_eq!(host_error("[1, 2]").code, ErrorCode::MalformedMessage);
+        assert_eq!(
+            host_error(r#"{"session":"s","folder":1}"#).code,
+            ErrorCode::MalformedMessage
+        );
+        assert_eq!(
+            host_error(r#"{"systems":{}}"#).code,
+            ErrorCode::MalformedMessage
+        );
+        assert_eq!(
+            host_error(r#"{"lineFrequency":"60"}"#).code,
+            ErrorCode::InvalidArgument
+        );
+        assert_eq!(
+            host_error(&legacy("saved_config", "", "{")).code,
+            ErrorCode::MalformedMessage
+        );
+        assert_eq!(
+            host_error(&legacy("saved_config", "", "{}")).code,
+            ErrorCode::InvalidArgument
+        );
+    }
+
+    #[test]
+    fn test_host_line_format() {
+        assert_eq!(host_line_format("launch"), WireFormat::Legacy);
+        assert_eq!(host_line_format(r#"{"systems":{}}"#), WireFormat::Legacy);
+        assert_eq!(
+            host_line_format(r#" {"protocol_version":2,"request_type":"shutdown"} "#),
+            WireFormat::Versioned
+        );
+        assert_eq!(
+            host_line_format(r#"{"protocol_version":1,"request_type":"launch"}"#),
+            WireFormat::Versioned
+        );
+    }
+
+    #[test]
+    fn test_check_version() {
+        for (text, code) in [
+            (
+                r#"{"protocol_version":2,"request_type":"get_data"}"#,
+                ErrorCode::UnsupportedVersion,
+            ),
+            (
+                r#"{"protocol_version":0,"request_type":"get_data"}"#,
+                ErrorCode::UnsupportedVersion,
+            ),
+            (
+                r#"{"protocol_version":"1","request_type":"get_data"}"#,
+                ErrorCode::MalformedMessage,
+            ),
+            (
+                r#"{"protocol_version":-1,"request_type":"get_data"}"#,
+                ErrorCode::MalformedMessage,
+            ),
+            (
+                r#"{"protocol_version":1,"request_type":"launch"}"#,
+                ErrorCode::MalformedMessage,
+            ),
+        ] {
+            assert_eq!(client_error(text).code, code, "{text}");
+        }
+        let error = host_error(r#"{"protocol_version":2,"request_type":"shutdown"}"#);
+        assert_eq!(error.code, ErrorCode::UnsupportedVersion);
+        assert_eq!(
+            error.message,
+            "protocol version 2 is not supported, expected 1"
+        );
+    }
+
+    #[test]
+    fn test_client_request_round_trip() {
+        let requests = [
+            ClientRequest::GetData,
+            ClientRequest::EvaluateData {
+                sweep_model: SweepModel::new(),
+            },
+            ClientRequest::Reallocation {
+                sweep_model: SweepModel::new(),
+                change: ChannelChange::Update {
+                    chan_type: ChannelType::Sweep,
+                    old_chan_id: "smu1".to_string(),
+                    new_chan_id: "smu2".to_string(),
+                },
+            },
+            ClientRequest::OpenScript,
+            ClientRequest::Undo,
+            ClientRequest::Redo,
+            ClientRequest::History,
+            ClientRequest::Preview {
+                mode: PreviewMode::Full,
+            },
+            ClientRequest::GetDiagnostics,
+            ClientRequest::SaveProject {
+                path: "a.json".to_string(),
+            },
+            ClientRequest::OpenProject {
+                path: "a.tsp".to_string(),
+            },
+            ClientRequest::ImportLists {
+                target: ListTarget::Step,
+                csv: "1,2".to_string(),
+            },
+            ClientRequest::ExportLists {
+                target: ListTarget::Sweep,
+            },
+        ];
+        for request in requests {
+            let text = serde_json::to_string(&Envelope::new(&request)).unwrap();
+            let (parsed, format) = parse_client_request(&text).unwrap();
+            assert_eq!(format, WireFormat::Versioned);
+            assert_eq!(
+                serde_json::to_value(&parsed).unwrap(),
+                serde_json::to_value(&request).unwrap(),
+                "{text}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_host_request_round_trip() {
+        let requests = [
+            HostRequest::Shutdown,
+            HostRequest::OpenWorkspace,
+            HostRequest::CloseWorkspace,
+            HostRequest::ScriptPath {
+                session: "s".to_string(),
+                folder: "/tmp/s.tsp".to_string(),
+            },
+            HostRequest::SystemConfig {
+                systems: Vec::new(),
+            },
+            HostRequest::LineFrequency {
+                line_frequency: 50.0,
+            },
+            HostRequest::Refresh,
+            HostRequest::Reset,
+            HostRequest::SavedConfig {
+                sweep_model: Box::new(SweepModel::new()),
+            },
+            HostRequest::GetDiagnostics,
+            HostRequest::SaveProject {
+                path: "a.json".to_string(),
+            },
+            HostRequest::OpenProject {
+                path: "a.tsp".to_string(),
+            },
+        ];
+        for request in requests {
+            let text = serde_json::to_string(&Envelope::for_workspace(&request, "ws1")).unwrap();
+            let message = parse_host_request(&text).unwrap();
+            assert_eq!(message.format, WireFormat::Versioned);
+            assert_eq!(message.workspace, "ws1");
+            assert_eq!(
+                serde_json::to_value(&message.request).unwrap(),
+                serde_json::to_value(&request).unwrap(),
+                "{text}"
+            );
+        }
+
+        let text = serde_json::to_string(&Envelope::new(HostRequest::Reset)).unwrap();
+        assert_eq!(
+            parse_host_request(&text).unwrap().workspace,
+            DEFAULT_WORKSPACE
+        );
+    }
+}
@@ -0,0 +1,409 @@
+use std::sync::Arc;
+
+use actix_web::{web, HttpResponse};
+use script_gen_manager::model::sweep_data::{list_csv::ListTarget, sweep_model::SweepModel};
+
+use super::client_server::{AppState, WorkspaceQuery};
+use super::protocol::{ChannelChange, Envelope, ErrorCode, ProtocolError, Response};
+use super::workspace::Workspace;
+
+/// Registers the HTTP endpoints that mirror the WebSocket requests.
+///
+/// * `GET /api/sweep_model` - returns the current sweep model.
+/// * `PUT /api/sweep_model` - replaces and evaluates the sweep model.
+/// * `POST /api/evaluate` - evaluates the current sweep model.
+/// * `POST /api/channel` - adds, removes or updates a channel.
+/// * `POST /api/undo` - undoes the most recent edit.
+/// * `POST /api/redo` - redoes the most recently undone edit.
+/// * `GET /api/history` - lists the edits that can be undone and redone.
+/// * `GET /api/script` - returns the text of the last generated script.
+/// * `GET /api/diagnostics` - returns the version, template groups, devices and recent errors.
+/// * `GET /api/lists/{step|sweep}` - returns the step or sweep lists as CSV.
+/// * `PUT /api/lists/{step|sweep}` - imports the step or sweep lists from a CSV/TSV body.
+///
+/// Every endpoint addresses the default workspace unless `?workspace=<id>` is given.
+/// Responses of the `PUT` and `POST` endpoints are versioned protocol envelopes. Projects
+/// are saved and opened by the host and the UI only, the API does not touch files named in
+/// requests.
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/api")
+            .route("/sweep_model", web::get().to(get_sweep_model))
+            .route("/sweep_model", web::put().to(put_sweep_model))
+            .route("/evaluate", web::post().to(evaluate))
+            .route("/channel", web::post().to(update_channel))
+            .route("/undo", web::post().to(undo))
+            .route("/redo", web::post().to(redo))
+            .route("/history", web::get().to(history))
+            .route("/script", web::get().to(get_script))
+            .route("/diagnostics", web::get().to(diagnostics))
+            .route("/lists/{target}", web::get().to(export_lists))
+            .route("/lists/{target}", web::put().to(import_lists)),
+    );
+}
+
+/// Resolves the workspace selected by `query`, or a 404 response if it is not open.
+async fn workspace(
+    app_state: &AppState,
+    query: &WorkspaceQuery,
+) -> Result<Arc<Workspace>, HttpResponse> {
+    app_state.workspace(query.id()).await.ok_or_else(|| {
+        HttpResponse::NotFound().json(Envelope::for_workspace(
+            Response::Error(ProtocolError::new(
+                ErrorCode::InvalidArgument,
+                format!("workspace '{}' is not open", query.id()),
+            )),
+            query.id(),
+        ))
+    })
+}
+
+async fn get_sweep_model(
+    app_state: web::Data<Arc<AppState>>,
+    query: web::Query<WorkspaceQuery>,
+) -> HttpResponse {
+    let workspace = match workspace(&app_state, &query).await {
+        Ok(workspace) => workspace,
+        Err(response) => return response,
+    };
+    let data_model = workspace.data_model.lock().await;
+    HttpResponse::Ok().json(&data_model.sweep_model)
+}
+
+async fn put_sweep_model(
+    app_state: web::Data<Arc<AppState>>,
+    query: web::Query<WorkspaceQuery>,
+    body: String,
+) -> HttpResponse {
+    let workspace = match workspace(&app_state, &query).await {
+        Ok(workspace) => workspace,
+        Err(response) => return response,
+    };
+    let response = match serde_json::from_str::<SweepModel>(&body) {
+        Ok(sweep_model) => {
+            let mut data_model = workspace.data_model.lock().await;
+            data_model.process_data_from_client(sweep_model)
+        }
+        Err(e) => Response::Error(ProtocolError::new(
+            ErrorCode::InvalidArgument,
+            format!("invalid sweep model: {e}"),
+        )),
+    };
+    respond(&workspace, response).await
+}
+
+async fn evaluate(
+    app_state: web::Data<Arc<AppState>>,
+    query: web::Query<WorkspaceQuery>,
+) -> HttpResponse {
+    let workspace = match workspace(&app_state, &query).await {
+        Ok(workspace) => workspace,
+        Err(response) => return response,
+    };
+    let response = {
+        let mut data_model = workspace.data_model.lock().await;
+        let sweep_model = data_model.sweep_model.clone();
+        data_model.process_data_from_client(sweep_model)
+    };
+    respond(&workspace, response).await
+}
+
+async fn update_channel(
+    app_state: web::Data<Arc<AppState>>,
+    query: web::Query<WorkspaceQuery>,
+    change: web::Json<ChannelChange>,
+) -> HttpResponse {
+    let workspace = match workspace(&app_state, &query).await {
+        Ok(workspace) => workspace,
+        Err(response) => return response,
+    };
+    let response = {
+        let mut data_model = workspace.data_model.lock().await;
+        let sweep_model = data_model.sweep_model.clone();
+        data_model.add_remove_channel(sweep_model, &change)
+    };
+    respond(&workspace, response).await
+}
+
+async fn undo(
+    app_state: web::Data<Arc<AppState>>,
+    query: web::Query<WorkspaceQuery>,
+) -> HttpResponse {
+    let workspace = match workspace(&app_state, &query).await {
+        Ok(workspace) => workspace,
+        Err(response) => return response,
+    };
+    let response = workspace.undo().await;
+    reply(&workspace, response).await
+}
+
+async fn redo(
+    app_state: web::Data<Arc<AppState>>,
+    query: web::Query<WorkspaceQuery>,
+) -> HttpResponse {
+    let workspace = match workspace(&app_state, &query).await {
+        Ok(workspace) => workspace,
+        Err(response) => return response,
+    };
+    let response = workspace.redo().await;
+    reply(&workspace, response).await
+}
+
+async fn history(
+    app_state: web::Data<Arc<AppState>>,
+    query: web::Query<WorkspaceQuery>,
+) -> HttpResponse {
+    let workspace = match workspace(&app_state, &query).await {
+        Ok(workspace) => workspace,
+        Err(response) => return response,
+    };
+    let response = workspace.data_model.lock().await.history();
+    HttpResponse::Ok().json(Envelope::for_workspace(response, &workspace.id))
+}
+
+async fn get_script(
+    app_state: web::Data<Arc<AppState>>,
+    query: web::Query<WorkspaceQuery>,
+) -> HttpResponse {
+    let workspace = match workspace(&app_state, &query).await {
+        Ok(workspace) => workspace,
+        Err(response) => return response,
+    };
+    let work_folder = workspace.work_folder.lock().await.clone();
+    let Some(path) = work_folder else {
+        return HttpResponse::NotFound().body("No work folder has been set");
+    };
+    match std::fs::read_to_string(&path) {
+        Ok(script) => HttpResponse::Ok()
+            .content_type("text/plain; charset=utf-8")
+            .body(script),
+        Err(e) => HttpResponse::NotFound().body(format!("Failed to read script {path}: {e}")),
+    }
+}
+
+async fn diagnostics(
+    app_state: web::Data<Arc<AppState>>,
+    query: web::Query<WorkspaceQuery>,
+) -> HttpResponse {
+    let workspace = match workspace(&app_state, &query).await {
+        Ok(workspace) => workspace,
+        Err(response) => return response,
+    };
+    let response = workspace.diagnostics().await;
+    HttpResponse::Ok().json(Envelope::for_workspace(response, &workspace.id))
+}
+
+async fn import_lists(
+    app_state: web::Data<Arc<AppState>>,
+    query: web::Query<WorkspaceQuery>,
+    target: web::Path<ListTarget>,
+    body: String,
+) -> HttpResponse {
+    let workspace = match workspace(&app_state, &query).await {
+        Ok(workspace) => workspace,
+        Err(response) => return response,
+    };
+    let response = workspace.import_lists(*target, &body).await;
+    reply(&workspace, response).await
+}
+
+async fn export_lists(
+    app_state: web::Data<Arc<AppState>>,
+    query: web::Query<WorkspaceQuery>,
+    target: web::Path<ListTarget>,
+) -> HttpResponse {
+    let workspace = match workspace(&app_state, &query).await {
+        Ok(workspace) => workspace,
+        Err(response) => return response,
+    };
+    match workspace.export_lists(*target).await {
+        Response::ListsExported { csv, .. } => HttpResponse::Ok()
+            .content_type("text/csv; charset=utf-8")
+            .body(csv),
+        response => HttpResponse::InternalServerError()
+            .json(Envelope::for_workspace(response, &workspace.id)),
+    }
+}
+
+/// Requests script generation, forwards the response to the UI connected to the workspace
+/// (if any) and returns it to the HTTP client.
+async fn respond(workspace: &Workspace, response: Response) -> HttpResponse {
+    if !response.is_error() {
+        workspace.request_script_generation();
+    }
+    reply(workspace, response).await
+}
+
+/// Forwards the response to the UI connected to the workspace (if any) and returns it to
+/// the HTTP client.
+async fn reply(workspace: &Workspace, response: Response) -> HttpResponse {
+    if response.is_error() {
+        return HttpResponse::BadRequest().json(Envelope::for_workspace(response, &workspace.id));
+    }
+
+    workspace.send_to_client(&response).await;
+    HttpResponse::Ok().json(Envelope::for_workspace(response, &workspace.id))
+}
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod tests {
+    use actix_web::http::StatusCode;
+    use actix_web::test::{call_service, init_service, read_body, read_body_json, TestRequest};
+    use actix_web::App;
+    use script_gen_manager::catalog::Catalog;
+    use script_gen_manager::script_component::script::ScriptWriter;
+    use serde_json::{json, Value};
+
+    use super::*;
+    use crate::back_end::protocol::DEFAULT_WORKSPACE;
+
+    const SYSTEM: &str = r#"{"systems":[{"name":"sys1","isActive":true,"localNode":"MP5103",
+        "slots":[{"slotId":"slot[1]","module":"MSMU60-2"},
+        {"slotId":"slot[2]","module":"MPSU50-2ST"}],"nodes":[]}]}"#;
+
+    /// Returns the server state with the devices of [`SYSTEM`] in the default workspace.
+    async fn app_state() -> (web::Data<Arc<AppState>>, Arc<Workspace>) {
+        let mut catalog = Catalog::new();
+        catalog.refresh_function_metadata().unwrap();
+        let app_state = Arc::new(AppState::new(catalog, ScriptWriter::default()));
+        let workspace = app_state.workspace(DEFAULT_WORKSPACE).await.unwrap();
+        let response = workspace
+            .data_model
+            .lock()
+            .await
+            .process_system_config(SYSTEM);
+        assert!(!response.is_error());
+        (web::Data::new(app_state), workspace)
+    }
+
+    async fn bias_devices(workspace: &Workspace) -> Vec<String> {
+        let data_model = workspace.data_model.lock().await;
+        data_model
+            .sweep_model
+            .sweep_config
+            .bias_channels
+            .iter()
+            .map(|channel| channel.common_chan_attributes.device_id.clone())
+            .collect()
+    }
+
+    #[actix_web::test]
+    async fn test_get_put_sweep_model() {
+        let (app_state, workspace) = app_state().await;
+        let app = init_service(App::new().app_data(app_state).configure(configure)).await;
+
+        let response = call_service(
+            &app,
+            TestRequest::get().uri("/api/sweep_model").to_request(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let mut sweep_model: Value = read_body_json(response).await;
+        assert_eq!(
+            sweep_model["sweep_config"]["sweep_global_parameters"]["sweep_points"]["value"],
+            10
+        );
+
+        sweep_model["sweep_config"]["sweep_global_parameters"]["sweep_points"]["value"] = json!(25);
+        let request = TestRequest::put()
+            .uri("/api/sweep_model")
+            .set_payload(sweep_model.to_string())
+            .to_request();
+        let response = call_service(&app, request).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let envelope: Value = read_body_json(response).await;
+        assert_eq!(envelope["workspace"], DEFAULT_WORKSPACE);
+        assert_eq!(envelope["response_type"], "evaluated_response");
+        assert_eq!(
+            workspace
+                .data_model
+                .lock()
+                .await
+                .sweep_model
+                .sweep_config
+                .sweep_global_parameters
+                .sweep_points
+                .value,
+            25
+        );
+
+        let request = TestRequest::put()
+            .uri("/api/sweep_model")
+            .set_payload("{")
+            .to_request();
+        let response = call_service(&app, request).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let envelope: Value = read_body_json(response).await;
+        assert_eq!(envelope["code"], "invalid_argument");
+    }
+
+    #[actix_web::test]
+    async fn test_add_remove_channel() {
+        let (app_state, workspace) = app_state().await;
+        let app = init_service(App::new().app_data(app_state).configure(configure)).await;
+        let initial = bias_devices(&workspace).await;
+
+        let request = TestRequest::post()
+            .uri("/api/channel")
+            .set_json(json!({ "action": "add", "chan_type": "bias" }))
+            .to_request();
+        assert_eq!(call_service(&app, request).await.status(), StatusCode::OK);
+        let devices = bias_devices(&workspace).await;
+        assert_eq!(devices.len(), initial.len() + 1);
+        let added = devices.last().unwrap();
+
+        let request = TestRequest::post()
+            .uri("/api/channel")
+            .set_json(json!({ "action": "remove", "chan_type": "bias", "chan_id": added }))
+            .to_request();
+        assert_eq!(call_service(&app, request).await.status(), StatusCode::OK);
+        assert_eq!(bias_devices(&workspace).await, initial);
+
+        // the removal can be undone
+        let request = TestRequest::post().uri("/api/undo").to_request();
+        assert_eq!(call_service(&app, request).await.status(), StatusCode::OK);
+        assert_eq!(bias_devices(&workspace).await, devices);
+    }
+
+    #[actix_web::test]
+    async fn test_unknown_workspace() {
+        let (app_state, _) = app_state().await;
+        let app = init_service(App::new().app_data(app_state).configure(configure)).await;
+
+        for request in [
+            TestRequest::get().uri("/api/sweep_model?workspace=missing"),
+            TestRequest::post().uri("/api/evaluate?workspace=missing"),
+            TestRequest::get().uri("/api/script?workspace=missing"),
+            TestRequest::get().uri("/api/lists/step?workspace=missing"),
+        ] {
+            let response = call_service(&app, request.to_request()).await;
+            assert_eq!(response.status(), StatusCode::NOT_FOUND);
+            let envelope: Value = read_body_json(response).await;
+            assert_eq!(envelope["workspace"], "missing");
+            assert_eq!(envelope["code"], "invalid_argument");
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_script() {
+        let (app_state, workspace) = app_state().await;
+        let app = init_service(App::new().app_data(app_state).configure(configure)).await;
+
+        let request = TestRequest::get().uri("/api/script").to_request();
+        assert_eq!(
+            call_service(&app, request).await.status(),
+            StatusCode::NOT_FOUND
+        );
+
+        let path =
+            std::env::temp_dir().join(format!("kic-script-gen-rest-{}.tsp", std::process::id()));
+        std::fs::write(&path, "print('sweep')\n").unwrap();
+        *workspace.work_folder.lock().await = Some(path.to_string_lossy().into_owned());
+        let request = TestRequest::get().uri("/api/script").to_request();
+        let response = call_service(&app, request).await;
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(read_body(response).await, "print('sweep')\n");
+    }
+}
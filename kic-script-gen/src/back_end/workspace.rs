@@ -0,0 +1,701 @@
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Weak};
+use std::time::{Duration, Instant};
+
+use actix_ws::Session;
+use script_gen_manager::catalog::Catalog;
+use script_gen_manager::error::ScriptGenError;
+use script_gen_manager::model::sweep_data::list_csv::ListTarget;
+use script_gen_manager::model::sweep_data::status_msg::{StatusMsg, StatusType};
+use script_gen_manager::model::system_info::Root;
+use script_gen_manager::project::Project;
+use script_gen_manager::script_component::script::{ScriptModel, ScriptWriter, WriteStatus};
+use sha2::{Digest, Sha256};
+use tokio::sync::{watch, Mutex};
+
+use super::chunking::send_text;
+use super::client_server::ScriptPath;
+use super::data_model::DataModel;
+use super::preview::Preview;
+use super::protocol::{
+    self, ClientRequest, ErrorCode, HostRequest, ProtocolError, Response, TemplateSource,
+    WireFormat,
+};
+use crate::logging;
+
+/// Time without further generation requests after which the script is generated.
+pub const GENERATION_DEBOUNCE: Duration = Duration::from_millis(150);
+
+/// Longest time the script generation is delayed by further requests, counted from the first
+/// pending request.
+pub const GENERATION_MAX_DELAY: Duration = Duration::from_secs(1);
+
+/// State of one script-gen panel: its sweep model, output path, UI connection and script
+/// generation task.
+pub struct Workspace {
+    pub id: String,
+    /// The UI connection of the workspace and its connection number.
+    session: Mutex<Option<(u64, Session)>>,
+    /// Number of the next UI connection.
+    next_connection: AtomicU64,
+    pub data_model: Mutex<DataModel>,
+    pub work_folder: Mutex<Option<String>>,
+    /// Message format used by the connected UI, replies are sent back in the same format.
+    pub client_format: Mutex<WireFormat>,
+    /// Script preview pushed to the connected UI.
+    pub preview: Mutex<Preview>,
+    /// Names of the script template groups loaded from the catalog, sorted.
+    pub template_groups: Vec<String>,
+    /// The templates loaded by the catalog and where they came from.
+    pub templates: Vec<TemplateSource>,
+    gen_script_tx: watch::Sender<()>,
+}
+
+impl Workspace {
+    /// Creates a workspace and starts its script generation task.
+    ///
+    /// Generation requests are coalesced: the script is generated once no further request
+    /// arrived for [`GENERATION_DEBOUNCE`], but at the latest [`GENERATION_MAX_DELAY`] after
+    /// the first pending request. The task holds a weak reference only, it ends once the
+    /// workspace is dropped. Rendering and writing the script run on the blocking thread pool,
+    /// so that they do not stall the other workspaces and connections. A failed generation,
+    /// including a panic, is reported in the `ScriptGenerated` event.
+    pub fn open(id: &str, catalog: &Catalog, writer: ScriptWriter) -> Arc<Self> {
+        let (gen_script_tx, mut gen_script_rx) = watch::channel(());
+        let mut template_groups: Vec<String> =
+            catalog.function_metadata_map.keys().cloned().collect();
+        template_groups.sort();
+        let workspace = Arc::new(Workspace {
+            id: id.to_string(),
+            session: Mutex::new(None),
+            next_connection: AtomicU64::new(0),
+            data_model: Mutex::new(DataModel::with_limits(catalog.sweep_limits())),
+            work_folder: Mutex::new(None),
+            client_format: Mutex::new(WireFormat::Legacy),
+            preview: Mutex::new(Preview::default()),
+            template_groups,
+            templates: catalog.resources.iter().map(TemplateSource::from).collect(),
+            gen_script_tx,
+        });
+
+        let catalog = catalog.clone();
+        let mut script_model = new_script_model(&catalog);
+
+        let weak: Weak<Workspace> = Arc::downgrade(&workspace);
+        tokio::spawn(async move {
+            while settle(&mut gen_script_rx).await {
+                let Some(workspace) = weak.upgrade() else {
+                    break;
+                };
+                tracing::debug!(workspace = %workspace.id, "Starting script generation");
+                // render from a snapshot so that requests are not blocked meanwhile
+                let sweep_config = workspace
+                    .data_model
+                    .lock()
+                    .await
+                    .sweep_model
+                    .sweep_config
+                    .clone();
+                let work_folder = workspace.work_folder.lock().await.clone();
+
+                let start = Instant::now();
+                let rendered = match blocking(move || {
+                    let rendered = script_model.render_with_source_map(&sweep_config);
+                    (script_model, rendered)
+                })
+                .await
+                {
+                    Ok((model, rendered)) => {
+                        script_model = model;
+                        rendered.map_err(|e| ProtocolError::from(&e))
+                    }
+                    Err(e) => {
+                        // the script model is lost with the render, start over
+                        script_model = new_script_model(&catalog);
+                        Err(e)
+                    }
+                };
+                let mut hash = None;
+                let result = match rendered {
+                    Ok((script, source_map)) => {
+                        hash = Some(format!("{:x}", Sha256::digest(script.as_bytes())));
+                        let preview = workspace.preview.lock().await.update(&script);
+                        if let Some(preview) = preview {
+                            workspace.send_to_client(&preview).await;
+                        }
+                        match work_folder.clone() {
+                            Some(path) => blocking(move || {
+                                let status = writer.write(&script, Path::new(&path))?;
+                                writer.write_source_map(&source_map, Path::new(&path))?;
+                                Ok(status)
+                            })
+                            .await
+                            .and_then(
+                                |written: Result<WriteStatus, ScriptGenError>| {
+                                    written.map_err(|e| ProtocolError::from(&e))
+                                },
+                            ),
+                            None => Err(ProtocolError::from(&ScriptGenError::NoOutputPath)),
+                        }
+                    }
+                    Err(e) => Err(e),
+                };
+                let duration_ms = u64::try_from(start.elapsed().as_millis()).unwrap_or(u64::MAX);
+                let event = match result {
+                    Ok(status) => Response::ScriptGenerated {
+                        path: work_folder,
+                        hash,
+                        unchanged: status == WriteStatus::Unchanged,
+                        duration_ms,
+                        error: None,
+                    },
+                    Err(e) => {
+                        if e.code == ErrorCode::NoOutputPath {
+                            tracing::warn!(workspace = %workspace.id, "Script not written: {}", e.message);
+                        } else {
+                            tracing::error!(workspace = %workspace.id, "Failed to generate script: {}", e.message);
+                        }
+                        Response::ScriptGenerated {
+                            path: work_folder,
+                            hash,
+                            unchanged: false,
+                            duration_ms,
+                            error: Some(e),
+                        }
+                    }
+                };
+                workspace.send_to_client(&event).await;
+            }
+        });
+
+        workspace
+    }
+
+    /// Closes the UI connection of the workspace, if any.
+    pub async fn close(&self) {
+        if let Some((_, session)) = self.session.lock().await.take() {
+            let _ = session.close(None).await;
+        }
+    }
+
+    /// Makes `session` the UI connection of the workspace and returns its connection number.
+    ///
+    /// Returns `None` if another UI is connected, a workspace belongs to a single panel.
+    pub async fn attach(&self, session: Session) -> Option<u64> {
+        let mut current = self.session.lock().await;
+        if current.is_some() {
+            tracing::warn!(workspace = %self.id, "Rejected a second UI connection");
+            return None;
+        }
+        let connection = self.next_connection.fetch_add(1, Ordering::Relaxed);
+        *current = Some((connection, session));
+        self.preview.lock().await.reset();
+        Some(connection)
+    }
+
+    /// Forgets the UI connection `connection` once it ended, so that the UI can reconnect.
+    pub async fn detach(&self, connection: u64) {
+        let mut current = self.session.lock().await;
+        if current.as_ref().is_some_and(|(id, _)| *id == connection) {
+            *current = None;
+        }
+    }
+
+    async fn update_script_name(&self, script_path: &ScriptPath) {
+        tracing::info!(
+            "Updating work folder to session {} in {}",
+            script_path.session,
+            script_path.folder
+        );
+        let mut work_folder_guard = self.work_folder.lock().await;
+        let filename: String = format!("{}.tsp", script_path.session);
+        let path_file = Path::new(&script_path.folder).join(filename);
+        // Check if the folder exists and is writable
+        if path_file.parent().is_some_and(|folder| folder.exists()) {
+            *work_folder_guard = Some(path_file.to_string_lossy().to_string());
+            tracing::info!("Work folder updated to: {work_folder_guard:?}");
+        } else {
+            let status_msg = StatusMsg::new(
+                StatusType::Error,
+                format!(
+                    "The folder is read-only (OR) Work folder does not exist: {:?}",
+                    path_file.to_string_lossy().to_string()
+                ),
+            );
+            tracing::error!("{}", status_msg.message);
+            protocol::write_to_host(
+                &serde_json::to_string(&status_msg).unwrap_or_else(|_| status_msg.message.clone()),
+            );
+        }
+    }
+
+    /// Sends `response` to the connected UI (if any) in the format the UI talks.
+    pub async fn send_to_client(&self, response: &Response) {
+        let format = *self.client_format.lock().await;
+        let mut session = self.session.lock().await;
+        if let Some((_, session)) = session.as_mut() {
+            if let Err(e) = send_text(session, response.to_json(format)).await {
+                tracing::warn!(workspace = %self.id, "Failed to send response to UI: {e}");
+            }
+        }
+    }
+
+    /// Requests a script generation.
+    pub fn request_script_generation(&self) {
+        if let Err(e) = self.gen_script_tx.send(()) {
+            tracing::error!(workspace = %self.id, "Failed to request script generation: {e}");
+        }
+    }
+
+    /// Handles a request received from the UI and returns the reply, if any.
+    pub async fn handle_client_request(&self, request: ClientRequest) -> Option<Response> {
+        match request {
+            ClientRequest::GetData => {
+                protocol::write_to_host("instrument data requested");
+                None
+            }
+            ClientRequest::EvaluateData { sweep_model } => {
+                let response = self
+                    .data_model
+                    .lock()
+                    .await
+                    .process_data_from_client(sweep_model);
+                self.request_script_generation();
+                Some(response)
+            }
+            ClientRequest::Reallocation {
+                sweep_model,
+                change,
+            } => {
+                let response = self
+                    .data_model
+                    .lock()
+                    .await
+                    .add_remove_channel(sweep_model, &change);
+                self.request_script_generation();
+                Some(response)
+            }
+            ClientRequest::OpenScript => {
+                // Generate script if needed
+                self.request_script_generation();
+                Some(Response::OpenScript)
+            }
+            ClientRequest::Undo => Some(self.undo().await),
+            ClientRequest::Redo => Some(self.redo().await),
+            ClientRequest::History => Some(self.data_model.lock().await.history()),
+            ClientRequest::Preview { mode } => {
+                self.preview.lock().await.set_mode(mode);
+                // send the current script right away
+                self.request_script_generation();
+                None
+            }
+            ClientRequest::GetDiagnostics => Some(self.diagnostics().await),
+            ClientRequest::SaveProject { path } => Some(self.save_project(&path).await),
+            ClientRequest::OpenProject { path } => Some(self.open_project(&path).await),
+            ClientRequest::ImportLists { target, csv } => {
+                Some(self.import_lists(target, &csv).await)
+            }
+            ClientRequest::ExportLists { target } => Some(self.export_lists(target).await),
+        }
+    }
+
+    /// Saves the sweep model, system configuration and output path as a project file.
+    pub async fn save_project(&self, path: &str) -> Response {
+        let output_path = self.work_folder.lock().await.clone();
+        let project = {
+            let data_model = self.data_model.lock().await;
+            Project::new(
+                data_model.sweep_model.clone(),
+                data_model.system_info.clone(),
+                output_path,
+            )
+        };
+        match project.save(Path::new(path)) {
+            Ok(()) => Response::ProjectSaved {
+                path: path.to_string(),
+            },
+            Err(e) => {
+                tracing::error!("Failed to save project {path}: {e}");
+                Response::Error(ProtocolError::new(
+                    ErrorCode::ProcessingFailed,
+                    format!("failed to save project {path}: {e}"),
+                ))
+            }
+        }
+    }
+
+    /// Opens a project file, makes its sweep model the current one and reports the devices
+    /// of the project that do not match the connected hardware (if known).
+    pub async fn open_project(&self, path: &str) -> Response {
+        let project = match Project::load(Path::new(path)) {
+            Ok(project) => project,
+            Err(e) => {
+                tracing::error!("Failed to open project {path}: {e}");
+                return Response::Error(ProtocolError::new(
+                    ErrorCode::InvalidArgument,
+                    format!("failed to open project {path}: {e}"),
+                ));
+            }
+        };
+
+        let (evaluated, device_mismatches) = {
+            let mut data_model = self.data_model.lock().await;
+            // without a system configuration the connected hardware is unknown
+            let device_mismatches = data_model
+                .connected_devices()
+                .map(|devices| project.device_mismatches(&devices))
+                .unwrap_or_default();
+            (
+                data_model.process_data_from_project(project.sweep_model),
+                device_mismatches,
+            )
+        };
+        for mismatch in &device_mismatches {
+            tracing::warn!("Project {path} does not match the connected hardware: {mismatch:?}");
+        }
+        match project.output_path {
+            Some(output_path) if is_output_path(&output_path) => {
+                *self.work_folder.lock().await = Some(output_path);
+            }
+            Some(output_path) => {
+                tracing::warn!("Ignoring output path {output_path:?} of project {path}, expected a .tsp file in an existing folder");
+            }
+            None => {}
+        }
+        self.send_to_client(&evaluated).await;
+        self.request_script_generation();
+
+        Response::ProjectOpened {
+            path: path.to_string(),
+            generator_version: project.generator_version,
+            device_mismatches,
+        }
+    }
+
+    /// Replaces the step or sweep lists with the values of a CSV/TSV file and regenerates
+    /// the script.
+    pub async fn import_lists(&self, target: ListTarget, csv: &str) -> Response {
+        let result = self.data_model.lock().await.import_lists(target, csv);
+        let (evaluated, import) = match result {
+            Ok(result) => result,
+            Err(e) => {
+                return Response::Error(ProtocolError::new(
+                    ErrorCode::InvalidArgument,
+                    e.to_string(),
+                ))
+            }
+        };
+        for value in &import.clamped {
+            tracing::info!(
+                "Clamped {} of {} from {} to {}",
+                value.parameter,
+                value.channel,
+                value.requested,
+                value.value
+            );
+        }
+        self.send_to_client(&evaluated).await;
+        self.request_script_generation();
+
+        Response::ListsImported {
+            target,
+            points: import.points,
+            channels: import.channels,
+            clamped: import.clamped,
+        }
+    }
+
+    /// Returns the step or sweep lists as CSV.
+    pub async fn export_lists(&self, target: ListTarget) -> Response {
+        let csv = self
+            .data_model
+            .lock()
+            .await
+            .sweep_model
+            .sweep_config
+            .export_lists(target);
+        Response::ListsExported { target, csv }
+    }
+
+    /// Reports the server version, loaded template groups, current devices and the most
+    /// recent warnings and errors.
+    pub async fn diagnostics(&self) -> Response {
+        let devices = self
+            .data_model
+            .lock()
+            .await
+            .sweep_model
+            .sweep_config
+            .device_list
+            .clone();
+        Response::Diagnostics {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            template_groups: self.template_groups.clone(),
+            templates: self.templates.clone(),
+            devices,
+            recent_errors: logging::recent_errors(),
+        }
+    }
+
+    /// Undoes the most recent edit and regenerates the script.
+    pub async fn undo(&self) -> Response {
+        let response = self.data_model.lock().await.undo();
+        if !response.is_error() {
+            self.request_script_generation();
+        }
+        response
+    }
+
+    /// Redoes the most recently undone edit and regenerates the script.
+    pub async fn redo(&self) -> Response {
+        let response = self.data_model.lock().await.redo();
+        if !response.is_error() {
+            self.request_script_generation();
+        }
+        response
+    }
+
+    /// Handles a request received from the host on stdin. Requests that concern the whole
+    /// server (shutdown and the workspace lifecycle) are handled by the caller.
+    pub async fn handle_host_request(&self, request: HostRequest, format: WireFormat) {
+        match request {
+            HostRequest::Shutdown | HostRequest::OpenWorkspace | HostRequest::CloseWorkspace => {}
+            HostRequest::ScriptPath { session, folder } => {
+                self.update_script_name(&ScriptPath { session, folder })
+                    .await;
+            }
+            HostRequest::SystemConfig { systems } => {
+                let system_info = match serde_json::to_string(&Root { systems }) {
+                    Ok(system_info) => system_info,
+                    Err(e) => {
+                        tracing::error!("Failed to serialize system configuration: {e}");
+                        return;
+                    }
+                };
+                let response = self
+                    .data_model
+                    .lock()
+                    .await
+                    .process_system_config(&system_info);
+                protocol::write_to_host(&response.to_host_json(format, &self.id));
+                // Send generate script signal
+                if !response.is_error() {
+                    self.request_script_generation();
+                }
+                self.send_to_client(&response).await;
+            }
+            HostRequest::LineFrequency { line_frequency } => {
+                let mut data_model = self.data_model.lock().await;
+                data_model
+                    .sweep_model
+                    .sweep_config
+                    .global_parameters
+                    .set_line_frequency(line_frequency);
+                tracing::info!("Set line frequency to {line_frequency}");
+            }
+            HostRequest::Refresh => {
+                protocol::write_to_host("instrument data requested"); // refreshing by initiating session again does not affect the JSON state
+            }
+            HostRequest::Reset => {
+                let response = self.data_model.lock().await.reset_sweep_config();
+                self.send_to_client(&response).await;
+                protocol::write_to_host("instrument data requested"); // getting the system configuration for new session
+            }
+            HostRequest::SavedConfig { sweep_model } => {
+                let response = self
+                    .data_model
+                    .lock()
+                    .await
+                    .process_data_from_saved_config(*sweep_model);
+                tracing::info!(
+                    "processed data from saved config {}",
+                    response.to_host_json(format, &self.id)
+                );
+                self.send_to_client(&response).await;
+            }
+            HostRequest::GetDiagnostics => {
+                let response = self.diagnostics().await;
+                protocol::write_to_host(&response.to_host_json(format, &self.id));
+            }
+            HostRequest::SaveProject { path } => {
+                let response = self.save_project(&path).await;
+                protocol::write_to_host(&response.to_host_json(format, &self.id));
+            }
+            HostRequest::OpenProject { path } => {
+                let response = self.open_project(&path).await;
+                protocol::write_to_host(&response.to_host_json(format, &self.id));
+            }
+        }
+    }
+}
+
+/// Returns whether `path` may be adopted as the output path of a project: an absolute path
+/// of a `.tsp` file in an existing folder. Generation overwrites it.
+fn is_output_path(path: &str) -> bool {
+    let path = Path::new(path);
+    path.is_absolute()
+        && path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("tsp"))
+        && path.parent().is_some_and(Path::is_dir)
+}
+
+/// Creates the script model the generation task renders with.
+fn new_script_model(catalog: &Catalog) -> ScriptModel {
+    let mut script_model = ScriptModel::new(catalog.clone());
+    script_model.initialize_scripts();
+    script_model.add_sweep();
+    script_model.add_data_report();
+    script_model
+}
+
+/// Waits for a generation request and until the requests settle: no further request arrived
+/// for [`GENERATION_DEBOUNCE`], or [`GENERATION_MAX_DELAY`] passed since the first one.
+///
+/// Returns `false` if the workspace was dropped.
+async fn settle(requests: &mut watch::Receiver<()>) -> bool {
+    if requests.changed().await.is_err() {
+        return false;
+    }
+    let deadline = tokio::time::Instant::now() + GENERATION_MAX_DELAY;
+    loop {
+        let wake = deadline.min(tokio::time::Instant::now() + GENERATION_DEBOUNCE);
+        tokio::select! {
+            res = requests.changed() => if res.is_err() { return false },
+            () = tokio::time::sleep_until(wake) => return true,
+        }
+    }
+}
+
+/// Runs `f` on the blocking thread pool and returns its result, or an error if `f` panicked.
+async fn blocking<T: Send + 'static>(
+    f: impl FnOnce() -> T + Send + 'static,
+) -> Result<T, ProtocolError> {
+    tokio::task::spawn_blocking(f).await.map_err(|e| {
+        ProtocolError::new(
+            ErrorCode::ProcessingFailed,
+            format!("Script generation failed: {e}"),
+        )
+    })
+}
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod tests {
+    use script_gen_manager::model::sweep_data::sweep_model::SweepModel;
+
+    use super::*;
+
+    #[test]
+    fn test_output_path() {
+        let folder = std::env::temp_dir();
+        let script = folder.join("script.tsp");
+        assert!(is_output_path(&script.to_string_lossy()));
+        assert!(is_output_path(&folder.join("SCRIPT.TSP").to_string_lossy()));
+        assert!(!is_output_path("script.tsp"));
+        assert!(!is_output_path(
+            &folder.join("script.lua").to_string_lossy()
+        ));
+        assert!(!is_output_path(&folder.to_string_lossy()));
+        assert!(!is_output_path(
+            &folder
+                .join("missing-folder")
+                .join("script.tsp")
+                .to_string_lossy()
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_open_project_ignores_invalid_output_path() {
+        let mut catalog = Catalog::new();
+        catalog.refresh_function_metadata().unwrap();
+        let workspace = Workspace::open("test", &catalog, ScriptWriter::default());
+        let folder = std::env::temp_dir();
+        let path = folder.join(format!("kic-script-gen-{}.json", std::process::id()));
+
+        for (output_path, adopted) in [
+            (folder.join("project.tsp"), true),
+            (folder.join("project.json"), false),
+        ] {
+            let output_path = output_path.to_string_lossy().into_owned();
+            *workspace.work_folder.lock().await = None;
+            Project::new(SweepModel::new(), None, Some(output_path.clone()))
+                .save(&path)
+                .unwrap();
+            let response = workspace.open_project(&path.to_string_lossy()).await;
+            assert!(matches!(response, Response::ProjectOpened { .. }));
+            let expected = adopted.then_some(output_path);
+            assert_eq!(*workspace.work_folder.lock().await, expected);
+        }
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[tokio::test]
+    async fn test_diagnostics_list_template_sources() {
+        let mut catalog = Catalog::new();
+        catalog.refresh_function_metadata().unwrap();
+        let workspace = Workspace::open("test", &catalog, ScriptWriter::default());
+        let Response::Diagnostics {
+            template_groups,
+            templates,
+            ..
+        } = workspace.diagnostics().await
+        else {
+            panic!("expected diagnostics");
+        };
+        assert!(template_groups.contains(&"Sweep".to_string()));
+        assert_eq!(templates.len(), catalog.resources.len());
+        assert!(
+            templates
+                .iter()
+                .any(|template| template.name == "DEFAULT_LIMITS_XML"
+                    && template.origin == "embedded")
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_generation_requests_are_coalesced() {
+        let (requests, mut pending) = watch::channel(());
+        let start = tokio::time::Instant::now();
+        let burst = tokio::spawn(async move {
+            for _ in 0..5 {
+                requests.send_replace(());
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+            requests
+        });
+        assert!(settle(&mut pending).await);
+        // the last request of the burst was sent after 200 ms
+        assert_eq!(
+            start.elapsed(),
+            Duration::from_millis(200) + GENERATION_DEBOUNCE
+        );
+        let requests = burst.await.unwrap();
+        assert!(!pending.has_changed().unwrap());
+
+        // a steady stream of requests does not postpone the generation for good
+        let start = tokio::time::Instant::now();
+        let stream = tokio::spawn(async move {
+            for _ in 0..100 {
+                requests.send_replace(());
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+        });
+        assert!(settle(&mut pending).await);
+        assert_eq!(start.elapsed(), GENERATION_MAX_DELAY);
+
+        // the task ends once the workspace is dropped
+        stream.await.unwrap();
+        assert!(!settle(&mut pending).await);
+    }
+
+    #[tokio::test]
+    async fn test_panic_is_reported() {
+        let error = blocking(|| -> u32 { panic!("render failed") })
+            .await
+            .unwrap_err();
+        assert_eq!(error.code, ErrorCode::ProcessingFailed);
+        assert_eq!(blocking(|| 2).await.unwrap(), 2);
+    }
+}
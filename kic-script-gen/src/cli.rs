@@ -0,0 +1,332 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Context};
+use clap::{Args, Parser, Subcommand};
+use script_aggregator::source_map::SourceMap;
+use script_gen_manager::{
+    catalog::Catalog,
+    model::sweep_data::sweep_model::SweepModel,
+    project::{self, Project},
+    script_component::{
+        lint::lint_templates,
+        script::{source_map_path, ScriptModel, ScriptWriter, WriteStatus},
+    },
+};
+use xml_handler::lint::Severity;
+
+use crate::back_end::{client_server::ServerConfig, protocol};
+use crate::logging::LogArgs;
+
+/// Command line interface of the KIC Script Generator.
+#[derive(Parser, Debug)]
+#[command(version, about = "KIC Script Generator")]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    #[command(flatten)]
+    pub serve: ServeArgs,
+
+    #[command(flatten)]
+    pub output: OutputArgs,
+
+    #[command(flatten)]
+    pub templates: TemplateArgs,
+
+    #[command(flatten)]
+    pub log: LogArgs,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Start the web server used by the script generation UI (default).
+    Serve,
+    /// Generate a script from configuration files and exit.
+    Generate(GenerateArgs),
+    /// Print the JSON Schema of the UI and host message protocol and exit.
+    Schema,
+    /// Check the script templates and exit, failing if errors are found.
+    Lint(LintArgs),
+    /// Print the template elements a line of a generated script was generated from and exit.
+    Locate(LocateArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct ServeArgs {
+    /// Address the web server binds to.
+    #[arg(
+        long,
+        global = true,
+        env = "KIC_SCRIPT_GEN_HOST",
+        default_value = "127.0.0.1"
+    )]
+    pub host: String,
+
+    /// Port the web server binds to, 0 picks a free port.
+    #[arg(
+        long,
+        global = true,
+        env = "KIC_SCRIPT_GEN_PORT",
+        default_value_t = 27950
+    )]
+    pub port: u16,
+
+    /// Fall back to a free port if `--port` is already in use.
+    #[arg(long, global = true, env = "KIC_SCRIPT_GEN_AUTO_PORT")]
+    pub auto_port: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct OutputArgs {
+    /// Keep the previous version of a regenerated script as `<script>.bak`.
+    #[arg(long, global = true, env = "KIC_SCRIPT_GEN_KEEP_BACKUP")]
+    pub keep_backup: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct TemplateArgs {
+    /// Directory of XML templates that override the embedded ones, using the same file
+    /// names (e.g. `sweep/MP5000Sweep.xml`).
+    #[arg(long, global = true, env = "KIC_SCRIPT_GEN_TEMPLATE_DIR")]
+    pub template_dir: Option<PathBuf>,
+}
+
+/// Creates the catalog of script templates, preferring the templates of
+/// `args.template_dir` over the embedded ones.
+///
+/// A template that cannot be parsed does not fail here: the error is kept in the catalog and
+/// reported whenever a script is generated from it.
+///
+/// # Errors
+///
+/// Returns an error if the template directory does not exist.
+pub fn load_catalog(args: &TemplateArgs) -> anyhow::Result<Catalog> {
+    let mut catalog = Catalog::with_template_dir(template_dir(args)?);
+    let _ = catalog.refresh_function_metadata();
+    Ok(catalog)
+}
+
+fn template_dir(args: &TemplateArgs) -> anyhow::Result<Option<PathBuf>> {
+    if let Some(dir) = &args.template_dir {
+        if !dir.is_dir() {
+            bail!("template directory {} does not exist", dir.display());
+        }
+    }
+    Ok(args.template_dir.clone())
+}
+
+impl From<&OutputArgs> for ScriptWriter {
+    fn from(args: &OutputArgs) -> Self {
+        ScriptWriter {
+            keep_backup: args.keep_backup,
+        }
+    }
+}
+
+impl From<&Cli> for ServerConfig {
+    fn from(cli: &Cli) -> Self {
+        ServerConfig {
+            host: cli.serve.host.clone(),
+            port: cli.serve.port,
+            auto_port: cli.serve.auto_port,
+            writer: (&cli.output).into(),
+        }
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct GenerateArgs {
+    /// JSON file describing the connected system (same shape the extension sends on stdin).
+    #[arg(long)]
+    pub system: Option<PathBuf>,
+
+    /// Project file, generated `.tsp` script, or JSON file containing a saved `sweep_model`,
+    /// either bare or wrapped as `{"sweep_model": ...}`.
+    #[arg(long)]
+    pub config: Option<PathBuf>,
+
+    /// Path of the generated script.
+    #[arg(long)]
+    pub out: PathBuf,
+
+    /// Line frequency of the instrument in Hz.
+    #[arg(long)]
+    pub line_frequency: Option<f64>,
+}
+
+/// Generates a script once, without starting the web server.
+///
+/// The sweep model is loaded from `args.config` (if any, otherwise it starts from the defaults
+/// of the catalog), the device list is created or updated from `args.system` (if any), the
+/// configuration is evaluated with the limits of the catalog and the resulting
+/// script is rendered from the templates of `catalog` and written to `args.out` with `writer`.
+///
+/// # Errors
+///
+/// Returns an error if an input file cannot be read or parsed, if no devices are found in
+/// the system configuration, or if the script cannot be generated or written.
+pub fn generate(args: &GenerateArgs, catalog: Catalog, writer: ScriptWriter) -> anyhow::Result<()> {
+    if args.system.is_none() && args.config.is_none() {
+        bail!("at least one of --system or --config must be provided");
+    }
+
+    let limits = catalog.sweep_limits();
+    let mut sweep_model = match &args.config {
+        Some(path) => load_sweep_model(path)?,
+        None => {
+            let mut sweep_model = SweepModel::new();
+            sweep_model.sweep_config.apply_defaults(&limits);
+            sweep_model
+        }
+    };
+    let sweep_config = &mut sweep_model.sweep_config;
+
+    if let Some(freq) = args.line_frequency {
+        sweep_config.global_parameters.set_line_frequency(freq);
+    }
+
+    if let Some(path) = &args.system {
+        let system_info = fs::read_to_string(path)
+            .with_context(|| format!("failed to read system file {}", path.display()))?;
+        if sweep_config.device_list.is_empty() {
+            if !sweep_config.create_device_list(&system_info) {
+                bail!("no devices found in system file {}", path.display());
+            }
+            sweep_config.auto_configure();
+        } else {
+            sweep_config.update_devices_for_changed_slots(&system_info);
+        }
+    }
+
+    sweep_config.evaluate(&limits);
+    if let Some(status_msg) = &sweep_config.status_msg {
+        tracing::warn!("{}", status_msg.message);
+    }
+
+    let mut script_model = ScriptModel::new(catalog);
+    script_model.initialize_scripts();
+    script_model.add_sweep();
+    script_model.add_data_report();
+
+    let status = script_model
+        .to_script(sweep_config, &args.out, writer)
+        .with_context(|| format!("failed to generate script {}", args.out.display()))?;
+    if status == WriteStatus::Unchanged {
+        tracing::info!("Script {} is unchanged", args.out.display());
+    }
+
+    Ok(())
+}
+
+#[derive(Args, Debug)]
+pub struct LintArgs {
+    /// Fail if warnings are found, too.
+    #[arg(long)]
+    pub deny_warnings: bool,
+}
+
+/// Checks the script templates, preferring the templates of `templates.template_dir` over
+/// the embedded ones, and prints the issues found to stdout.
+///
+/// # Errors
+///
+/// Returns an error if the template directory does not exist, or if errors (or warnings with
+/// `args.deny_warnings`) are found.
+pub fn lint(args: &LintArgs, templates: &TemplateArgs) -> anyhow::Result<()> {
+    let issues = lint_templates(template_dir(templates)?);
+    for issue in &issues {
+        println!("{issue}");
+    }
+
+    let errors = issues
+        .iter()
+        .filter(|issue| issue.severity == Severity::Error)
+        .count();
+    let warnings = issues.len() - errors;
+    println!("{errors} error(s), {warnings} warning(s)");
+    if errors > 0 || (args.deny_warnings && warnings > 0) {
+        bail!("template lint failed");
+    }
+    Ok(())
+}
+
+#[derive(Args, Debug)]
+pub struct LocateArgs {
+    /// Path of the generated script, its source map `<script>.map` is read.
+    #[arg(long)]
+    pub script: PathBuf,
+
+    /// One based number of the line of the script.
+    #[arg(long)]
+    pub line: usize,
+}
+
+/// Prints the template elements that generated line `args.line` of `args.script` to stdout,
+/// outermost first, as recorded in the source map written along with the script.
+///
+/// # Errors
+///
+/// Returns an error if the source map cannot be read or parsed, or if the line was not
+/// generated from a template element.
+pub fn locate(args: &LocateArgs) -> anyhow::Result<()> {
+    let path = source_map_path(&args.script);
+    let contents = fs::read_to_string(&path)
+        .with_context(|| format!("failed to read source map {}", path.display()))?;
+    let source_map: SourceMap = serde_json::from_str(&contents)
+        .with_context(|| format!("failed to parse source map {}", path.display()))?;
+    let Some(range) = source_map.lookup(args.line) else {
+        bail!(
+            "line {} of {} was not generated from a template element",
+            args.line,
+            args.script.display()
+        );
+    };
+
+    println!(
+        "{}:{}-{}",
+        args.script.display(),
+        range.first_line,
+        range.last_line
+    );
+    for (depth, element) in range.elements.iter().enumerate() {
+        println!("{:indent$}{element}", "", indent = 2 * (depth + 1));
+    }
+    Ok(())
+}
+
+/// Prints the JSON Schema of the message protocol to stdout.
+///
+/// # Errors
+///
+/// Returns an error if the schema cannot be serialized.
+pub fn print_schema() -> anyhow::Result<()> {
+    let schema = serde_json::to_string_pretty(&protocol::json_schema())?;
+    println!("{schema}");
+    Ok(())
+}
+
+fn load_sweep_model(path: &Path) -> anyhow::Result<SweepModel> {
+    if project::is_script(path) {
+        let project = Project::load(path)
+            .with_context(|| format!("failed to load script {}", path.display()))?;
+        return Ok(project.sweep_model);
+    }
+    let contents = fs::read_to_string(path)
+        .with_context(|| format!("failed to read config file {}", path.display()))?;
+    let mut value: serde_json::Value = serde_json::from_str(&contents)
+        .with_context(|| format!("failed to parse config file {}", path.display()))?;
+    if value.get("format_version").is_some() {
+        let project = Project::from_json(&contents)
+            .with_context(|| format!("failed to load project file {}", path.display()))?;
+        return Ok(project.sweep_model);
+    }
+    // Saved sessions wrap the model as {"sweep_model": {...}}
+    if let Some(sweep_model) = value.get_mut("sweep_model") {
+        value = sweep_model.take();
+    }
+    serde_json::from_value(value)
+        .with_context(|| format!("config file {} is not a valid sweep model", path.display()))
+}
@@ -1 +1,5 @@
+#![cfg_attr(coverage_nightly, feature(coverage_attribute))]
+
 pub mod back_end;
+pub mod cli;
+pub mod logging;
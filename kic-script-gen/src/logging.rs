@@ -0,0 +1,157 @@
+//! Logging setup.
+//!
+//! Log records are written to stderr and, as JSON lines, to a daily rolling file. They never
+//! go to stdout, which carries the protocol messages read by the host extension. Recent
+//! warnings and errors are also kept in memory so they can be reported by `get_diagnostics`.
+
+use std::collections::VecDeque;
+use std::fmt::Write as _;
+use std::io::IsTerminal;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use anyhow::Context as _;
+use clap::Args;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tracing::field::{Field, Visit};
+use tracing::{Event, Level, Subscriber};
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_subscriber::layer::{Context, SubscriberExt};
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{EnvFilter, Layer};
+
+/// Number of warnings and errors kept for diagnostics.
+pub const RECENT_ERRORS_LIMIT: usize = 100;
+
+static RECENT_ERRORS: Mutex<VecDeque<LogRecord>> = Mutex::new(VecDeque::new());
+
+#[derive(Args, Debug, Clone)]
+pub struct LogArgs {
+    /// Log filter, e.g. `info` or `kic_script_gen=debug,xml_handler=warn`.
+    #[arg(
+        long,
+        global = true,
+        env = "KIC_SCRIPT_GEN_LOG",
+        default_value = "info"
+    )]
+    pub log_level: String,
+
+    /// Directory of the log files, defaults to `kic-script-gen` in the temp directory.
+    #[arg(long, global = true, env = "KIC_SCRIPT_GEN_LOG_DIR")]
+    pub log_dir: Option<PathBuf>,
+}
+
+/// A warning or error kept for diagnostics.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+pub struct LogRecord {
+    pub timestamp: String,
+    pub level: String,
+    pub target: String,
+    pub message: String,
+}
+
+/// Returns the most recent warnings and errors, oldest first.
+pub fn recent_errors() -> Vec<LogRecord> {
+    RECENT_ERRORS
+        .lock()
+        .map(|records| records.iter().cloned().collect())
+        .unwrap_or_default()
+}
+
+/// Installs the global subscriber.
+///
+/// The returned guard flushes the log file when dropped and must be kept alive for the
+/// lifetime of the program.
+///
+/// # Errors
+///
+/// Returns an error if the log filter is invalid or the log directory cannot be created.
+pub fn init(args: &LogArgs) -> anyhow::Result<WorkerGuard> {
+    let filter = EnvFilter::try_new(&args.log_level)
+        .with_context(|| format!("invalid log filter '{}'", args.log_level))?;
+    let log_dir = args
+        .log_dir
+        .clone()
+        .unwrap_or_else(|| std::env::temp_dir().join("kic-script-gen"));
+    std::fs::create_dir_all(&log_dir)
+        .with_context(|| format!("failed to create log directory {}", log_dir.display()))?;
+
+    let file_appender = tracing_appender::rolling::daily(&log_dir, "kic-script-gen.log");
+    let (file_writer, guard) = tracing_appender::non_blocking(file_appender);
+
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(
+            tracing_subscriber::fmt::layer()
+                .with_ansi(std::io::stderr().is_terminal())
+                .with_writer(std::io::stderr),
+        )
+        .with(
+            tracing_subscriber::fmt::layer()
+                .json()
+                .with_writer(file_writer),
+        )
+        .with(RecentErrorsLayer)
+        .try_init()
+        .context("failed to install the logger")?;
+
+    Ok(guard)
+}
+
+/// Keeps the last [`RECENT_ERRORS_LIMIT`] warnings and errors in [`RECENT_ERRORS`].
+struct RecentErrorsLayer;
+
+impl<S: Subscriber> Layer<S> for RecentErrorsLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+        if *metadata.level() > Level::WARN {
+            return;
+        }
+        let mut visitor = MessageVisitor::default();
+        event.record(&mut visitor);
+        let record = LogRecord {
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            level: metadata.level().to_string(),
+            // events forwarded from the `log` crate carry their target as a field
+            target: visitor
+                .log_target
+                .unwrap_or_else(|| metadata.target().to_string()),
+            message: visitor.message,
+        };
+        if let Ok(mut records) = RECENT_ERRORS.lock() {
+            if records.len() == RECENT_ERRORS_LIMIT {
+                records.pop_front();
+            }
+            records.push_back(record);
+        }
+    }
+}
+
+/// Formats the message of an event followed by its other fields as `name=value`.
+#[derive(Default)]
+struct MessageVisitor {
+    message: String,
+    log_target: Option<String>,
+}
+
+impl Visit for MessageVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "log.target" {
+            self.log_target = Some(value.to_string());
+        } else {
+            self.record_debug(field, &value);
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name().starts_with("log.") {
+            // location of events forwarded from the `log` crate
+        } else if field.name() == "message" {
+            let fields = std::mem::take(&mut self.message);
+            let _ = write!(self.message, "{value:?}{fields}");
+        } else {
+            let _ = write!(self.message, " {}={value:?}", field.name());
+        }
+    }
+}
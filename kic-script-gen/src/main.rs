@@ -1,19 +1,30 @@
+use clap::Parser;
 use kic_script_gen::back_end::client_server::start;
-use script_gen_manager::{catalog, script_component::script::ScriptModel};
+use kic_script_gen::cli::{generate, lint, load_catalog, locate, print_schema, Cli, Command};
+use kic_script_gen::logging;
 
 #[actix_web::main]
 async fn main() -> anyhow::Result<()> {
-    println!("Welcome to KIC Script Generator!");
+    let cli = Cli::parse();
+    let _log_guard = logging::init(&cli.log)?;
 
-    let mut catalog = catalog::Catalog::new();
-    catalog.refresh_function_metadata();
+    if let Some(Command::Schema) = &cli.command {
+        return print_schema();
+    }
+    if let Some(Command::Lint(args)) = &cli.command {
+        return lint(args, &cli.templates);
+    }
+    if let Some(Command::Locate(args)) = &cli.command {
+        return locate(args);
+    }
+    let catalog = load_catalog(&cli.templates)?;
+    if let Some(Command::Generate(args)) = &cli.command {
+        return generate(args, catalog, (&cli.output).into());
+    }
 
-    let mut script_model = ScriptModel::new(catalog);
-    script_model.initialize_scripts();
-    script_model.add_sweep();
-    script_model.add_data_report();
+    tracing::info!("Welcome to KIC Script Generator!");
 
-    start(script_model).await?;
+    start(catalog, (&cli).into()).await?;
 
     Ok(())
 }
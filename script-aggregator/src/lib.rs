@@ -1,2 +1,5 @@
+#![cfg_attr(coverage_nightly, feature(coverage_attribute))]
+
 mod indent_engine;
 pub mod script_buffer;
+pub mod source_map;
@@ -1,6 +1,7 @@
 use std::cmp;
 
 use crate::indent_engine::IndentEngine;
+use crate::source_map::{Iteration, SourceElement, SourceMap};
 
 #[derive(Debug)]
 pub struct ScriptBuffer {
@@ -19,6 +20,17 @@ pub struct ScriptBuffer {
     preamble_indenter: IndentEngine,
     body_indenter: IndentEngine,
     postamble_indenter: IndentEngine,
+
+    /// The template elements that enclose the appended statements, outermost first
+    sources: Vec<SourceElement>,
+    /// The distinct values of `sources` that statements were appended with
+    locations: Vec<Vec<SourceElement>>,
+    /// The index of `sources` in `locations`, if it was recorded since `sources` changed
+    location: Option<usize>,
+    /// The index in `locations` of the source of each line, per portion of the script
+    preamble_sources: Vec<Option<usize>>,
+    body_sources: Vec<Option<usize>>,
+    postamble_sources: Vec<Option<usize>>,
 }
 
 impl ScriptBuffer {
@@ -42,6 +54,13 @@ impl ScriptBuffer {
             preamble_indenter: IndentEngine::new(String::from("    ")),
             body_indenter: IndentEngine::new(String::from("    ")),
             postamble_indenter: IndentEngine::new(String::from("    ")),
+
+            sources: Vec::new(),
+            locations: Vec::new(),
+            location: None,
+            preamble_sources: Vec::new(),
+            body_sources: Vec::new(),
+            postamble_sources: Vec::new(),
         }
     }
 
@@ -93,6 +112,8 @@ impl ScriptBuffer {
     ///
     /// * `statement` - The statement to be appended to the body.
     pub fn body_append(&mut self, statement: String) {
+        let location = self.location();
+        let start = self.body.len();
         if self.auto_indent {
             self.body_indenter.apply(&mut self.body, &statement);
         } else if self.indent_enabled && self.indent.is_some() {
@@ -106,6 +127,7 @@ impl ScriptBuffer {
         if let Some(eol) = &self.eol {
             self.body.push_str(eol);
         }
+        record(&mut self.body_sources, &self.body[start..], location);
     }
 
     /// Appends a statement to the "postamble" portion of the script.
@@ -114,6 +136,8 @@ impl ScriptBuffer {
     ///
     /// * `statement` - The statement to be appended to the postamble.
     pub fn postamble_append(&mut self, statement: String) {
+        let location = self.location();
+        let start = self.postamble.len();
         if self.auto_indent {
             self.postamble_indenter
                 .apply(&mut self.postamble, &statement);
@@ -128,6 +152,11 @@ impl ScriptBuffer {
         if let Some(eol) = &self.eol {
             self.postamble.push_str(eol);
         }
+        record(
+            &mut self.postamble_sources,
+            &self.postamble[start..],
+            location,
+        );
     }
 
     /// Appends a statement to the "preamble" portion of the script.
@@ -136,6 +165,8 @@ impl ScriptBuffer {
     ///
     /// * `statement` - The statement to be appended to the preamble.
     pub fn preamble_append(&mut self, statement: String) {
+        let location = self.location();
+        let start = self.preamble.len();
         if self.auto_indent {
             self.preamble_indenter.apply(&mut self.preamble, &statement);
         } else if self.indent_enabled && self.indent.is_some() {
@@ -149,6 +180,90 @@ impl ScriptBuffer {
         if let Some(eol) = &self.eol {
             self.preamble.push_str(eol);
         }
+        record(
+            &mut self.preamble_sources,
+            &self.preamble[start..],
+            location,
+        );
+    }
+
+    /// Appends the contents of `script` to the "preamble" portion of the script, keeping the
+    /// template elements its lines were generated from.
+    ///
+    /// # Arguments
+    ///
+    /// * `script` - The script buffer to be appended to the preamble.
+    pub fn preamble_append_script(&mut self, script: &ScriptBuffer) {
+        let start = self.preamble_sources.len();
+        self.preamble_append(script.to_string());
+        for (line, source) in (start..self.preamble_sources.len()).zip(script.line_sources()) {
+            self.preamble_sources[line] = source.map(|elements| {
+                self.locations.push(elements.to_vec());
+                self.locations.len() - 1
+            });
+        }
+    }
+
+    /// Enters a template element, the statements appended until it is left are recorded as
+    /// generated from it.
+    ///
+    /// # Arguments
+    ///
+    /// * `element` - The template element, nested in the elements entered before.
+    pub fn enter_source(&mut self, element: SourceElement) {
+        self.sources.push(element);
+        self.location = None;
+    }
+
+    /// Sets the iteration of the `repeat` of the template element entered last.
+    ///
+    /// # Arguments
+    ///
+    /// * `iteration` - The current iteration, or `None` outside of the repeat.
+    pub fn set_iteration(&mut self, iteration: Option<Iteration>) {
+        if let Some(element) = self.sources.last_mut() {
+            element.iteration = iteration;
+            self.location = None;
+        }
+    }
+
+    /// Leaves the template element entered last.
+    pub fn leave_source(&mut self) {
+        self.sources.pop();
+        self.location = None;
+    }
+
+    /// Returns the template elements each line of the script was generated from.
+    ///
+    /// # Returns
+    ///
+    /// * A `SourceMap` of the lines of [`ScriptBuffer::to_string`].
+    pub fn source_map(&self) -> SourceMap {
+        let mut source_map = SourceMap::default();
+        for (index, source) in self.line_sources().enumerate() {
+            if let Some(elements) = source {
+                source_map.push(index + 1, elements);
+            }
+        }
+        source_map
+    }
+
+    /// Returns the template elements of each line, in the order of [`ScriptBuffer::to_string`].
+    fn line_sources(&self) -> impl Iterator<Item = Option<&[SourceElement]>> {
+        self.preamble_sources
+            .iter()
+            .chain(&self.body_sources)
+            .chain(&self.postamble_sources)
+            .map(|location| location.map(|index| self.locations[index].as_slice()))
+    }
+
+    /// Returns the index in `locations` of the template elements entered, if any.
+    fn location(&mut self) -> Option<usize> {
+        if self.location.is_none() && !self.sources.is_empty() {
+            self.locations.push(self.sources.clone());
+            self.location = Some(self.locations.len() - 1);
+        }
+        self.location
     }
 
     /// Generates a unique name based on the given basename.
@@ -189,3 +304,9 @@ impl ScriptBuffer {
         script
     }
 }
+
+/// Records `location` as the source of each line ended in `appended`.
+fn record(sources: &mut Vec<Option<usize>>, appended: &str, location: Option<usize>) {
+    let lines = appended.bytes().filter(|byte| *byte == b'\n').count();
+    sources.extend(std::iter::repeat_n(location, lines));
+}
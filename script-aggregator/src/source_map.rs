@@ -0,0 +1,182 @@
+//! Maps the lines of a generated script to the template elements they were generated from.
+//!
+//! The [`ScriptBuffer`](crate::script_buffer::ScriptBuffer) records the enclosing template
+//! elements of every line it appends. The resulting [`SourceMap`] is saved next to the script,
+//! so that a line of the script can be looked up without generating it again.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// A template element, such as a `<composite>` or a `<snippet>`, that generated lines of a
+/// script.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SourceElement {
+    /// The template the element is defined in, e.g. `MP5000_SWEEP_XML`.
+    pub resource: String,
+    /// The tag of the element, e.g. `composite`.
+    pub element: String,
+    /// The name of the element, empty if it has none.
+    pub name: String,
+    /// The byte offset of the element in `resource`.
+    pub position: u64,
+    /// The iteration of the `repeat` of the element, if it has one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub iteration: Option<Iteration>,
+}
+
+impl SourceElement {
+    pub fn new(resource: &str, element: &str, name: &str, position: u64) -> Self {
+        SourceElement {
+            resource: resource.to_string(),
+            element: element.to_string(),
+            name: name.to_string(),
+            position,
+            iteration: None,
+        }
+    }
+}
+
+impl fmt::Display for SourceElement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} (position {}): <{}",
+            self.resource, self.position, self.element
+        )?;
+        if !self.name.is_empty() {
+            write!(f, " name=\"{}\"", self.name)?;
+        }
+        write!(f, ">")?;
+        if let Some(iteration) = &self.iteration {
+            write!(f, " {iteration}")?;
+        }
+        Ok(())
+    }
+}
+
+/// An iteration of the `repeat` of a template element.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Iteration {
+    /// The name of the repeated list, e.g. `SWEEP-DEVICE`.
+    pub repeat: String,
+    /// The current item of the list.
+    pub item: String,
+    /// The one based index of the item.
+    pub loop_count: usize,
+}
+
+impl fmt::Display for Iteration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} loop {} ({})",
+            self.repeat, self.loop_count, self.item
+        )
+    }
+}
+
+/// Consecutive lines of a script generated by the same template elements.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SourceRange {
+    /// The one based number of the first line.
+    pub first_line: usize,
+    /// The one based number of the last line, inclusive.
+    pub last_line: usize,
+    /// The template elements the lines were generated from, outermost first.
+    pub elements: Vec<SourceElement>,
+}
+
+/// Maps the lines of a script to the template elements they were generated from.
+///
+/// Lines that were not generated from a template element, such as the embedded
+/// configuration, are not mapped.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SourceMap {
+    /// The mapped lines, in ascending order.
+    pub ranges: Vec<SourceRange>,
+}
+
+impl SourceMap {
+    /// Returns the lines that contain the one based `line`, if it is mapped.
+    pub fn lookup(&self, line: usize) -> Option<&SourceRange> {
+        let index = self.ranges.partition_point(|range| range.last_line < line);
+        self.ranges
+            .get(index)
+            .filter(|range| range.first_line <= line)
+    }
+
+    /// Maps `line`, which follows the lines mapped so far, to `elements`.
+    pub(crate) fn push(&mut self, line: usize, elements: &[SourceElement]) {
+        if let Some(last) = self.ranges.last_mut() {
+            if last.last_line + 1 == line && last.elements == elements {
+                last.last_line = line;
+                return;
+            }
+        }
+        self.ranges.push(SourceRange {
+            first_line: line,
+            last_line: line,
+            elements: elements.to_vec(),
+        });
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod tests {
+    use super::*;
+    use crate::script_buffer::ScriptBuffer;
+
+    #[test]
+    fn test_source_map() {
+        let mut buffer = ScriptBuffer::new();
+        buffer.set_auto_indent(true);
+        buffer.preamble_append("-- header".to_string());
+        buffer.enter_source(SourceElement::new("SWEEP_XML", "composite", "sweep", 10));
+        for (index, item) in ["smu1", "smu2"].iter().enumerate() {
+            buffer.set_iteration(Some(Iteration {
+                repeat: "SWEEP-DEVICE".to_string(),
+                item: item.to_string(),
+                loop_count: index + 1,
+            }));
+            buffer.enter_source(SourceElement::new("SWEEP_XML", "snippet", "", 42));
+            buffer.body_append(format!("{item}.reset()"));
+            buffer.body_append(format!("{item}.source.output = 1"));
+            buffer.leave_source();
+        }
+        buffer.leave_source();
+        buffer.body_append("end".to_string());
+
+        let mut aux = ScriptBuffer::new();
+        aux.enter_source(SourceElement::new("INIT_XML", "snippet", "aux", 7));
+        aux.body_append("local a = 1".to_string());
+        buffer.preamble_append_script(&aux);
+
+        assert_eq!(buffer.to_string().lines().count(), 7);
+        let source_map = buffer.source_map();
+        assert_eq!(source_map.ranges.len(), 3);
+        assert_eq!(source_map.lookup(1), None);
+        assert_eq!(source_map.lookup(7), None);
+
+        let aux = source_map.lookup(2).unwrap();
+        assert_eq!(
+            aux.elements[0].to_string(),
+            "INIT_XML (position 7): <snippet name=\"aux\">"
+        );
+
+        let second = source_map.lookup(6).unwrap();
+        assert_eq!((second.first_line, second.last_line), (5, 6));
+        assert_eq!(
+            second.elements[0].to_string(),
+            "SWEEP_XML (position 10): <composite name=\"sweep\"> SWEEP-DEVICE loop 2 (smu2)"
+        );
+        assert_eq!(second.elements[1].name, "");
+
+        let json = serde_json::to_string(&source_map).unwrap();
+        assert_eq!(
+            serde_json::from_str::<SourceMap>(&json).unwrap(),
+            source_map
+        );
+    }
+}
@@ -0,0 +1,137 @@
+//! Measures how long it takes to render the sweep script from the embedded templates
+//! (`MP5000Sweep.xml`).
+//!
+//! Run with `cargo bench -p script-gen-manager --bench render`. Each node holds three MSMU60-2
+//! modules, six channels, and all channels of all nodes are used, split evenly into bias, step
+//! and sweep channels.
+
+use std::{
+    hint::black_box,
+    time::{Duration, Instant},
+};
+
+use script_gen_manager::{
+    catalog::Catalog,
+    device::Device,
+    model::{
+        sweep_data::{sweep_config::SweepConfig, sweep_limits::SweepLimits},
+        system_info::Slot,
+    },
+    script_component::script::ScriptModel,
+};
+
+/// Slots of each node, all holding an MSMU60-2.
+const SLOTS: [&str; 3] = ["slot[1]", "slot[2]", "slot[3]"];
+
+/// Time each configuration is rendered for by `cargo bench`.
+const DURATION: Duration = Duration::from_secs(2);
+
+/// Returns the channels of `nodes` nodes, the first one being the local node.
+fn device_list(nodes: usize) -> Vec<Device> {
+    let mut device_list = Vec::new();
+    for node in 0..nodes {
+        let node_id = match node {
+            0 => "localnode".to_string(),
+            _ => format!("node[{}]", node + 1),
+        };
+        for slot_id in SLOTS {
+            let slot = Slot {
+                slot_id: slot_id.to_string(),
+                module: "MSMU60-2".to_string(),
+            };
+            for chan_num in 1..=2 {
+                device_list.push(Device::new(
+                    node_id.clone(),
+                    "MP5103".to_string(),
+                    &slot,
+                    chan_num,
+                ));
+            }
+        }
+    }
+    device_list
+}
+
+fn sweep_config(nodes: usize, points: i32, list: bool) -> SweepConfig {
+    let mut sweep_config = SweepConfig::new();
+    sweep_config.device_list = device_list(nodes);
+    // one step and one sweep channel
+    sweep_config.auto_configure();
+    for chan_type in ["bias", "step", "sweep"]
+        .into_iter()
+        .cycle()
+        .take(sweep_config.device_list.len() - 2)
+    {
+        sweep_config.add_channel(chan_type.to_string());
+    }
+    sweep_config.step_global_parameters.list_step = list;
+    sweep_config.sweep_global_parameters.sweep_points.value = points;
+    sweep_config.sweep_global_parameters.list_sweep = list;
+    // the default limits allow more points than the templates
+    sweep_config.evaluate(&SweepLimits::default());
+    sweep_config
+}
+
+fn bench(name: &str, duration: Duration, mut f: impl FnMut()) {
+    // warm up
+    f();
+    if duration.is_zero() {
+        return;
+    }
+    let mut iterations = 0_u32;
+    let start = Instant::now();
+    while start.elapsed() < duration {
+        f();
+        iterations += 1;
+    }
+    let per_iteration = start.elapsed().as_secs_f64() / f64::from(iterations);
+    println!(
+        "{name:<32} {:>10.1} us/iter ({iterations} iterations)",
+        per_iteration * 1e6
+    );
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    // test runners list the tests of all targets
+    if args.iter().any(|arg| arg == "--list") {
+        return;
+    }
+    // `cargo bench` passes `--bench`, `cargo test --benches` only checks that each
+    // configuration renders
+    let duration = if args.iter().any(|arg| arg == "--bench") {
+        DURATION
+    } else {
+        Duration::ZERO
+    };
+
+    let mut catalog = Catalog::new();
+    catalog
+        .refresh_function_metadata()
+        .expect("embedded templates are valid");
+
+    for (nodes, points, list) in [
+        (1, 10, false),
+        (1, 1000, false),
+        (1, 100, true),
+        (1, 5000, true),
+        (8, 10, false),
+        (8, 100, true),
+        (32, 10, false),
+        (32, 100, true),
+    ] {
+        let sweep_config = sweep_config(nodes, points, list);
+        let name = format!(
+            "{} channels, {}{points} points",
+            sweep_config.device_list.len(),
+            if list { "list of " } else { "" }
+        );
+        let mut script_model = ScriptModel::new(catalog.clone());
+        script_model.initialize_scripts();
+        script_model.add_sweep();
+        script_model.add_data_report();
+        bench(&name, duration, || {
+            black_box(script_model.render(&sweep_config).expect("script renders"));
+        });
+    }
+}
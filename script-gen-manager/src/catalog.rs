@@ -1,35 +1,116 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, path::PathBuf, sync::Arc};
 
-use xml_handler::{generic_parser, group::Group};
+use xml_handler::{
+    error::XMLHandlerError,
+    generic_parser,
+    group::Group,
+    templates::{LoadedResource, ResourceOrigin, TemplateLoader},
+};
+
+use crate::model::sweep_data::sweep_limits::SweepLimits;
 
 /// Represents a catalog of function metadata.
 #[derive(Debug, Clone)]
 pub struct Catalog {
     /// A map of function metadata, keyed by function type (e.g., Initialize, Finalize, etc.)
     pub function_metadata_map: HashMap<String, Group>,
+    /// Directory whose templates override the embedded ones, if any.
+    pub template_dir: Option<PathBuf>,
+    /// The templates of the last refresh and where they were loaded from.
+    pub resources: Vec<LoadedResource>,
+    /// The error of the last refresh, if the templates could not be parsed.
+    pub error: Option<Arc<XMLHandlerError>>,
 }
 
 impl Catalog {
     pub fn new() -> Self {
+        Self::with_template_dir(None)
+    }
+
+    /// Creates a catalog that loads templates from `template_dir` before falling back to the
+    /// embedded ones.
+    pub fn with_template_dir(template_dir: Option<PathBuf>) -> Self {
         Catalog {
             function_metadata_map: HashMap::new(),
+            template_dir,
+            resources: Vec::new(),
+            error: None,
         }
     }
 
     /// Refreshes the function metadata by parsing XML data.
     ///
-    /// This method updates the `function_metadata_map` with the parsed XML data.
-    pub fn refresh_function_metadata(&mut self) {
-        match generic_parser::parse_xml() {
+    /// This method replaces the `function_metadata_map` with the parsed XML data, groups that
+    /// are no longer defined are dropped. If the templates cannot be parsed, the map is left
+    /// empty and the error is kept in `error` (and returned) so that scripts rendered from the
+    /// catalog report it.
+    ///
+    /// # Errors
+    ///
+    /// Returns the `XMLHandlerError` of a template that cannot be loaded or parsed.
+    pub fn refresh_function_metadata(&mut self) -> Result<(), Arc<XMLHandlerError>> {
+        let mut loader = TemplateLoader::new(self.template_dir.clone());
+        self.function_metadata_map.clear();
+        self.error = match generic_parser::parse_xml(&mut loader) {
             Ok(res) => {
                 for item in res {
                     self.function_metadata_map.insert(item.type_.clone(), item);
                 }
+                None
             }
             Err(e) => {
-                //eprintln!("Error: {:?}", e);
-                //return Err(e.into());
+                tracing::error!("Failed to parse script templates: {e}");
+                Some(Arc::new(e))
+            }
+        };
+
+        for resource in loader.loaded() {
+            match resource.origin {
+                ResourceOrigin::Embedded => {
+                    tracing::debug!("Template {} loaded from {}", resource.name, resource.origin);
+                }
+                ResourceOrigin::File(_) => {
+                    tracing::info!("Template {} loaded from {}", resource.name, resource.origin);
+                }
             }
         }
+        self.resources = loader.loaded().to_vec();
+        self.error.clone().map_or(Ok(()), Err)
+    }
+
+    /// Returns the limits of the sweep model defined by the variables of the "Sweep" group,
+    /// or the default limits if the catalog has no such group.
+    pub fn sweep_limits(&self) -> SweepLimits {
+        self.function_metadata_map
+            .get("Sweep")
+            .map_or_else(SweepLimits::default, |group| {
+                SweepLimits::from_variables(&group.variable_list)
+            })
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_refresh_replaces_groups() {
+        let mut catalog = Catalog::new();
+        catalog.refresh_function_metadata().unwrap();
+        let groups = catalog.function_metadata_map.len();
+        assert!(catalog.function_metadata_map.contains_key("Sweep"));
+        assert!(catalog
+            .resources
+            .iter()
+            .all(|resource| resource.origin == ResourceOrigin::Embedded));
+
+        let sweep = catalog.function_metadata_map["Sweep"].clone();
+        catalog
+            .function_metadata_map
+            .insert("Removed".to_string(), sweep);
+        catalog.refresh_function_metadata().unwrap();
+        assert_eq!(catalog.function_metadata_map.len(), groups);
+        assert!(!catalog.function_metadata_map.contains_key("Removed"));
     }
 }
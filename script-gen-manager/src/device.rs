@@ -9,11 +9,12 @@ use crate::{
     },
     model::system_info::Slot,
 };
+use schemars::JsonSchema;
 use serde::{Deserialize, Deserializer, Serialize};
 // use tsp_toolkit_kic_lib::instrument::info::InstrumentInfo;
 
 /// Represents the type of device in the mainframe slot.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub enum DeviceType {
     Smu,
     Psu,
@@ -31,7 +32,7 @@ impl fmt::Display for DeviceType {
 }
 
 /// Represents a device in one of the mainframe slots.
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, JsonSchema)]
 pub struct Device {
     pub node_id: String,
     pub slot_id: String,
@@ -97,6 +98,21 @@ impl<'de> Deserialize<'de> for Device {
     }
 }
 
+/// Devices are equal if their serialized fields are, the metadata follows from the device type.
+impl PartialEq for Device {
+    fn eq(&self, other: &Self) -> bool {
+        self.node_id == other.node_id
+            && self.slot_id == other.slot_id
+            && self.chan_num == other.chan_num
+            && self._id == other._id
+            && self.model == other.model
+            && self.device_type == other.device_type
+            && self.in_use == other.in_use
+            && self.is_valid == other.is_valid
+            && self.fw_version == other.fw_version
+    }
+}
+
 impl Default for Device {
     fn default() -> Self {
         Device {
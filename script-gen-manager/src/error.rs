@@ -0,0 +1,54 @@
+use std::{path::PathBuf, sync::Arc};
+
+use thiserror::Error;
+use xml_handler::error::XMLHandlerError;
+
+/// Errors when generating or writing a script.
+#[derive(Error, Debug)]
+pub enum ScriptGenError {
+    /// The script could not be written
+    #[error("Failed to write {}: {source}", path.display())]
+    IOError {
+        /// The file or directory that could not be written
+        path: PathBuf,
+        /// The original `[std::io::Error]`
+        source: std::io::Error,
+    },
+
+    /// Template groups the script is built from are not in the catalog
+    #[error("Missing script template groups: {}", groups.join(", "))]
+    MissingTemplateGroups {
+        /// Names of the missing groups
+        groups: Vec<String>,
+    },
+
+    /// The script templates of the catalog could not be loaded
+    #[error("Failed to load the script templates: {source}")]
+    CatalogError {
+        /// The error of the catalog
+        source: Arc<XMLHandlerError>,
+    },
+
+    /// A script template could not be rendered
+    #[error("Failed to render the script templates: {source}")]
+    TemplateError {
+        /// The original error
+        #[from]
+        source: XMLHandlerError,
+    },
+
+    /// No path to write the script to was set
+    #[error("No output path is set for the script")]
+    NoOutputPath,
+
+    /// The configuration could not be embedded in the script, or the source map of the script
+    /// could not be serialized
+    #[error("Failed to serialize the configuration or source map: {source}")]
+    SerializationError {
+        /// The original error
+        #[from]
+        source: serde_json::Error,
+    },
+}
+
+pub type Result<T> = std::result::Result<T, ScriptGenError>;
@@ -1,5 +1,10 @@
+#![cfg_attr(coverage_nightly, feature(coverage_attribute))]
+
 pub mod catalog;
 pub mod device;
+pub mod error;
 pub mod instr_metadata;
 pub mod model;
+pub mod project;
 pub mod script_component;
+pub mod sweep_results;
@@ -1,3 +1,4 @@
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 use super::default_channel::CommonChanAttributes;
@@ -6,7 +7,7 @@ use crate::{
     model::sweep_data::parameters::ParameterFloat,
 };
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct BiasChannel {
     pub common_chan_attributes: CommonChanAttributes,
     pub bias: ParameterFloat,
@@ -45,7 +46,7 @@ impl BiasChannel {
             }
         } else {
             //TODO: handle error condition
-            println!("bias.unit is None");
+            tracing::warn!("bias.unit is None");
         }
     }
 }
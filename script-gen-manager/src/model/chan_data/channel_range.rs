@@ -1,8 +1,9 @@
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 use crate::instr_metadata::base_metadata::BaseMetadata;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct ChannelRange {
     pub range: Vec<String>,
     pub value: String,
@@ -16,6 +17,13 @@ pub struct ChannelRange {
     pub overrange_scale: f64,
 }
 
+/// Ranges are equal if their serialized fields are, the limits follow from the device.
+impl PartialEq for ChannelRange {
+    fn eq(&self, other: &Self) -> bool {
+        self.range == other.range && self.value == other.value
+    }
+}
+
 impl ChannelRange {
     pub fn new() -> Self {
         ChannelRange {
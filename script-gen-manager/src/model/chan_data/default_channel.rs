@@ -1,3 +1,4 @@
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
@@ -12,7 +13,7 @@ use crate::{
 
 use super::{channel_range::ChannelRange, region_map::RegionMapMetadata};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct CommonChanAttributes {
     pub uuid: String,
     pub chan_name: String,
@@ -29,6 +30,22 @@ pub struct CommonChanAttributes {
     pub device_id: String,
 }
 
+/// Channels are equal if their serialized fields are, `device` follows from `device_id`.
+impl PartialEq for CommonChanAttributes {
+    fn eq(&self, other: &Self) -> bool {
+        self.uuid == other.uuid
+            && self.chan_name == other.chan_name
+            && self.source_function == other.source_function
+            && self.meas_function == other.meas_function
+            && self.source_range == other.source_range
+            && self.meas_range == other.meas_range
+            && self.source_limiti == other.source_limiti
+            && self.source_limitv == other.source_limitv
+            && self.sense_mode == other.sense_mode
+            && self.device_id == other.device_id
+    }
+}
+
 impl CommonChanAttributes {
     pub fn new(chan_name: String, device: Device) -> Self {
         let device_id = &device.get_id();
@@ -78,7 +95,7 @@ impl CommonChanAttributes {
             }
             DeviceType::Unknown => {
                 //todo: handle error
-                println!("Unknown device type");
+                tracing::warn!("Unknown device type");
             }
         }
         self.source_function.value = BaseMetadata::FUNCTION_VOLTAGE.to_string();
@@ -1,3 +1,4 @@
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 use super::default_channel::CommonChanAttributes;
@@ -7,7 +8,7 @@ use crate::{
     model::sweep_data::parameters::{ParameterFloat, ParameterString},
 };
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct StartStopChannel {
     pub common_chan_attributes: CommonChanAttributes,
     pub start: ParameterFloat,
@@ -139,7 +140,7 @@ impl StartStopChannel {
             }
         } else {
             //TODO: handle error condition
-            println!("start.unit is None");
+            tracing::warn!("start.unit is None");
         }
 
         if self.style.value == BaseMetadata::STYLE_LOG.to_string() {
@@ -180,7 +181,7 @@ impl StartStopChannel {
             }
         } else {
             //TODO: handle error condition
-            println!("bias.unit is None");
+            tracing::warn!("bias.unit is None");
         }
 
         if self.style.value == BaseMetadata::STYLE_LOG.to_string() {
@@ -1,8 +1,9 @@
 use super::start_stop_channel::StartStopChannel;
 use crate::device::Device;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct StepChannel {
     pub start_stop_channel: StartStopChannel,
 }
@@ -1,12 +1,13 @@
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use super::sweep_timing_config::SweepTimingConfig;
+use super::{sweep_limits::SweepLimits, sweep_timing_config::SweepTimingConfig};
 
 fn default_overhead_time() -> f64 {
     78e-6
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, JsonSchema)]
 #[serde(default)]
 pub struct GlobalParameters {
     pub sweep_timing_config: SweepTimingConfig,
@@ -29,8 +30,8 @@ impl GlobalParameters {
     pub fn new() -> Self {
         GlobalParameters::default()
     }
-    pub fn evaluate(&mut self) {
-        self.sweep_timing_config.evaluate();
+    pub fn evaluate(&mut self, limits: &SweepLimits) {
+        self.sweep_timing_config.evaluate(limits);
     }
     pub fn set_line_frequency(&mut self, frequency: f64) {
         self.line_frequency = frequency;
@@ -0,0 +1,643 @@
+//! Import and export of step and sweep list values as CSV or TSV.
+//!
+//! Each column holds the list of one channel, each row one point. The first row may name the
+//! channels (by channel name or device ID), otherwise the columns are assigned to the channels
+//! in order. Values are plain numbers or numbers with an SI prefix and an optional unit, such as
+//! `10m`, `2.5 µA` or `1e-3`.
+
+use std::fmt::Write as _;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    instr_metadata::base_metadata::BaseMetadata,
+    model::chan_data::start_stop_channel::StartStopChannel,
+};
+
+use super::{parameters::ParameterFloat, sweep_config::SweepConfig, sweep_limits::SweepLimits};
+
+/// The lists a CSV file is imported into or exported from.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ListTarget {
+    Step,
+    Sweep,
+}
+
+impl ListTarget {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ListTarget::Step => "step",
+            ListTarget::Sweep => "sweep",
+        }
+    }
+
+    /// Smallest and largest number of points of the lists.
+    fn points_range(self, limits: &SweepLimits) -> (usize, usize) {
+        match self {
+            ListTarget::Step => limits.step_points.count_range(),
+            ListTarget::Sweep => limits.sweep_points.count_range(),
+        }
+    }
+}
+
+/// Errors when importing list values.
+#[derive(Error, Debug, PartialEq)]
+pub enum ListCsvError {
+    /// The file holds no values
+    #[error("The list file is empty")]
+    Empty,
+
+    /// A cell is not a number
+    #[error("Invalid value '{value}' in line {line}, column {column}")]
+    InvalidValue {
+        /// 1-based line number
+        line: usize,
+        /// 1-based column number
+        column: usize,
+        /// The cell contents
+        value: String,
+    },
+
+    /// A row has a different number of cells than the first row
+    #[error("Line {line} has {found} values, expected {expected}")]
+    RaggedRow {
+        /// 1-based line number
+        line: usize,
+        /// Number of columns of the first row
+        expected: usize,
+        /// Number of cells of the row
+        found: usize,
+    },
+
+    /// A header names no channel of the target lists
+    #[error("No {target} channel named '{name}'")]
+    UnknownChannel {
+        /// `step` or `sweep`
+        target: &'static str,
+        /// The header cell
+        name: String,
+    },
+
+    /// There are more columns than channels
+    #[error("The file has {columns} columns but there are only {channels} {target} channels")]
+    TooManyColumns {
+        /// `step` or `sweep`
+        target: &'static str,
+        /// Number of columns
+        columns: usize,
+        /// Number of channels
+        channels: usize,
+    },
+
+    /// The number of rows is not a valid number of points
+    #[error("The file has {points} {target} points, expected {min} to {max}")]
+    PointCount {
+        /// `step` or `sweep`
+        target: &'static str,
+        /// Number of rows
+        points: usize,
+        /// Smallest valid number of points
+        min: usize,
+        /// Largest valid number of points
+        max: usize,
+    },
+}
+
+/// A value that was changed to fit the source range or the source limits.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, JsonSchema)]
+pub struct ClampedValue {
+    pub channel: String,
+    /// `list_<index>` for list values, otherwise the ID of the limit parameter.
+    pub parameter: String,
+    pub requested: f64,
+    pub value: f64,
+}
+
+/// Result of importing list values.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, JsonSchema)]
+pub struct ListImport {
+    /// Number of points of the lists.
+    pub points: usize,
+    /// Names of the channels whose lists were replaced.
+    pub channels: Vec<String>,
+    pub clamped: Vec<ClampedValue>,
+}
+
+/// Parsed contents of a list file.
+#[derive(Debug, PartialEq)]
+pub struct ListTable {
+    /// Header cells, if the first row is not numeric.
+    pub headers: Option<Vec<String>>,
+    pub columns: Vec<Vec<f64>>,
+}
+
+/// Parses a number with an optional SI prefix and unit, e.g. `10m`, `2.5 µA` or `-1e-3V`.
+pub fn parse_si_value(text: &str) -> Option<f64> {
+    let text = text.trim();
+    let text = text
+        .strip_suffix(BaseMetadata::UNIT_VOLTS)
+        .or_else(|| text.strip_suffix(BaseMetadata::UNIT_AMPERES))
+        .unwrap_or(text)
+        .trim_end();
+
+    let value = match text.parse::<f64>() {
+        Ok(value) => value,
+        Err(_) => {
+            let prefix = text.chars().last()?;
+            let scale = match prefix {
+                'f' => 1e-15,
+                'p' => 1e-12,
+                'n' => 1e-9,
+                'u' | 'µ' | 'μ' => 1e-6,
+                'm' => 1e-3,
+                'k' => 1e3,
+                'M' => 1e6,
+                'G' => 1e9,
+                _ => return None,
+            };
+            let number = text[..text.len() - prefix.len_utf8()].trim_end();
+            number.parse::<f64>().ok()? * scale
+        }
+    };
+    value.is_finite().then_some(value)
+}
+
+/// Parses CSV, TSV or semicolon separated list values.
+///
+/// # Errors
+///
+/// Returns an error if the file holds no values, a row has a different number of cells
+/// than the first row, or a cell is not a number.
+pub fn parse_table(text: &str) -> Result<ListTable, ListCsvError> {
+    let mut lines = text
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty());
+
+    let Some((first_line, first)) = lines.next() else {
+        return Err(ListCsvError::Empty);
+    };
+    let delimiter = ['\t', ';', ',']
+        .into_iter()
+        .find(|d| first.contains(*d))
+        .unwrap_or(',');
+    let split = |line: &'_ str| -> Vec<String> {
+        line.split(delimiter)
+            .map(|cell| cell.trim().trim_matches('"').to_string())
+            .collect()
+    };
+
+    let first_cells = split(first);
+    let expected = first_cells.len();
+    let mut columns = vec![Vec::new(); expected];
+    let headers = if first_cells
+        .iter()
+        .all(|cell| parse_si_value(cell).is_some())
+    {
+        push_row(&mut columns, first_line, &first_cells)?;
+        None
+    } else {
+        Some(first_cells)
+    };
+
+    for (line, text) in lines {
+        let cells = split(text);
+        if cells.len() != expected {
+            return Err(ListCsvError::RaggedRow {
+                line,
+                expected,
+                found: cells.len(),
+            });
+        }
+        push_row(&mut columns, line, &cells)?;
+    }
+
+    if columns[0].is_empty() {
+        return Err(ListCsvError::Empty);
+    }
+    Ok(ListTable { headers, columns })
+}
+
+fn push_row(columns: &mut [Vec<f64>], line: usize, cells: &[String]) -> Result<(), ListCsvError> {
+    for (i, (column, cell)) in columns.iter_mut().zip(cells).enumerate() {
+        let value = parse_si_value(cell).ok_or_else(|| ListCsvError::InvalidValue {
+            line,
+            column: i + 1,
+            value: cell.clone(),
+        })?;
+        column.push(value);
+    }
+    Ok(())
+}
+
+impl SweepConfig {
+    /// Replaces the step or sweep lists with the values of a CSV/TSV file.
+    ///
+    /// Enables the list mode of `target`, sets the number of points to the number of rows and
+    /// evaluates the configuration with `limits`. Values outside of the source range of their channel and
+    /// source limits that do not fit the new values are clamped and reported.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be parsed, its columns do not match the channels
+    /// of `target`, or it has too few or too many rows. The configuration is not changed
+    /// in that case.
+    pub fn import_lists(
+        &mut self,
+        target: ListTarget,
+        text: &str,
+        limits: &SweepLimits,
+    ) -> Result<ListImport, ListCsvError> {
+        let table = parse_table(text)?;
+
+        let channel_ids: Vec<(String, String)> = self
+            .start_stop_channels(target)
+            .map(|c| {
+                let attributes = &c.common_chan_attributes;
+                (attributes.chan_name.clone(), attributes.device_id.clone())
+            })
+            .collect();
+        let indices: Vec<usize> = match &table.headers {
+            Some(headers) => headers
+                .iter()
+                .map(|name| {
+                    channel_ids
+                        .iter()
+                        .position(|(chan_name, device_id)| chan_name == name || device_id == name)
+                        .ok_or_else(|| ListCsvError::UnknownChannel {
+                            target: target.as_str(),
+                            name: name.clone(),
+                        })
+                })
+                .collect::<Result<_, _>>()?,
+            None if table.columns.len() > channel_ids.len() => {
+                return Err(ListCsvError::TooManyColumns {
+                    target: target.as_str(),
+                    columns: table.columns.len(),
+                    channels: channel_ids.len(),
+                })
+            }
+            None => (0..table.columns.len()).collect(),
+        };
+
+        let points = table.columns[0].len();
+        let (min, max) = target.points_range(limits);
+        if points < min || points > max {
+            return Err(ListCsvError::PointCount {
+                target: target.as_str(),
+                points,
+                min,
+                max,
+            });
+        }
+
+        match target {
+            ListTarget::Step => {
+                self.step_global_parameters.step_points.value = points as i32;
+                self.step_global_parameters.list_step = true;
+            }
+            ListTarget::Sweep => {
+                self.sweep_global_parameters.sweep_points.value = points as i32;
+                self.sweep_global_parameters.list_sweep = true;
+            }
+        }
+        // brings the source ranges and units up to date
+        self.evaluate(limits);
+
+        let mut clamped = Vec::new();
+        let mut channels = Vec::new();
+        let mut source_limits_before = Vec::new();
+        for (index, values) in indices.iter().zip(&table.columns) {
+            let Some(channel) = self.start_stop_channels_mut(target).nth(*index) else {
+                continue;
+            };
+            let attributes = &mut channel.common_chan_attributes;
+            let unit = attributes.source_range.unit.clone();
+            channel.list = values
+                .iter()
+                .enumerate()
+                .map(|(i, &requested)| {
+                    let id = format!("list_{i}");
+                    let value = attributes.source_range.limit(requested);
+                    if value != requested {
+                        clamped.push(ClampedValue {
+                            channel: attributes.chan_name.clone(),
+                            parameter: id.clone(),
+                            requested,
+                            value,
+                        });
+                    }
+                    ParameterFloat::new(&id, value, Some(unit.clone()))
+                })
+                .collect();
+            channels.push(attributes.chan_name.clone());
+            source_limits_before.push((*index, source_limits(channel)));
+        }
+
+        // applies the source limit clamps for the new values
+        self.evaluate(limits);
+        for (index, before) in source_limits_before {
+            let Some(channel) = self.start_stop_channels(target).nth(index) else {
+                continue;
+            };
+            for (limit, requested) in source_limits(channel).into_iter().zip(before) {
+                if let (Some(limit), Some(requested)) = (limit, requested) {
+                    if limit.value != requested.value {
+                        clamped.push(ClampedValue {
+                            channel: channel.common_chan_attributes.chan_name.clone(),
+                            parameter: limit.id,
+                            requested: requested.value,
+                            value: limit.value,
+                        });
+                    }
+                }
+            }
+        }
+
+        Ok(ListImport {
+            points,
+            channels,
+            clamped,
+        })
+    }
+
+    /// Returns the step or sweep lists as CSV, one column per channel headed by the channel
+    /// name.
+    pub fn export_lists(&self, target: ListTarget) -> String {
+        let channels: Vec<_> = self.start_stop_channels(target).collect();
+        let mut csv = channels
+            .iter()
+            .map(|c| c.common_chan_attributes.chan_name.as_str())
+            .collect::<Vec<_>>()
+            .join(",");
+        csv.push('\n');
+
+        let points = channels.iter().map(|c| c.list.len()).max().unwrap_or(0);
+        for i in 0..points {
+            let row = channels
+                .iter()
+                .map(|c| {
+                    c.list
+                        .get(i)
+                        .map(|pf| pf.value.to_string())
+                        .unwrap_or_default()
+                })
+                .collect::<Vec<_>>()
+                .join(",");
+            let _ = writeln!(csv, "{row}");
+        }
+        csv
+    }
+
+    fn start_stop_channels(
+        &self,
+        target: ListTarget,
+    ) -> Box<dyn Iterator<Item = &StartStopChannel> + '_> {
+        match target {
+            ListTarget::Step => Box::new(self.step_channels.iter().map(|c| &c.start_stop_channel)),
+            ListTarget::Sweep => {
+                Box::new(self.sweep_channels.iter().map(|c| &c.start_stop_channel))
+            }
+        }
+    }
+
+    fn start_stop_channels_mut(
+        &mut self,
+        target: ListTarget,
+    ) -> Box<dyn Iterator<Item = &mut StartStopChannel> + '_> {
+        match target {
+            ListTarget::Step => Box::new(
+                self.step_channels
+                    .iter_mut()
+                    .map(|c| &mut c.start_stop_channel),
+            ),
+            ListTarget::Sweep => Box::new(
+                self.sweep_channels
+                    .iter_mut()
+                    .map(|c| &mut c.start_stop_channel),
+            ),
+        }
+    }
+}
+
+fn source_limits(channel: &StartStopChannel) -> [Option<ParameterFloat>; 2] {
+    let attributes = &channel.common_chan_attributes;
+    [
+        attributes.source_limiti.clone(),
+        attributes.source_limitv.clone(),
+    ]
+}
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod tests {
+    use super::*;
+    use crate::model::sweep_data::sweep_model::SweepModel;
+
+    const V2: &str = include_str!("../../../tests/fixtures/sweep_model/v2.json");
+
+    /// The configuration of the fixture, with the channels `step1` and `sweep1`.
+    fn sweep_config() -> SweepConfig {
+        serde_json::from_str::<SweepModel>(V2).unwrap().sweep_config
+    }
+
+    fn list(config: &SweepConfig, target: ListTarget, index: usize) -> Vec<f64> {
+        config
+            .start_stop_channels(target)
+            .nth(index)
+            .unwrap()
+            .list
+            .iter()
+            .map(|value| value.value)
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_si_value() {
+        assert_eq!(parse_si_value("1.5"), Some(1.5));
+        assert_eq!(parse_si_value("-1e-3"), Some(-1e-3));
+        assert_eq!(parse_si_value("10m"), Some(10.0 * 1e-3));
+        assert_eq!(parse_si_value(" 2.5 µA "), Some(2.5 * 1e-6));
+        assert_eq!(parse_si_value("3uV"), Some(3.0 * 1e-6));
+        assert_eq!(parse_si_value("1k"), Some(1e3));
+        assert_eq!(parse_si_value("2V"), Some(2.0));
+        assert_eq!(parse_si_value("m"), None);
+        assert_eq!(parse_si_value("1x"), None);
+        assert_eq!(parse_si_value("inf"), None);
+    }
+
+    #[test]
+    fn test_parse_table_with_header() {
+        let table = parse_table("step1\tstep2\n1\t2m\n\n3\t4m\n").unwrap();
+        assert_eq!(
+            table,
+            ListTable {
+                headers: Some(vec!["step1".to_string(), "step2".to_string()]),
+                columns: vec![vec![1.0, 3.0], vec![2e-3, 4e-3]],
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_table_without_header() {
+        let table = parse_table("1;2\r\n3;4\r\n").unwrap();
+        assert_eq!(table.headers, None);
+        assert_eq!(table.columns, vec![vec![1.0, 3.0], vec![2.0, 4.0]]);
+    }
+
+    #[test]
+    fn test_parse_table_errors() {
+        assert_eq!(parse_table(" \n"), Err(ListCsvError::Empty));
+        assert_eq!(parse_table("a,b\n"), Err(ListCsvError::Empty));
+        assert_eq!(
+            parse_table("1,2\n3\n"),
+            Err(ListCsvError::RaggedRow {
+                line: 2,
+                expected: 2,
+                found: 1
+            })
+        );
+        assert_eq!(
+            parse_table("a\n1\nx\n"),
+            Err(ListCsvError::InvalidValue {
+                line: 3,
+                column: 1,
+                value: "x".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn test_import_lists_resizes_and_clamps() {
+        let mut config = sweep_config();
+        let limits = SweepLimits::default();
+
+        let import = config
+            .import_lists(ListTarget::Step, "1\n100\n-2.5\n500m\n", &limits)
+            .unwrap();
+        assert_eq!(import.points, 4);
+        assert_eq!(import.channels, vec!["step1".to_string()]);
+        // the source range of the 60 V SMU ends at 60.6 V
+        assert_eq!(
+            import.clamped,
+            vec![ClampedValue {
+                channel: "step1".to_string(),
+                parameter: "list_1".to_string(),
+                requested: 100.0,
+                value: 60.6,
+            }]
+        );
+        assert_eq!(config.step_global_parameters.step_points.value, 4);
+        assert!(config.step_global_parameters.list_step);
+        assert_eq!(
+            list(&config, ListTarget::Step, 0),
+            vec![1.0, 60.6, -2.5, 0.5]
+        );
+        // the other lists are left alone
+        assert!(!config.sweep_global_parameters.list_sweep);
+        assert_eq!(config.sweep_global_parameters.sweep_points.value, 10);
+
+        let import = config
+            .import_lists(ListTarget::Sweep, "sweep1\n1m\n2m\n3m\n", &limits)
+            .unwrap();
+        assert_eq!(import.points, 3);
+        assert!(import.clamped.is_empty());
+        assert_eq!(config.sweep_global_parameters.sweep_points.value, 3);
+        assert!(config.sweep_global_parameters.list_sweep);
+        assert_eq!(list(&config, ListTarget::Sweep, 0), vec![1e-3, 2e-3, 3e-3]);
+    }
+
+    #[test]
+    fn test_import_lists_maps_headers_to_channels() {
+        let mut config = sweep_config();
+        let mut step2 = config.step_channels[0].clone();
+        let attributes = &mut step2.start_stop_channel.common_chan_attributes;
+        attributes.chan_name = "step2".to_string();
+        attributes.device_id = "localnode.slot[1].smu[2]".to_string();
+        config.step_channels.push(step2);
+
+        // by device ID and by channel name, in any order
+        let import = config
+            .import_lists(
+                ListTarget::Step,
+                "localnode.slot[1].smu[2],step1\n1,2\n3,4\n",
+                &SweepLimits::default(),
+            )
+            .unwrap();
+        assert_eq!(
+            import.channels,
+            vec!["step2".to_string(), "step1".to_string()]
+        );
+        assert_eq!(list(&config, ListTarget::Step, 0), vec![2.0, 4.0]);
+        assert_eq!(list(&config, ListTarget::Step, 1), vec![1.0, 3.0]);
+
+        assert_eq!(
+            config.import_lists(ListTarget::Step, "step3\n1\n", &SweepLimits::default()),
+            Err(ListCsvError::UnknownChannel {
+                target: "step",
+                name: "step3".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn test_import_lists_errors() {
+        let mut config = sweep_config();
+        let limits = SweepLimits::default();
+        let before = serde_json::to_value(&config).unwrap();
+
+        assert_eq!(
+            config.import_lists(ListTarget::Step, "1,2\n3,4\n", &limits),
+            Err(ListCsvError::TooManyColumns {
+                target: "step",
+                columns: 2,
+                channels: 1
+            })
+        );
+        assert_eq!(
+            config.import_lists(ListTarget::Sweep, "1\n", &limits),
+            Err(ListCsvError::PointCount {
+                target: "sweep",
+                points: 1,
+                min: 2,
+                max: 60000
+            })
+        );
+        let rows = "1\n".repeat(60001);
+        assert_eq!(
+            config.import_lists(ListTarget::Step, &rows, &limits),
+            Err(ListCsvError::PointCount {
+                target: "step",
+                points: 60001,
+                min: 1,
+                max: 60000
+            })
+        );
+        assert_eq!(serde_json::to_value(&config).unwrap(), before);
+    }
+
+    #[test]
+    fn test_export_import_round_trip() {
+        let mut config = sweep_config();
+        let limits = SweepLimits::default();
+        config
+            .import_lists(ListTarget::Sweep, "0\n-1.5\n2e-3\n60\n", &limits)
+            .unwrap();
+
+        let csv = config.export_lists(ListTarget::Sweep);
+        assert_eq!(csv, "sweep1\n0\n-1.5\n0.002\n60\n");
+        let mut imported = sweep_config();
+        let import = imported
+            .import_lists(ListTarget::Sweep, &csv, &limits)
+            .unwrap();
+        assert!(import.clamped.is_empty());
+        assert_eq!(
+            list(&imported, ListTarget::Sweep, 0),
+            list(&config, ListTarget::Sweep, 0)
+        );
+    }
+}
@@ -0,0 +1,216 @@
+//! Upgrades saved sweep models to the current layout.
+//!
+//! A serialized [`SweepModel`](super::sweep_model::SweepModel) carries its layout version in
+//! `schema_version`. Models saved before the field existed are version 1. On load the JSON is
+//! upgraded one version at a time by the functions in [`MIGRATIONS`] before it is deserialized,
+//! so the structs only need to match the current layout.
+//!
+//! To change the layout, bump [`SCHEMA_VERSION`], append a migration from the previous
+//! version and add a fixture of the previous layout under `tests/fixtures/sweep_model`.
+
+use serde_json::{Map, Value};
+use thiserror::Error;
+
+/// Layout version of sweep models written by this generator.
+pub const SCHEMA_VERSION: u32 = 2;
+
+/// Upgrades a sweep model from version `index + 1` to version `index + 2`.
+type Migration = fn(&mut Map<String, Value>) -> Result<(), MigrationError>;
+
+const MIGRATIONS: [Migration; SCHEMA_VERSION as usize - 1] = [v1_to_v2];
+
+/// Errors when upgrading a saved sweep model.
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum MigrationError {
+    /// The model was saved by a newer generator
+    #[error(
+        "Sweep model schema version {version} is not supported, expected at most {SCHEMA_VERSION}"
+    )]
+    UnsupportedVersion {
+        /// The schema version of the model
+        version: u64,
+    },
+
+    /// The model does not have the shape of its schema version
+    #[error("Invalid sweep model: {message}")]
+    InvalidShape {
+        /// What is wrong
+        message: String,
+    },
+}
+
+/// Returns the schema version of a serialized sweep model.
+///
+/// # Errors
+///
+/// Returns an error if `schema_version` is not an unsigned integer.
+pub fn schema_version(sweep_model: &Value) -> Result<u64, MigrationError> {
+    match sweep_model.get("schema_version") {
+        None => Ok(1),
+        Some(version) => version
+            .as_u64()
+            .ok_or_else(|| MigrationError::InvalidShape {
+                message: format!("schema_version must be an unsigned integer, found {version}"),
+            }),
+    }
+}
+
+/// Upgrades a serialized sweep model in place to [`SCHEMA_VERSION`] and returns the version
+/// it was saved with.
+///
+/// # Errors
+///
+/// Returns an error if the model was saved by a newer generator or does not have the shape
+/// of its schema version.
+pub fn migrate(sweep_model: &mut Value) -> Result<u64, MigrationError> {
+    let version = schema_version(sweep_model)?;
+    if version == 0 || version > u64::from(SCHEMA_VERSION) {
+        return Err(MigrationError::UnsupportedVersion { version });
+    }
+    let Value::Object(map) = sweep_model else {
+        return Err(invalid("expected a JSON object"));
+    };
+
+    for (from, migration) in MIGRATIONS.iter().enumerate().skip(version as usize - 1) {
+        migration(map)?;
+        map.insert("schema_version".to_string(), Value::from(from + 2));
+    }
+    if version < u64::from(SCHEMA_VERSION) {
+        tracing::info!("Upgraded sweep model from schema version {version} to {SCHEMA_VERSION}");
+    }
+    Ok(version)
+}
+
+/// Version 2 adds `schema_version`. Version 1 models saved before `asymptote` (step and sweep
+/// channels) and `overhead_time` (global parameters) were added lack these fields.
+fn v1_to_v2(sweep_model: &mut Map<String, Value>) -> Result<(), MigrationError> {
+    let sweep_config = object(sweep_model, "sweep_config")?;
+
+    if let Some(global_parameters) = sweep_config.get_mut("global_parameters") {
+        let Value::Object(global_parameters) = global_parameters else {
+            return Err(invalid("global_parameters must be an object"));
+        };
+        global_parameters
+            .entry("overhead_time")
+            .or_insert(Value::from(78e-6));
+    }
+
+    for key in ["step_channels", "sweep_channels"] {
+        let Some(channels) = sweep_config.get_mut(key) else {
+            continue;
+        };
+        let Value::Array(channels) = channels else {
+            return Err(invalid(&format!("{key} must be an array")));
+        };
+        for channel in channels {
+            let Value::Object(channel) = channel else {
+                return Err(invalid(&format!("{key} must contain objects")));
+            };
+            object(channel, "start_stop_channel")?
+                .entry("asymptote")
+                .or_insert(Value::from(0.0));
+        }
+    }
+    Ok(())
+}
+
+fn object<'a>(
+    map: &'a mut Map<String, Value>,
+    key: &str,
+) -> Result<&'a mut Map<String, Value>, MigrationError> {
+    match map.get_mut(key) {
+        Some(Value::Object(value)) => Ok(value),
+        _ => Err(invalid(&format!("{key} must be an object"))),
+    }
+}
+
+fn invalid(message: &str) -> MigrationError {
+    MigrationError::InvalidShape {
+        message: message.to_string(),
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod tests {
+    use super::*;
+    use crate::model::sweep_data::sweep_model::SweepModel;
+
+    /// Version 1 layout before `asymptote` and `overhead_time` were added.
+    const V1: &str = include_str!("../../../tests/fixtures/sweep_model/v1.json");
+    /// Version 1 layout as saved by 0.1.3, including `asymptote` and `overhead_time`.
+    const V1_ASYMPTOTE: &str =
+        include_str!("../../../tests/fixtures/sweep_model/v1_asymptote.json");
+    const V2: &str = include_str!("../../../tests/fixtures/sweep_model/v2.json");
+
+    fn upgrade(fixture: &str) -> (u64, Value) {
+        let mut value: Value = serde_json::from_str(fixture).unwrap();
+        let version = migrate(&mut value).unwrap();
+        (version, value)
+    }
+
+    #[test]
+    fn test_v1_to_v2() {
+        let (version, value) = upgrade(V1);
+        assert_eq!(version, 1);
+        assert_eq!(value["schema_version"], 2);
+        let sweep_config = &value["sweep_config"];
+        assert_eq!(sweep_config["global_parameters"]["overhead_time"], 78e-6);
+        assert_eq!(
+            sweep_config["step_channels"][0]["start_stop_channel"]["asymptote"],
+            0.0
+        );
+        assert_eq!(
+            sweep_config["sweep_channels"][0]["start_stop_channel"]["asymptote"],
+            0.0
+        );
+    }
+
+    #[test]
+    fn test_v1_keeps_existing_values() {
+        let (version, value) = upgrade(V1_ASYMPTOTE);
+        assert_eq!(version, 1);
+        let original: Value = serde_json::from_str(V1_ASYMPTOTE).unwrap();
+        let mut expected = original.clone();
+        expected["schema_version"] = Value::from(2);
+        assert_eq!(value, expected);
+    }
+
+    #[test]
+    fn test_current_version_is_unchanged() {
+        let (version, value) = upgrade(V2);
+        assert_eq!(version, u64::from(SCHEMA_VERSION));
+        assert_eq!(value, serde_json::from_str::<Value>(V2).unwrap());
+    }
+
+    #[test]
+    fn test_fixtures_deserialize() {
+        for fixture in [V1, V1_ASYMPTOTE, V2] {
+            let sweep_model: SweepModel = serde_json::from_str(fixture).unwrap();
+            assert_eq!(sweep_model.schema_version, SCHEMA_VERSION);
+            assert_eq!(sweep_model.sweep_config.device_list.len(), 4);
+            assert_eq!(sweep_model.sweep_config.step_channels.len(), 1);
+        }
+    }
+
+    #[test]
+    fn test_newer_version_is_rejected() {
+        let mut value: Value = serde_json::from_str(V2).unwrap();
+        value["schema_version"] = Value::from(SCHEMA_VERSION + 1);
+        assert_eq!(
+            migrate(&mut value),
+            Err(MigrationError::UnsupportedVersion {
+                version: u64::from(SCHEMA_VERSION + 1)
+            })
+        );
+    }
+
+    #[test]
+    fn test_invalid_shape_is_rejected() {
+        let mut value = serde_json::json!({ "sweep_config": [] });
+        assert!(matches!(
+            migrate(&mut value),
+            Err(MigrationError::InvalidShape { .. })
+        ));
+    }
+}
@@ -1,9 +1,12 @@
 pub mod global_parameters;
+pub mod list_csv;
+pub mod migration;
 pub mod number_limit;
 pub mod parameters;
 pub mod status_msg;
 pub mod step_sweep_config;
 pub mod sweep_config;
+pub mod sweep_limits;
 pub mod sweep_model;
 pub mod sweep_timing_config;
 //pub mod timing_config;
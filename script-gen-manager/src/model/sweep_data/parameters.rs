@@ -1,14 +1,15 @@
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 /// The `ParameterInt` struct represents an integer parameter.
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, JsonSchema)]
 pub struct ParameterInt {
     pub id: String,
     pub value: i32,
 }
 
 /// The `ParameterFloat` struct represents a floating-point parameter.
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, JsonSchema)]
 pub struct ParameterFloat {
     pub id: String,
     pub value: f64,
@@ -16,7 +17,7 @@ pub struct ParameterFloat {
 }
 
 /// The `ParameterString` struct represents a selection (drop-down) parameter.
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, JsonSchema)]
 pub struct ParameterString {
     pub id: String,
     pub value: String,
@@ -1,14 +1,15 @@
 use chrono::Local;
+use schemars::JsonSchema;
 use serde::Serialize;
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, JsonSchema)]
 pub struct StatusMsg {
     pub status_type: StatusType,
     pub message: String,
     pub time_stamp: String,
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, JsonSchema)]
 pub enum StatusType {
     Info,
     Warning,
@@ -1,3 +1,4 @@
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::str;
 
@@ -5,7 +6,7 @@ use crate::instr_metadata::base_metadata::BaseMetadata;
 
 use super::parameters::{ParameterFloat, ParameterInt};
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, JsonSchema)]
 pub struct StepGlobalParameters {
     pub step_points: ParameterInt,
     pub step_to_sweep_delay: ParameterFloat,
@@ -26,7 +27,7 @@ impl StepGlobalParameters {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, JsonSchema)]
 pub struct SweepGlobalParameters {
     pub sweep_points: ParameterInt,
     pub list_sweep: bool,
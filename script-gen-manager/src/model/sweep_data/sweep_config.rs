@@ -1,5 +1,6 @@
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use crate::{
     device::Device,
@@ -16,9 +17,10 @@ use crate::{
 use super::{
     global_parameters::GlobalParameters,
     step_sweep_config::{StepGlobalParameters, SweepGlobalParameters},
+    sweep_limits::SweepLimits,
 };
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, JsonSchema)]
 pub struct SweepConfig {
     pub global_parameters: GlobalParameters,
     pub bias_channels: Vec<BiasChannel>,
@@ -89,7 +91,7 @@ impl SweepConfig {
                 let found_local = if system.local_node == "MP5103" {
                     let found = process_slots("localnode", &system.local_node, &system.slots);
                     if !found {
-                        println!("All modules are empty in localnode. Checking nodes...");
+                        tracing::info!("All modules are empty in localnode. Checking nodes...");
                     }
                     found
                 } else {
@@ -104,19 +106,19 @@ impl SweepConfig {
                             if found {
                                 break;
                             } else {
-                                println!(
+                                tracing::info!(
                                     "All modules are empty in node {}. Skipping.",
                                     node.node_id
                                 );
                             }
                         }
                     } else {
-                        println!("No nodes found in the system info.");
+                        tracing::warn!("No nodes found in the system info.");
                     }
                 }
             }
         } else if let Err(e) = res {
-            println!("Error: {:#?}", e);
+            tracing::error!("Failed to parse system info: {:#?}", e);
         }
 
         is_device_found
@@ -165,14 +167,16 @@ impl SweepConfig {
                 found_any_valid_slots |= processed;
             }
         } else if let Err(e) = res {
-            println!("Error: {:#?}", e);
+            tracing::error!("Failed to parse system info: {:#?}", e);
         }
 
         if !found_any_valid_slots {
             for device in &mut self.device_list {
                 device.is_valid = false;
             }
-            println!("No valid slots found or system_info is empty. All devices invalidated.");
+            tracing::warn!(
+                "No valid slots found or system_info is empty. All devices invalidated."
+            );
         }
 
         self.remove_unused_invalid_channels();
@@ -362,17 +366,23 @@ impl SweepConfig {
         self.sweep_channels.push(sweep_chan);
     }
 
-    pub fn evaluate(&mut self) {
+    /// Clamps the parameters of the configuration to `limits`, resolved for its current
+    /// parameter values, and to the ranges of the devices of its channels.
+    pub fn evaluate(&mut self, limits: &SweepLimits) {
+        let limits = &limits.for_config(self);
         self.update_channel_devices();
-        self.global_parameters.evaluate();
-        self.step_global_parameters.step_points.limit(1, 60000);
-        self.sweep_global_parameters.sweep_points.limit(2, 60000);
-        self.global_parameters
-            .sweep_timing_config
-            .measure_count
-            .limit(1, 60000);
+        self.global_parameters.evaluate(limits);
+        limits
+            .step_points
+            .limit_int(&mut self.step_global_parameters.step_points);
+        limits
+            .sweep_points
+            .limit_int(&mut self.sweep_global_parameters.sweep_points);
 
         // Validating Step to Sweep Delay
+        limits
+            .step_to_sweep_delay
+            .limit_float(&mut self.step_global_parameters.step_to_sweep_delay);
         if !self.step_channels.is_empty() {
             let device_metadata = self.step_channels[0]
                 .start_stop_channel
@@ -451,7 +461,45 @@ impl SweepConfig {
         }
     }
 
+    /// Marks the devices used by a channel as in use and all other devices as free, then
+    /// refreshes the device copies held by the channels.
+    pub fn sync_device_usage(&mut self) {
+        let used: HashSet<&str> = self
+            .bias_channels
+            .iter()
+            .map(|c| c.common_chan_attributes.device_id.as_str())
+            .chain(self.step_channels.iter().map(|c| {
+                c.start_stop_channel
+                    .common_chan_attributes
+                    .device_id
+                    .as_str()
+            }))
+            .chain(self.sweep_channels.iter().map(|c| {
+                c.start_stop_channel
+                    .common_chan_attributes
+                    .device_id
+                    .as_str()
+            }))
+            .collect();
+        let in_use: Vec<bool> = self
+            .device_list
+            .iter()
+            .map(|device| used.contains(device._id.as_str()))
+            .collect();
+        for (device, in_use) in self.device_list.iter_mut().zip(in_use) {
+            device.in_use = in_use;
+        }
+        self.update_channel_devices();
+    }
+
+    /// Removes the channel of the device `chan_id`, if any, and releases the device.
     pub fn remove_channel(&mut self, chan_id: String) {
+        self.bias_channels
+            .retain(|c| c.common_chan_attributes.device_id != chan_id);
+        self.step_channels
+            .retain(|c| c.start_stop_channel.common_chan_attributes.device_id != chan_id);
+        self.sweep_channels
+            .retain(|c| c.start_stop_channel.common_chan_attributes.device_id != chan_id);
         self.device_list.iter_mut().for_each(|device| {
             if device._id == chan_id {
                 device.in_use = false;
@@ -613,4 +661,17 @@ impl SweepConfig {
     pub fn reset(&mut self) {
         *self = SweepConfig::new();
     }
+
+    /// Sets the global parameters to the default values of `limits`.
+    ///
+    /// Call it for a new configuration, before channels are added with the default number of
+    /// points.
+    pub fn apply_defaults(&mut self, limits: &SweepLimits) {
+        self.step_global_parameters.step_points.value = limits.step_points.default as i32;
+        self.step_global_parameters.step_to_sweep_delay.value = limits.step_to_sweep_delay.default;
+        self.sweep_global_parameters.sweep_points.value = limits.sweep_points.default as i32;
+        let sweep_timing_config = &mut self.global_parameters.sweep_timing_config;
+        sweep_timing_config.measure_count.value = limits.measure_count.default as i32;
+        sweep_timing_config.smu_timing.apply_defaults(limits);
+    }
 }
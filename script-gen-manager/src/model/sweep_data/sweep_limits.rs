@@ -0,0 +1,299 @@
+use std::collections::HashMap;
+
+use xml_handler::variable::{self, ResolvedVariable, Variable};
+
+use super::{
+    number_limit::{CommonTimingLimit, NumberLimit, SmuTimingLimit},
+    parameters::{ParameterFloat, ParameterInt},
+    sweep_config::SweepConfig,
+};
+
+/// The limit and default value of a sweep model parameter.
+#[derive(Debug, Clone)]
+pub struct ParameterLimit {
+    /// The id of the template variable that defines the limit, e.g. "stepCount".
+    pub id: &'static str,
+    /// The range the value is clamped to.
+    pub limit: NumberLimit,
+    /// The value of a new sweep model.
+    pub default: f64,
+}
+
+impl ParameterLimit {
+    pub fn new(id: &'static str, limit: NumberLimit, default: f64) -> Self {
+        ParameterLimit { id, limit, default }
+    }
+
+    /// Clamps the value of `parameter` to the limit.
+    pub fn limit_int(&self, parameter: &mut ParameterInt) {
+        parameter.value = self.limit.limit_int(parameter.value);
+    }
+
+    /// Clamps the value of `parameter` to the limit.
+    pub fn limit_float(&self, parameter: &mut ParameterFloat) {
+        parameter.value = self.limit.limit(parameter.value);
+    }
+
+    /// Returns the limit as an inclusive range of counts.
+    pub fn count_range(&self) -> (usize, usize) {
+        let min = self.limit.get_min();
+        let max = self.limit.get_max();
+        (
+            if min.is_nan() {
+                0
+            } else {
+                min.max(0.0) as usize
+            },
+            if max.is_nan() {
+                usize::MAX
+            } else {
+                max.max(0.0) as usize
+            },
+        )
+    }
+}
+
+/// The limits and default values of the global parameters of the sweep model.
+///
+/// The catalog defines them with the template variables of `DefaultLimits.xml`, see
+/// [`SweepLimits::from_variables`]. The default limits apply if the templates are not
+/// available.
+#[derive(Debug, Clone)]
+pub struct SweepLimits {
+    /// The template variables the limits are resolved from.
+    variables: Vec<Variable>,
+    /// The resolved template variables, in dependency order.
+    pub values: Vec<ResolvedVariable>,
+    pub step_points: ParameterLimit,
+    pub step_to_sweep_delay: ParameterLimit,
+    pub sweep_points: ParameterLimit,
+    pub measure_count: ParameterLimit,
+    pub nplc: ParameterLimit,
+    pub aperture: ParameterLimit,
+    pub source_delay: ParameterLimit,
+    pub measure_delay: ParameterLimit,
+}
+
+impl Default for SweepLimits {
+    fn default() -> Self {
+        let common = CommonTimingLimit::new();
+        let smu = SmuTimingLimit::new();
+        SweepLimits {
+            variables: Vec::new(),
+            values: Vec::new(),
+            step_points: ParameterLimit::new(
+                "stepCount",
+                NumberLimit::new(1.0, 60000.0, true, None),
+                10.0,
+            ),
+            step_to_sweep_delay: ParameterLimit::new(
+                "stepToSweepDelay",
+                NumberLimit::default(),
+                0.0,
+            ),
+            sweep_points: ParameterLimit::new(
+                "sweepCount",
+                NumberLimit::new(2.0, 60000.0, true, None),
+                10.0,
+            ),
+            measure_count: ParameterLimit::new("measureCount", common.measure_count_limits, 1.0),
+            nplc: ParameterLimit::new("nplc", smu.nplc_limits, 1.0),
+            aperture: ParameterLimit::new("aperture", smu.aperture_limits, 1e-6),
+            source_delay: ParameterLimit::new("sourceDelay", smu.source_delay_limits, 0.0),
+            measure_delay: ParameterLimit::new("measureDelay", smu.measure_delay_limits, 0.0),
+        }
+    }
+}
+
+impl SweepLimits {
+    /// Creates the limits defined by the `<constraints>` and `<default>` of `variables`,
+    /// resolved with their default values.
+    ///
+    /// Parameters without a variable, or whose variable has no constraints or no numeric
+    /// default, keep the corresponding default limit or value. If the variables cannot be
+    /// resolved, all parameters keep the default limits.
+    pub fn from_variables(variables: &[Variable]) -> Self {
+        let mut limits = SweepLimits {
+            variables: variables.to_vec(),
+            ..SweepLimits::default()
+        };
+        limits.resolve(&HashMap::new(), true);
+        limits
+    }
+
+    /// Returns the limits with the variables resolved for the parameter values of
+    /// `sweep_config`, so that constraints referring to other parameters follow their values.
+    pub fn for_config(&self, sweep_config: &SweepConfig) -> Self {
+        let mut limits = self.clone();
+        if !self.variables.is_empty() {
+            let values = limits.parameter_values(sweep_config);
+            limits.resolve(&values, false);
+        }
+        limits
+    }
+
+    fn resolve(&mut self, values: &HashMap<&'static str, String>, defaults: bool) {
+        let lookup = |id: &str| values.get(id).cloned().into_iter().collect();
+        self.values = match variable::resolve(&self.variables, lookup) {
+            Ok(values) => values,
+            Err(e) => {
+                tracing::error!("Failed to resolve template variables: {e}");
+                return;
+            }
+        };
+        let values = std::mem::take(&mut self.values);
+        for parameter in self.parameters_mut() {
+            let Some(variable) = values.iter().find(|v| v.id == parameter.id) else {
+                continue;
+            };
+            if let Some(constraint) = &variable.constraint {
+                parameter.limit = NumberLimit::new(constraint.min, constraint.max, true, None);
+            }
+            if defaults && !variable.value.is_empty() {
+                match variable.value.trim().parse() {
+                    Ok(default) => parameter.default = parameter.limit.limit(default),
+                    Err(_) => tracing::warn!(
+                        "Ignoring default '{}' of template variable {}, it is not a number",
+                        variable.value,
+                        variable.id
+                    ),
+                }
+            }
+        }
+        self.values = values;
+    }
+
+    /// Returns the values of the parameters of `sweep_config`, keyed by variable id.
+    fn parameter_values(&self, sweep_config: &SweepConfig) -> HashMap<&'static str, String> {
+        let timing = &sweep_config.global_parameters.sweep_timing_config;
+        let smu_timing = &timing.smu_timing;
+        HashMap::from([
+            (
+                self.step_points.id,
+                sweep_config
+                    .step_global_parameters
+                    .step_points
+                    .value
+                    .to_string(),
+            ),
+            (
+                self.step_to_sweep_delay.id,
+                sweep_config
+                    .step_global_parameters
+                    .step_to_sweep_delay
+                    .value
+                    .to_string(),
+            ),
+            (
+                self.sweep_points.id,
+                sweep_config
+                    .sweep_global_parameters
+                    .sweep_points
+                    .value
+                    .to_string(),
+            ),
+            (
+                self.measure_count.id,
+                timing.measure_count.value.to_string(),
+            ),
+            (self.nplc.id, smu_timing.nplc.value.to_string()),
+            (self.aperture.id, smu_timing.aperture.value.to_string()),
+            (
+                self.source_delay.id,
+                smu_timing.source_delay.value.to_string(),
+            ),
+            (
+                self.measure_delay.id,
+                smu_timing.measure_delay.value.to_string(),
+            ),
+        ])
+    }
+
+    fn parameters_mut(&mut self) -> [&mut ParameterLimit; 8] {
+        [
+            &mut self.step_points,
+            &mut self.step_to_sweep_delay,
+            &mut self.sweep_points,
+            &mut self.measure_count,
+            &mut self.nplc,
+            &mut self.aperture,
+            &mut self.source_delay,
+            &mut self.measure_delay,
+        ]
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod tests {
+    use super::*;
+    use xml_handler::{generic_parser, templates::TemplateLoader, variable::Constraint};
+
+    fn variable(id: &str, constraint: Constraint) -> Variable {
+        Variable {
+            id: id.to_string(),
+            default: "10".to_string(),
+            value_attr: String::new(),
+            depends_array: Vec::new(),
+            ref_array: Vec::new(),
+            constraint: Some(constraint),
+        }
+    }
+
+    #[test]
+    fn test_default_limits_template() {
+        let groups = generic_parser::parse_xml(&mut TemplateLoader::default()).unwrap();
+        let sweep = groups.iter().find(|group| group.type_ == "Sweep").unwrap();
+        let limits = SweepLimits::from_variables(&sweep.variable_list);
+
+        assert_eq!(limits.step_points.count_range(), (1, 60000));
+        assert_eq!(limits.sweep_points.count_range(), (2, 60000));
+        assert_eq!(limits.nplc.default, 1.0);
+        assert_eq!(limits.nplc.limit.limit(30.0), 30.0);
+        assert_eq!(limits.nplc.limit.limit(40.0), 30.0);
+        assert_eq!(limits.nplc.limit.limit(1e-5), 5e-5);
+        assert_eq!(limits.step_to_sweep_delay.limit.limit(-1.0), 0.0);
+        // not defined by the templates
+        assert_eq!(limits.aperture.limit.get_max(), 500e-3);
+
+        let mut step_points = ParameterInt::new("step_points", 70000);
+        limits.step_points.limit_int(&mut step_points);
+        assert_eq!(step_points.value, 60000);
+
+        let pulse_width = limits.values.iter().find(|v| v.id == "pulseWidth").unwrap();
+        assert_eq!(pulse_width.constraint.as_ref().unwrap().max, 50e-3);
+    }
+
+    #[test]
+    fn test_limits_for_config() {
+        let variables = [
+            variable("sweepCount", Constraint::new(1.0, 1000.0)),
+            variable(
+                "measureCount",
+                Constraint {
+                    max_ref: Some("sweepCount".to_string()),
+                    ..Constraint::new(1.0, f64::NAN)
+                },
+            ),
+        ];
+        let limits = SweepLimits::from_variables(&variables);
+        assert_eq!(limits.measure_count.limit.get_max(), 10.0);
+
+        let mut sweep_config = SweepConfig::new();
+        sweep_config.sweep_global_parameters.sweep_points.value = 20;
+        sweep_config
+            .global_parameters
+            .sweep_timing_config
+            .measure_count
+            .value = 50;
+        sweep_config.evaluate(&limits);
+        assert_eq!(
+            sweep_config
+                .global_parameters
+                .sweep_timing_config
+                .measure_count
+                .value,
+            20
+        );
+    }
+}
@@ -1,11 +1,18 @@
-use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
+use serde::{de, Deserialize, Deserializer, Serialize};
+use serde_json::Value;
 
-use super::sweep_config::SweepConfig;
+use super::{migration, sweep_config::SweepConfig};
 
 /// The `SweepModel` struct represents the model for sweep configurations.
 /// It contains a `SweepConfig` which holds the configuration details for the sweep.
-#[derive(Serialize, Deserialize, Debug, Clone)]
+///
+/// Models saved with an older `schema_version` are upgraded when deserialized, see
+/// [`migration`].
+#[derive(Serialize, Debug, Clone, PartialEq, JsonSchema)]
 pub struct SweepModel {
+    /// Layout version, [`migration::SCHEMA_VERSION`] once deserialized.
+    pub schema_version: u32,
     //#[serde(deserialize_with = "deserialize_sweep_model")]
     pub sweep_config: SweepConfig,
 }
@@ -13,11 +20,34 @@ pub struct SweepModel {
 impl SweepModel {
     pub fn new() -> Self {
         SweepModel {
+            schema_version: migration::SCHEMA_VERSION,
             sweep_config: SweepConfig::new(),
         }
     }
 }
 
+impl<'de> Deserialize<'de> for SweepModel {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        struct SweepModelData {
+            schema_version: u32,
+            sweep_config: SweepConfig,
+        }
+
+        let mut value = Value::deserialize(deserializer)?;
+        migration::migrate(&mut value).map_err(de::Error::custom)?;
+        let data = SweepModelData::deserialize(value).map_err(de::Error::custom)?;
+
+        Ok(SweepModel {
+            schema_version: data.schema_version,
+            sweep_config: data.sweep_config,
+        })
+    }
+}
+
 // fn deserialize_sweep_model<'de, D>(deserializer: D) -> Result<SweepConfig, D::Error>
 // where
 //     D: Deserializer<'de>,
@@ -1,3 +1,4 @@
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 use crate::{
@@ -5,11 +6,12 @@ use crate::{
 };
 
 use super::{
-    number_limit::{NumberLimit, SmuTimingLimit},
+    number_limit::NumberLimit,
     parameters::{ParameterFloat, ParameterString},
+    sweep_limits::SweepLimits,
 };
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, JsonSchema)]
 pub struct SweepTimingConfig {
     pub measure_count: ParameterInt,
     pub smu_timing: SmuTiming,
@@ -25,13 +27,14 @@ impl SweepTimingConfig {
         }
     }
 
-    pub fn evaluate(&mut self) {
-        self.smu_timing.evaluate();
+    pub fn evaluate(&mut self, limits: &SweepLimits) {
+        limits.measure_count.limit_int(&mut self.measure_count);
+        self.smu_timing.evaluate(limits);
         self.psu_timing.evaluate();
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, JsonSchema)]
 pub struct SmuTiming {
     pub nplc: ParameterFloat,
     pub aperture: ParameterFloat,
@@ -86,13 +89,20 @@ impl SmuTiming {
         self.nplc_type.value = String::from("NPLC");
     }
 
-    pub fn evaluate(&mut self) {
-        let smu_limits = SmuTimingLimit::new();
+    pub fn evaluate(&mut self, limits: &SweepLimits) {
         //TODO: verify if additional validation is needed
-        self.evaluate_nplc(&smu_limits.nplc_limits);
-        self.evaluate_aperture(&smu_limits.aperture_limits);
-        self.evaluate_source_delay(&smu_limits.source_delay_limits);
-        self.evaluate_measure_delay(&smu_limits.measure_delay_limits);
+        self.evaluate_nplc(&limits.nplc.limit);
+        self.evaluate_aperture(&limits.aperture.limit);
+        self.evaluate_source_delay(&limits.source_delay.limit);
+        self.evaluate_measure_delay(&limits.measure_delay.limit);
+    }
+
+    /// Sets the timing parameters to the default values of `limits`.
+    pub fn apply_defaults(&mut self, limits: &SweepLimits) {
+        self.nplc.value = limits.nplc.default;
+        self.aperture.value = limits.aperture.default;
+        self.source_delay.value = limits.source_delay.default;
+        self.measure_delay.value = limits.measure_delay.default;
     }
 
     // fn compute_effective_delay(&self) -> f64 {
@@ -136,7 +146,7 @@ impl SmuTiming {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, JsonSchema)]
 pub struct PsuTiming {
     rate: ParameterString,
     rate_normal: f64,
@@ -0,0 +1,349 @@
+use std::{collections::HashMap, fs, path::Path};
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    device::Device,
+    model::{sweep_data::sweep_model::SweepModel, system_info::Root},
+    script_component::embedded_config::{self, EmbeddedConfigError},
+};
+
+/// Version of the project file format written by this generator.
+pub const PROJECT_FORMAT_VERSION: u32 = 1;
+
+/// Errors when saving or loading a project file.
+#[derive(Error, Debug)]
+pub enum ProjectError {
+    /// The file could not be read or written
+    #[error("IO error occurred: {source}")]
+    IOError {
+        /// The original `[std::io::Error]`
+        #[from]
+        source: std::io::Error,
+    },
+
+    /// The file is a script without a valid embedded configuration
+    #[error("Invalid script: {source}")]
+    ScriptError {
+        /// The original error
+        #[from]
+        source: EmbeddedConfigError,
+    },
+
+    /// The file is not a valid project
+    #[error("Invalid project file: {source}")]
+    ParseError {
+        /// The original error
+        #[from]
+        source: serde_json::Error,
+    },
+
+    /// The file was written by a newer generator
+    #[error("Project format version {version} is not supported, expected at most {PROJECT_FORMAT_VERSION}")]
+    UnsupportedVersion {
+        /// The format version of the file
+        version: u32,
+    },
+}
+
+/// A saved script generation project.
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct Project {
+    pub format_version: u32,
+    /// Version of the generator that wrote the file.
+    pub generator_version: String,
+    pub sweep_model: SweepModel,
+    /// System configuration the project was created with.
+    pub system_info: Option<Root>,
+    /// Line frequency of the instrument in Hz.
+    pub line_frequency: f64,
+    /// Path the script is written to.
+    pub output_path: Option<String>,
+}
+
+/// A device of a project that does not match the connected hardware.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case", tag = "kind")]
+pub enum DeviceMismatch {
+    /// No device is connected at this position.
+    Missing { device_id: String, model: String },
+    /// A different model is connected at this position.
+    ModelChanged {
+        device_id: String,
+        expected_model: String,
+        current_model: String,
+    },
+}
+
+impl Project {
+    /// Creates a project of the current format version.
+    pub fn new(
+        sweep_model: SweepModel,
+        system_info: Option<Root>,
+        output_path: Option<String>,
+    ) -> Self {
+        let line_frequency = sweep_model.sweep_config.global_parameters.line_frequency;
+        Project {
+            format_version: PROJECT_FORMAT_VERSION,
+            generator_version: env!("CARGO_PKG_VERSION").to_string(),
+            sweep_model,
+            system_info,
+            line_frequency,
+            output_path,
+        }
+    }
+
+    /// Parses a project from its JSON representation.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the JSON is not a valid project or was written by a newer format
+    /// version.
+    pub fn from_json(json: &str) -> Result<Self, ProjectError> {
+        #[derive(Deserialize)]
+        struct Header {
+            format_version: u32,
+        }
+
+        let header: Header = serde_json::from_str(json)?;
+        if header.format_version > PROJECT_FORMAT_VERSION {
+            return Err(ProjectError::UnsupportedVersion {
+                version: header.format_version,
+            });
+        }
+        let mut project: Project = serde_json::from_str(json)?;
+        project
+            .sweep_model
+            .sweep_config
+            .global_parameters
+            .set_line_frequency(project.line_frequency);
+        Ok(project)
+    }
+
+    /// Creates a project from the configuration embedded in a generated script.
+    ///
+    /// The script is the output path of the project, so regenerating updates it in place.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the script does not contain a valid configuration.
+    pub fn from_script(script: &str, path: &Path) -> Result<Self, ProjectError> {
+        let config = embedded_config::parse(script)?;
+        let mut project = Project::new(
+            config.sweep_model,
+            None,
+            Some(path.to_string_lossy().into_owned()),
+        );
+        project.generator_version = config.generator_version;
+        Ok(project)
+    }
+
+    /// Loads a project from `path`, which is either a project file or a generated `.tsp`
+    /// script.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read or is not a valid project or script.
+    pub fn load(path: &Path) -> Result<Self, ProjectError> {
+        let contents = fs::read_to_string(path)?;
+        if is_script(path) {
+            Self::from_script(&contents, path)
+        } else {
+            Self::from_json(&contents)
+        }
+    }
+
+    /// Saves the project to `path`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the project cannot be serialized or the file cannot be written.
+    pub fn save(&self, path: &Path) -> Result<(), ProjectError> {
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Returns the devices of the project that are not connected, or connected as a different
+    /// model, according to `current_devices`.
+    pub fn device_mismatches(&self, current_devices: &[Device]) -> Vec<DeviceMismatch> {
+        let current: HashMap<&str, &str> = current_devices
+            .iter()
+            .map(|device| (device._id.as_str(), device.model.as_str()))
+            .collect();
+
+        self.sweep_model
+            .sweep_config
+            .device_list
+            .iter()
+            .filter_map(|device| match current.get(device._id.as_str()) {
+                None => Some(DeviceMismatch::Missing {
+                    device_id: device._id.clone(),
+                    model: device.model.clone(),
+                }),
+                Some(model) if *model != device.model => Some(DeviceMismatch::ModelChanged {
+                    device_id: device._id.clone(),
+                    expected_model: device.model.clone(),
+                    current_model: (*model).to_string(),
+                }),
+                Some(_) => None,
+            })
+            .collect()
+    }
+}
+
+/// Returns whether `path` is a TSP script rather than a project file.
+pub fn is_script(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("tsp"))
+}
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::script_component::embedded_config;
+
+    const V1: &str = include_str!("../tests/fixtures/sweep_model/v1.json");
+    const V2: &str = include_str!("../tests/fixtures/sweep_model/v2.json");
+    const SYSTEM: &str = r#"{"systems":[{"name":"sys1","isActive":true,"localNode":"MP5103",
+        "slots":[{"slotId":"slot[1]","module":"MSMU60-2"},
+        {"slotId":"slot[2]","module":"MPSU50-2ST"}],"nodes":[]}]}"#;
+
+    fn sweep_model() -> SweepModel {
+        serde_json::from_str(V2).unwrap()
+    }
+
+    /// Returns a path in the temporary directory that is unique to the calling test.
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("kic-script-gen-{}-{name}", std::process::id()))
+    }
+
+    #[test]
+    fn test_save_load_round_trip() {
+        let system_info: Root = serde_json::from_str(SYSTEM).unwrap();
+        let project = Project::new(
+            sweep_model(),
+            Some(system_info.clone()),
+            Some("C:\\scripts\\sweep.tsp".to_string()),
+        );
+        let path = temp_path("round_trip.json");
+        project.save(&path).unwrap();
+        let loaded = Project::load(&path);
+        let _ = fs::remove_file(&path);
+        let loaded = loaded.unwrap();
+
+        assert_eq!(loaded.format_version, PROJECT_FORMAT_VERSION);
+        assert_eq!(loaded.generator_version, env!("CARGO_PKG_VERSION"));
+        assert!(loaded.sweep_model == project.sweep_model);
+        assert_eq!(
+            serde_json::to_value(&loaded.system_info).unwrap(),
+            serde_json::to_value(Some(system_info)).unwrap()
+        );
+        assert_eq!(loaded.line_frequency, 60.0);
+        assert_eq!(
+            loaded.output_path.as_deref(),
+            Some("C:\\scripts\\sweep.tsp")
+        );
+    }
+
+    #[test]
+    fn test_line_frequency_is_applied() {
+        let mut project = serde_json::to_value(Project::new(sweep_model(), None, None)).unwrap();
+        project["line_frequency"] = 50.0.into();
+        let project = Project::from_json(&project.to_string()).unwrap();
+        assert_eq!(
+            project
+                .sweep_model
+                .sweep_config
+                .global_parameters
+                .line_frequency,
+            50.0
+        );
+    }
+
+    #[test]
+    fn test_older_sweep_model_is_migrated() {
+        let mut project = serde_json::to_value(Project::new(sweep_model(), None, None)).unwrap();
+        project["sweep_model"] = serde_json::from_str(V1).unwrap();
+        let project = Project::from_json(&project.to_string()).unwrap();
+        assert_eq!(
+            project.sweep_model.schema_version,
+            SweepModel::new().schema_version
+        );
+    }
+
+    #[test]
+    fn test_unsupported_version() {
+        let mut project = serde_json::to_value(Project::new(sweep_model(), None, None)).unwrap();
+        project["format_version"] = (PROJECT_FORMAT_VERSION + 1).into();
+        let error = Project::from_json(&project.to_string()).unwrap_err();
+        assert!(matches!(
+            error,
+            ProjectError::UnsupportedVersion { version } if version == PROJECT_FORMAT_VERSION + 1
+        ));
+
+        assert!(matches!(
+            Project::from_json("{}"),
+            Err(ProjectError::ParseError { .. })
+        ));
+    }
+
+    #[test]
+    fn test_from_script() {
+        let sweep_model = sweep_model();
+        let block = embedded_config::to_comment_block(&sweep_model.sweep_config).unwrap();
+        let path = temp_path("from_script.TSP");
+        fs::write(&path, format!("{block}\nreset()\n")).unwrap();
+        let loaded = Project::load(&path);
+        let _ = fs::remove_file(&path);
+        let project = loaded.unwrap();
+
+        assert!(project.sweep_model == sweep_model);
+        assert_eq!(project.generator_version, env!("CARGO_PKG_VERSION"));
+        assert!(project.system_info.is_none());
+        // regenerating updates the script in place
+        assert_eq!(
+            project.output_path,
+            Some(path.to_string_lossy().into_owned())
+        );
+
+        assert!(matches!(
+            Project::from_script("reset()\n", &path),
+            Err(ProjectError::ScriptError { .. })
+        ));
+    }
+
+    #[test]
+    fn test_device_mismatches() {
+        let project = Project::new(sweep_model(), None, None);
+        let mut current = project.sweep_model.sweep_config.device_list.clone();
+        assert!(project.device_mismatches(&current).is_empty());
+
+        // the PSU module of slot 2 was replaced by an SMU module with other device ids
+        current.retain(|device| device.slot_id != "slot[2]");
+        current[1].model = "MSMU60-2X".to_string();
+        assert_eq!(
+            project.device_mismatches(&current),
+            vec![
+                DeviceMismatch::ModelChanged {
+                    device_id: "localnode.slot[1].smu[2]".to_string(),
+                    expected_model: "MSMU60-2".to_string(),
+                    current_model: "MSMU60-2X".to_string(),
+                },
+                DeviceMismatch::Missing {
+                    device_id: "localnode.slot[2].psu[1]".to_string(),
+                    model: "MPSU50-2ST".to_string(),
+                },
+                DeviceMismatch::Missing {
+                    device_id: "localnode.slot[2].psu[2]".to_string(),
+                    model: "MPSU50-2ST".to_string(),
+                },
+            ]
+        );
+        assert_eq!(project.device_mismatches(&[]).len(), 4);
+    }
+}
@@ -1,6 +1,7 @@
 use std::{any::Any, collections::HashMap};
 
-use crate::model::sweep_data::sweep_config::SweepConfig;
+use crate::{error::Result, model::sweep_data::sweep_config::SweepConfig};
+use data_report::Tags;
 
 use super::function::FunctionModel;
 use script_aggregator::script_buffer::ScriptBuffer;
@@ -38,7 +39,7 @@ impl FunctionModel for DataReportModel {
         &self.metadata
     }
 
-    fn to_script(&mut self, sweep_config: &SweepConfig, script_buffer: &mut ScriptBuffer) {
+    fn define_values(&mut self, _sweep_config: &SweepConfig) {
         let buffers = String::from("{}");
         let buffer_names = String::from("{}");
         let buffer_smu_names = String::from("{}");
@@ -55,40 +56,21 @@ impl FunctionModel for DataReportModel {
         self.val_replacement_map
             .insert(String::from("MAX-READINGS-TO-RETURN"), String::from("100"));
 
-        self.val_replacement_map
-            .insert(String::from("TAG-DATA-REPORT"), String::from("Data"));
-        self.val_replacement_map
-            .insert(String::from("TAG-SWEEP-START"), String::from("SWEEP-START"));
-        self.val_replacement_map
-            .insert(String::from("TAG-START"), String::from("START"));
-        self.val_replacement_map.insert(
-            String::from("TAG-EXPECTED-COUNT"),
-            String::from("EXPECTED-COUNT"),
-        );
-        self.val_replacement_map
-            .insert(String::from("TAG-NAME"), String::from("NAME"));
-        self.val_replacement_map
-            .insert(String::from("TAG-PTS-IN-BUFF"), String::from("PTS-IN-BUFF"));
-        self.val_replacement_map.insert(
-            String::from("TAG-PTS-RETURNED"),
-            String::from("PTS-RETURNED"),
-        );
-        self.val_replacement_map.insert(
-            String::from("TAG-BASE-TIME-STAMP"),
-            String::from("BASE-TIME-STAMP"),
-        );
-        self.val_replacement_map
-            .insert(String::from("TAG-READINGS"), String::from("READINGS"));
-        self.val_replacement_map
-            .insert(String::from("TAG-TIMESTAMPS"), String::from("TIMESTAMPS"));
-        self.val_replacement_map
-            .insert(String::from("TAG-SRCVALS"), String::from("SRCVALS"));
-        self.val_replacement_map
-            .insert(String::from("TAG-END"), String::from("END"));
-        self.val_replacement_map
-            .insert(String::from("TAG-COMPLETE"), String::from("COMPLETE"));
+        // the tags the data report parser expects
+        for (placeholder, tag) in Tags::default().replacements() {
+            self.val_replacement_map
+                .insert(placeholder.to_string(), tag.to_string());
+        }
+    }
+
+    fn to_script(
+        &mut self,
+        sweep_config: &SweepConfig,
+        script_buffer: &mut ScriptBuffer,
+    ) -> Result<()> {
+        self.define_values(sweep_config);
 
-        self.build(script_buffer);
+        self.build(script_buffer)
     }
 }
 
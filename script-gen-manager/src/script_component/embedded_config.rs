@@ -0,0 +1,323 @@
+//! The configuration embedded in generated scripts.
+//!
+//! The preamble of a generated script starts with a block of Lua line comments holding the
+//! sweep model that produced it, so the script can be reopened in the generator:
+//!
+//! ```text
+//! -- kic-script-gen configuration begin
+//! -- generator_version: 0.1.3
+//! -- sha256: <hex digest of the JSON>
+//! -- encoding: deflate
+//! -- <base64 of the deflated JSON of the sweep model, split into lines>
+//! -- kic-script-gen configuration end
+//! ```
+//!
+//! Line comments are used because the Lua 5.0 interpreter of TSP instruments nests `[[`
+//! inside long comments. The model is base64 encoded so that editors that trim trailing
+//! whitespace or change line endings leave it intact. It is deflated first because the lists
+//! of list sweeps repeat the same keys for every point. Blocks without an `encoding` line,
+//! written by earlier generators, hold the plain JSON.
+
+use std::{fs, path::Path};
+
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use miniz_oxide::{deflate::compress_to_vec, inflate::decompress_to_vec_with_limit};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+use crate::model::sweep_data::{sweep_config::SweepConfig, sweep_model::SweepModel};
+
+const BEGIN: &str = "-- kic-script-gen configuration begin";
+const END: &str = "-- kic-script-gen configuration end";
+const GENERATOR_VERSION: &str = "-- generator_version: ";
+const CHECKSUM: &str = "-- sha256: ";
+const ENCODING: &str = "-- encoding: ";
+const DEFLATE: &str = "deflate";
+/// Compression level of [`compress_to_vec`], the zlib default.
+const COMPRESSION_LEVEL: u8 = 6;
+/// Largest configuration JSON that is inflated, in bytes.
+const MAX_JSON_LEN: usize = 64 * 1024 * 1024;
+/// Number of base64 characters per comment line.
+const LINE_LEN: usize = 76;
+
+/// Errors when reading the configuration embedded in a script.
+#[derive(Error, Debug)]
+pub enum EmbeddedConfigError {
+    /// The script could not be read
+    #[error("IO error occurred: {source}")]
+    IOError {
+        /// The original `[std::io::Error]`
+        #[from]
+        source: std::io::Error,
+    },
+
+    /// The script has no configuration block
+    #[error("The script does not contain a script generator configuration")]
+    NotFound,
+
+    /// The configuration block is incomplete
+    #[error("Invalid configuration block: {message}")]
+    InvalidBlock {
+        /// What is wrong
+        message: String,
+    },
+
+    /// The configuration was changed after the script was generated
+    #[error("Configuration checksum mismatch, expected {expected} but found {actual}")]
+    ChecksumMismatch {
+        /// The checksum written to the script
+        expected: String,
+        /// The checksum of the embedded configuration
+        actual: String,
+    },
+
+    /// The configuration is not a valid sweep model
+    #[error("Invalid sweep model: {source}")]
+    ParseError {
+        /// The original error
+        #[from]
+        source: serde_json::Error,
+    },
+}
+
+/// The configuration read back from a generated script.
+#[derive(Debug, Clone)]
+pub struct EmbeddedConfig {
+    /// Version of the generator that wrote the script.
+    pub generator_version: String,
+    pub sweep_model: SweepModel,
+}
+
+/// Returns the comment block holding `sweep_config`, without a trailing line break.
+///
+/// # Errors
+///
+/// Returns an error if the configuration cannot be serialized.
+pub fn to_comment_block(sweep_config: &SweepConfig) -> Result<String, serde_json::Error> {
+    let mut sweep_model = SweepModel::new();
+    sweep_model.sweep_config = sweep_config.clone();
+    let json = serde_json::to_string(&sweep_model)?;
+    let encoded = STANDARD.encode(compress_to_vec(json.as_bytes(), COMPRESSION_LEVEL));
+
+    let mut lines = vec![
+        BEGIN.to_string(),
+        format!("{GENERATOR_VERSION}{}", env!("CARGO_PKG_VERSION")),
+        format!("{CHECKSUM}{}", checksum(&json)),
+        format!("{ENCODING}{DEFLATE}"),
+    ];
+    // base64 is ASCII, so any byte offset is a char boundary
+    let mut rest = encoded.as_str();
+    while !rest.is_empty() {
+        let (line, tail) = rest.split_at(LINE_LEN.min(rest.len()));
+        lines.push(format!("-- {line}"));
+        rest = tail;
+    }
+    lines.push(END.to_string());
+    Ok(lines.join("\n"))
+}
+
+/// Reads the configuration embedded in `script`.
+///
+/// # Errors
+///
+/// Returns an error if the script has no configuration block, if the block is incomplete
+/// or was modified, or if it does not hold a valid sweep model.
+pub fn parse(script: &str) -> Result<EmbeddedConfig, EmbeddedConfigError> {
+    let mut lines = script.lines().map(str::trim);
+    if !lines.by_ref().any(|line| line == BEGIN) {
+        return Err(EmbeddedConfigError::NotFound);
+    }
+
+    let mut generator_version = None;
+    let mut expected = None;
+    let mut encoding = None;
+    let mut encoded = String::new();
+    let mut terminated = false;
+    for line in lines {
+        if line == END {
+            terminated = true;
+            break;
+        }
+        if let Some(version) = line.strip_prefix(GENERATOR_VERSION) {
+            generator_version = Some(version.trim().to_string());
+        } else if let Some(digest) = line.strip_prefix(CHECKSUM) {
+            expected = Some(digest.trim().to_ascii_lowercase());
+        } else if let Some(name) = line.strip_prefix(ENCODING) {
+            encoding = Some(name.trim().to_string());
+        } else if let Some(data) = line.strip_prefix("--") {
+            encoded.push_str(data.trim());
+        } else {
+            return Err(invalid(&format!("unexpected line '{line}'")));
+        }
+    }
+    if !terminated {
+        return Err(invalid("missing end of block"));
+    }
+    let generator_version =
+        generator_version.ok_or_else(|| invalid("missing generator_version"))?;
+    let expected = expected.ok_or_else(|| invalid("missing sha256"))?;
+
+    let decoded = STANDARD
+        .decode(encoded)
+        .map_err(|e| invalid(&format!("invalid base64: {e}")))?;
+    let decoded = match encoding.as_deref() {
+        None => decoded,
+        Some(DEFLATE) => decompress_to_vec_with_limit(&decoded, MAX_JSON_LEN)
+            .map_err(|e| invalid(&format!("invalid deflate data: {e}")))?,
+        Some(other) => return Err(invalid(&format!("unsupported encoding '{other}'"))),
+    };
+    let json = String::from_utf8(decoded).map_err(|e| invalid(&e.to_string()))?;
+    let actual = checksum(&json);
+    if actual != expected {
+        return Err(EmbeddedConfigError::ChecksumMismatch { expected, actual });
+    }
+
+    Ok(EmbeddedConfig {
+        generator_version,
+        sweep_model: serde_json::from_str(&json)?,
+    })
+}
+
+/// Reads the configuration embedded in the script at `path`.
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be read or does not contain a valid configuration.
+pub fn load(path: &Path) -> Result<EmbeddedConfig, EmbeddedConfigError> {
+    parse(&fs::read_to_string(path)?)
+}
+
+fn checksum(json: &str) -> String {
+    Sha256::digest(json.as_bytes())
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+fn invalid(message: &str) -> EmbeddedConfigError {
+    EmbeddedConfigError::InvalidBlock {
+        message: message.to_string(),
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod tests {
+    use super::*;
+    use crate::model::sweep_data::parameters::ParameterFloat;
+
+    const V2: &str = include_str!("../../tests/fixtures/sweep_model/v2.json");
+
+    fn script() -> (SweepModel, String) {
+        let sweep_model: SweepModel = serde_json::from_str(V2).unwrap();
+        let block = to_comment_block(&sweep_model.sweep_config).unwrap();
+        let script = format!("{block}\nreset()\nprint(\"done\")\n");
+        (sweep_model, script)
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let (sweep_model, script) = script();
+        assert!(script.lines().all(|line| line.len() <= 80));
+
+        let config = parse(&script).unwrap();
+        assert_eq!(config.generator_version, env!("CARGO_PKG_VERSION"));
+        assert_eq!(
+            serde_json::to_value(&config.sweep_model).unwrap(),
+            serde_json::to_value(&sweep_model).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_crlf_and_indent_are_ignored() {
+        let (_, script) = script();
+        let script = script
+            .lines()
+            .map(|line| format!("    {line}"))
+            .collect::<Vec<_>>()
+            .join("\r\n");
+        assert!(parse(&script).is_ok());
+    }
+
+    #[test]
+    fn test_modified_block_is_rejected() {
+        let (_, script) = script();
+        let tampered = script.replacen(CHECKSUM, &format!("{CHECKSUM}00"), 1);
+        assert!(matches!(
+            parse(&tampered),
+            Err(EmbeddedConfigError::ChecksumMismatch { .. })
+        ));
+
+        let truncated = script.replace(END, "");
+        assert!(matches!(
+            parse(&truncated),
+            Err(EmbeddedConfigError::InvalidBlock { .. })
+        ));
+    }
+
+    #[test]
+    fn test_plain_block() {
+        // blocks written before the configuration was deflated
+        let (sweep_model, _) = script();
+        let json = serde_json::to_string(&sweep_model).unwrap();
+        let mut lines = vec![
+            BEGIN.to_string(),
+            format!("{GENERATOR_VERSION}0.1.3"),
+            format!("{CHECKSUM}{}", checksum(&json)),
+        ];
+        lines.extend(
+            STANDARD
+                .encode(&json)
+                .as_bytes()
+                .chunks(LINE_LEN)
+                .map(|line| format!("-- {}", String::from_utf8_lossy(line))),
+        );
+        lines.push(END.to_string());
+
+        let config = parse(&lines.join("\n")).unwrap();
+        assert_eq!(config.generator_version, "0.1.3");
+        assert_eq!(config.sweep_model, sweep_model);
+
+        let unknown =
+            lines
+                .join("\n")
+                .replacen(CHECKSUM, &format!("{ENCODING}zstd\n{CHECKSUM}"), 1);
+        assert!(matches!(
+            parse(&unknown),
+            Err(EmbeddedConfigError::InvalidBlock { .. })
+        ));
+    }
+
+    #[test]
+    fn test_large_list_sweep_is_bounded() {
+        const POINTS: usize = 60000;
+
+        let (mut sweep_model, _) = script();
+        let sweep_config = &mut sweep_model.sweep_config;
+        sweep_config.sweep_global_parameters.list_sweep = true;
+        sweep_config.sweep_global_parameters.sweep_points.value = POINTS as i32;
+        for (channel, sweep_channel) in sweep_config.sweep_channels.iter_mut().enumerate() {
+            sweep_channel.start_stop_channel.list = (0..POINTS)
+                .map(|i| {
+                    let value = (i * (channel + 1) % 2001) as f64 / 1024.0 - 1.0;
+                    ParameterFloat::new(&format!("list_{i}"), value, Some("V".to_string()))
+                })
+                .collect();
+        }
+        let json = serde_json::to_string(&sweep_model).unwrap();
+        let block = to_comment_block(&sweep_model.sweep_config).unwrap();
+        // about 50 bytes of JSON per point, the keys and units compress away
+        assert!(json.len() > 50 * POINTS);
+        assert!(block.len() < 10 * POINTS, "{} bytes", block.len());
+
+        assert!(parse(&block).unwrap().sweep_model == sweep_model);
+    }
+
+    #[test]
+    fn test_missing_block() {
+        assert!(matches!(
+            parse("reset()\n"),
+            Err(EmbeddedConfigError::NotFound)
+        ));
+    }
+}
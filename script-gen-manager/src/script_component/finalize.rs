@@ -1,7 +1,7 @@
 use std::{any::Any, collections::HashMap};
 
 use super::function::FunctionModel;
-use crate::model::sweep_data::sweep_config::SweepConfig;
+use crate::{error::Result, model::sweep_data::sweep_config::SweepConfig};
 use script_aggregator::script_buffer::ScriptBuffer;
 use xml_handler::group::Group;
 
@@ -36,8 +36,14 @@ impl FunctionModel for FinalizeModel {
         &self.metadata
     }
 
-    fn to_script(&mut self, sweep_config: &SweepConfig, script_buffer: &mut ScriptBuffer) {
-        self.build(script_buffer);
+    fn define_values(&mut self, _sweep_config: &SweepConfig) {}
+
+    fn to_script(
+        &mut self,
+        sweep_config: &SweepConfig,
+        script_buffer: &mut ScriptBuffer,
+    ) -> Result<()> {
+        self.build(script_buffer)
     }
 }
 
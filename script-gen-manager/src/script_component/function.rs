@@ -1,9 +1,9 @@
 use std::any::Any;
 
 use script_aggregator::script_buffer::ScriptBuffer;
-use xml_handler::composite::CommonChunk;
+use xml_handler::{composite::CommonChunk, render::Scope};
 
-use crate::model::sweep_data::sweep_config::SweepConfig;
+use crate::{error::Result, model::sweep_data::sweep_config::SweepConfig};
 
 pub trait FunctionModel: Send + Sync {
     fn as_any(&self) -> &dyn Any;
@@ -15,8 +15,18 @@ pub trait FunctionModel: Send + Sync {
     fn get_val_replacement_map(&self) -> &std::collections::HashMap<String, String>;
     /// Returns the metadata (parsed Group information) of the function model.
     fn get_metadata(&self) -> &xml_handler::group::Group;
+    /// Fills the value replacement map with the values of `sweep_config`.
+    fn define_values(&mut self, sweep_config: &SweepConfig);
     /// Converts the function model to a script.
-    fn to_script(&mut self, sweep_config: &SweepConfig, script_buffer: &mut ScriptBuffer);
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the templates of the function model cannot be rendered.
+    fn to_script(
+        &mut self,
+        sweep_config: &SweepConfig,
+        script_buffer: &mut ScriptBuffer,
+    ) -> Result<()>;
 
     /// Converts a description string into a Lua comment format.
     ///
@@ -109,19 +119,21 @@ pub trait FunctionModel: Send + Sync {
     /// # Arguments
     ///
     /// * `script_buffer` - A mutable reference to the script buffer.
-    fn build(&mut self, script_buffer: &mut ScriptBuffer) {
-        let metadata = self.get_metadata();
-        let val_replacement_map = self.get_val_replacement_map();
-
-        let mut metadata = metadata.clone();
-        for child in metadata.children.iter_mut() {
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a template cannot be rendered.
+    fn build(&self, script_buffer: &mut ScriptBuffer) -> Result<()> {
+        let scope = Scope::new(self.get_val_replacement_map());
+        for child in &self.get_metadata().children {
             if let xml_handler::group::IncludeResult::Composite(comp) = child {
                 //not aux type
                 if comp.type_.is_none() {
-                    comp.to_script(script_buffer, val_replacement_map);
+                    comp.to_script(script_buffer, &scope)?;
                 }
             }
         }
+        Ok(())
     }
 
     /// Formats a floating-point value.
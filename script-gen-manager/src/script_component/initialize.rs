@@ -1,9 +1,9 @@
 use std::{any::Any, collections::HashMap};
 
 use script_aggregator::script_buffer::ScriptBuffer;
-use xml_handler::{composite::CommonChunk, group::Group};
+use xml_handler::{composite::CommonChunk, group::Group, render::Scope};
 
-use crate::model::sweep_data::sweep_config::SweepConfig;
+use crate::{error::Result, model::sweep_data::sweep_config::SweepConfig};
 
 use super::function::FunctionModel;
 
@@ -38,7 +38,7 @@ impl FunctionModel for InitializeModel {
         &self.metadata
     }
 
-    fn to_script(&mut self, sweep_config: &SweepConfig, script_buffer: &mut ScriptBuffer) {
+    fn define_values(&mut self, _sweep_config: &SweepConfig) {
         self.val_replacement_map
             .insert(String::from("MAX-NODES"), String::from("64"));
         self.val_replacement_map
@@ -47,8 +47,17 @@ impl FunctionModel for InitializeModel {
             .insert(String::from("INCLUDE-TIMESTAMPS"), String::from("1"));
         self.val_replacement_map
             .insert(String::from("INCLUDE-SRCVALS"), String::from("1"));
+    }
+
+    fn to_script(
+        &mut self,
+        sweep_config: &SweepConfig,
+        script_buffer: &mut ScriptBuffer,
+    ) -> Result<()> {
+        self.define_values(sweep_config);
 
-        for child in self.metadata.children.iter_mut() {
+        let scope = Scope::new(&self.val_replacement_map);
+        for child in &self.metadata.children {
             if let xml_handler::group::IncludeResult::Composite(comp) = child {
                 // aux chunk
                 if comp.type_.is_some() {
@@ -56,10 +65,10 @@ impl FunctionModel for InitializeModel {
                     temp.set_auto_indent(true);
 
                     temp.change_indent(ScriptBuffer::DEFAULT_INDENT);
-                    comp.to_script(&mut temp, &self.val_replacement_map);
+                    comp.to_script(&mut temp, &scope)?;
                     temp.change_indent(-ScriptBuffer::DEFAULT_INDENT);
 
-                    script_buffer.preamble_append(temp.to_string());
+                    script_buffer.preamble_append_script(&temp);
                 }
             }
         }
@@ -67,7 +76,7 @@ impl FunctionModel for InitializeModel {
         // self.val_replacement_map
         //     .insert(String::from("PRODUCT-SETUP"), self.get_product_setup());
 
-        self.build(script_buffer);
+        self.build(script_buffer)
     }
 }
 
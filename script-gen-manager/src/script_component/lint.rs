@@ -0,0 +1,101 @@
+//! Checks the script templates against the values the function models provide.
+
+use std::path::PathBuf;
+
+use xml_handler::{
+    generic_parser,
+    group::Group,
+    lint::{self, LintIssue, LintKind, Severity},
+    templates::TemplateLoader,
+};
+
+use super::{
+    data_report::DataReportModel, finalize::FinalizeModel, function::FunctionModel,
+    initialize::InitializeModel, sweep::SweepModel,
+};
+use crate::model::sweep_data::{sweep_config::SweepConfig, sweep_limits::SweepLimits};
+
+/// System the sample configuration is created from. Its four channels are enough for a bias,
+/// a step and a sweep channel, so the models provide the values of every kind of channel.
+const SAMPLE_SYSTEM: &str = r#"{"systems":[{"name":"lint","isActive":true,"localNode":"MP5103",
+    "slots":[{"slotId":"slot[1]","module":"MSMU60-2"},{"slotId":"slot[2]","module":"MPSU50-2ST"}],
+    "nodes":[]}]}"#;
+
+/// Checks the templates, preferring the ones of `template_dir` over the embedded ones.
+///
+/// The includes are checked first. If they are valid, each group is checked against the
+/// value replacement map its function model creates for a sample configuration.
+pub fn lint_templates(template_dir: Option<PathBuf>) -> Vec<LintIssue> {
+    let mut issues = lint::lint_includes(template_dir.clone(), generic_parser::ROOT_TEMPLATE);
+    if issues.iter().any(|issue| issue.severity == Severity::Error) {
+        return issues;
+    }
+
+    let groups = match generic_parser::parse_xml(&mut TemplateLoader::new(template_dir)) {
+        Ok(groups) => groups,
+        Err(e) => {
+            issues.push(LintIssue::error(
+                LintKind::ParseError,
+                generic_parser::ROOT_TEMPLATE,
+                e.to_string(),
+            ));
+            return issues;
+        }
+    };
+
+    let limits = groups
+        .iter()
+        .find(|group| group.type_ == "Sweep")
+        .map_or_else(SweepLimits::default, |group| {
+            SweepLimits::from_variables(&group.variable_list)
+        });
+    let sweep_config = sample_config(&limits);
+    for group in groups {
+        let Some(mut model) = function_model(group.clone()) else {
+            issues.push(LintIssue::warning(
+                LintKind::UnknownGroup,
+                &group.type_,
+                format!("no function model renders groups of type '{}'", group.type_),
+            ));
+            continue;
+        };
+        model.define_values(&sweep_config);
+        issues.extend(lint::lint_group(&group, model.get_val_replacement_map()));
+    }
+    issues
+}
+
+fn function_model(group: Group) -> Option<Box<dyn FunctionModel>> {
+    match group.type_.as_str() {
+        "Initialize" => Some(Box::new(InitializeModel::new(group))),
+        "Sweep" => Some(Box::new(SweepModel::new(group))),
+        "DataReport" => Some(Box::new(DataReportModel::new(group))),
+        "Finalize" => Some(Box::new(FinalizeModel::new(group))),
+        _ => None,
+    }
+}
+
+fn sample_config(limits: &SweepLimits) -> SweepConfig {
+    let mut sweep_config = SweepConfig::new();
+    sweep_config.create_device_list(SAMPLE_SYSTEM);
+    sweep_config.apply_defaults(limits);
+    sweep_config.auto_configure();
+    sweep_config.evaluate(limits);
+    sweep_config
+}
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_embedded_templates_have_no_errors() {
+        let issues = lint_templates(None);
+        let errors: Vec<_> = issues
+            .iter()
+            .filter(|issue| issue.severity == Severity::Error)
+            .collect();
+        assert!(errors.is_empty(), "{errors:?}");
+    }
+}
@@ -1,4 +1,6 @@
+pub mod embedded_config;
 pub mod function;
+pub mod lint;
 pub mod script;
 
 mod data_report;
@@ -1,16 +1,27 @@
-use std::{fs::File, io::Write, path::Path};
+use std::{
+    fs::{self, File},
+    io::Write,
+    path::{Path, PathBuf},
+};
 
 use super::{
-    data_report::DataReportModel, finalize::FinalizeModel, function::FunctionModel,
-    initialize::InitializeModel, sweep::SweepModel,
+    data_report::DataReportModel, embedded_config, finalize::FinalizeModel,
+    function::FunctionModel, initialize::InitializeModel, sweep::SweepModel,
+};
+use crate::{
+    catalog::Catalog,
+    error::{Result, ScriptGenError},
+    model::sweep_data::sweep_config::SweepConfig,
 };
-use crate::{catalog::Catalog, model::sweep_data::sweep_config::SweepConfig};
-use script_aggregator::script_buffer::ScriptBuffer;
+use script_aggregator::{script_buffer::ScriptBuffer, source_map::SourceMap};
+use xml_handler::group::Group;
 
 /// Creates and manages the individual functions that make up the script.
 pub struct ScriptModel {
     catalog: Catalog,
     chunks: Vec<Box<dyn FunctionModel>>,
+    /// Required template groups that are not in the catalog.
+    missing_groups: Vec<String>,
 }
 
 impl ScriptModel {
@@ -18,57 +29,86 @@ impl ScriptModel {
         ScriptModel {
             catalog,
             chunks: Vec::new(), //Initialize with an empty vector
+            missing_groups: Vec::new(),
         }
     }
 
     /// Clears the existing script chunks and adds the initialize and finalize chunks.
     pub fn initialize_scripts(&mut self) {
         self.chunks.clear();
+        self.missing_groups.clear();
 
-        if let Some(group) = self.catalog.function_metadata_map.get("Initialize") {
-            let initialize = InitializeModel::new(group.clone());
+        if let Some(group) = self.group("Initialize") {
+            let initialize = InitializeModel::new(group);
             self.chunks.push(Box::new(initialize));
         }
 
-        if let Some(group) = self.catalog.function_metadata_map.get("Finalize") {
-            let finalize = FinalizeModel::new(group.clone());
+        if let Some(group) = self.group("Finalize") {
+            let finalize = FinalizeModel::new(group);
             self.chunks.push(Box::new(finalize));
         }
     }
 
-    /// Converts the script chunks to a script including ordering, indent and substitution.
-    pub fn to_script(&mut self, sweep_config: &SweepConfig, file_path: &str) {
+    /// Converts the script chunks to a script including ordering, indent and substitution
+    /// and returns it without touching the filesystem.
+    ///
+    /// The preamble starts with `sweep_config` as an [`embedded_config`] comment block.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the catalog failed to load its templates, if template groups the
+    /// script is built from are not in the catalog, if a template cannot be rendered, or if
+    /// the configuration cannot be embedded.
+    pub fn render(&mut self, sweep_config: &SweepConfig) -> Result<String> {
+        self.render_with_source_map(sweep_config)
+            .map(|(script, _)| script)
+    }
+
+    /// Renders the script like [`ScriptModel::render`] and returns it with the template
+    /// elements each of its lines was generated from.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the script cannot be rendered.
+    pub fn render_with_source_map(
+        &mut self,
+        sweep_config: &SweepConfig,
+    ) -> Result<(String, SourceMap)> {
+        if let Some(source) = &self.catalog.error {
+            return Err(ScriptGenError::CatalogError {
+                source: source.clone(),
+            });
+        }
+        if !self.missing_groups.is_empty() {
+            return Err(ScriptGenError::MissingTemplateGroups {
+                groups: self.missing_groups.clone(),
+            });
+        }
         let mut script_buffer = ScriptBuffer::new();
         script_buffer.set_auto_indent(true);
+        script_buffer.preamble_append(embedded_config::to_comment_block(sweep_config)?);
         for chunk in self.chunks.iter_mut() {
-            chunk.to_script(sweep_config, &mut script_buffer);
-        }
-        //let file_path = "C:\\ScriptGen\\Snippet.txt";
-        println!("Writing script to file: {}", file_path);
-        let path = Path::new(file_path);
-
-        // Check if file exists, if not, create the file and its parent directory if needed
-        if !path.exists() {
-            if let Some(parent) = path.parent() {
-                if !parent.exists() {
-                    if let Err(e) = std::fs::create_dir_all(parent) {
-                        println!("Failed to create directory: {}", e);
-                        return;
-                    }
-                }
-            }
+            chunk.to_script(sweep_config, &mut script_buffer)?;
         }
+        Ok((script_buffer.to_string(), script_buffer.source_map()))
+    }
 
-        match File::create(file_path) {
-            Ok(mut file_res) => {
-                if let Err(e) = file_res.write_all(script_buffer.to_string().as_bytes()) {
-                    println!("Error writing to file: {}", e);
-                }
-            }
-            Err(e) => {
-                println!("Error creating file: {}", e);
-            }
-        }
+    /// Converts the script chunks to a script including ordering, indent and substitution
+    /// and writes it to `path` with `writer`, along with its source map.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the script cannot be rendered or written.
+    pub fn to_script(
+        &mut self,
+        sweep_config: &SweepConfig,
+        path: &Path,
+        writer: ScriptWriter,
+    ) -> Result<WriteStatus> {
+        let (script, source_map) = self.render_with_source_map(sweep_config)?;
+        let status = writer.write(&script, path)?;
+        writer.write_source_map(&source_map, path)?;
+        Ok(status)
     }
 
     /// Adds a function chunk to the script.
@@ -87,17 +127,153 @@ impl ScriptModel {
 
     /// Adds a sweep function chunk to the script.
     pub fn add_sweep(&mut self) {
-        if let Some(group) = self.catalog.function_metadata_map.get("Sweep") {
-            let sweep = SweepModel::new(group.clone());
+        if let Some(group) = self.group("Sweep") {
+            let sweep = SweepModel::new(group);
             self.add(Box::new(sweep));
+        } else {
+            tracing::error!("Script template group 'Sweep' not found in the catalog");
+            self.missing_groups.push("Sweep".to_string());
         }
     }
 
     /// Adds a data report function chunk to the script.
     pub fn add_data_report(&mut self) {
-        if let Some(group) = self.catalog.function_metadata_map.get("DataReport") {
-            let data_report = DataReportModel::new(group.clone());
+        if let Some(group) = self.group("DataReport") {
+            let data_report = DataReportModel::new(group);
             self.add(Box::new(data_report));
         }
     }
+
+    fn group(&self, name: &str) -> Option<Group> {
+        self.catalog.function_metadata_map.get(name).cloned()
+    }
+}
+
+/// Result of [`ScriptWriter::write`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WriteStatus {
+    /// The file was created or replaced.
+    Written,
+    /// The file already had the same content and was left untouched.
+    Unchanged,
+}
+
+/// Writes generated scripts to disk.
+///
+/// The script is written to a temporary file next to the target, which then replaces the
+/// target, so readers never see a partially written script.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ScriptWriter {
+    /// Copy the previous script to `<file>.bak` before replacing it.
+    pub keep_backup: bool,
+}
+
+impl ScriptWriter {
+    /// Writes `script` to `path`, creating the parent directory if needed.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ScriptGenError::IOError`] if the directory, the temporary file, the backup
+    /// or the script cannot be written.
+    pub fn write(&self, script: &str, path: &Path) -> Result<WriteStatus> {
+        let io_error = |path: &Path| {
+            let path = path.to_path_buf();
+            move |source| ScriptGenError::IOError { path, source }
+        };
+
+        if fs::read(path).is_ok_and(|existing| existing == script.as_bytes()) {
+            tracing::debug!(path = %path.display(), "Script is unchanged");
+            return Ok(WriteStatus::Unchanged);
+        }
+        tracing::info!(path = %path.display(), "Writing script to file");
+
+        let parent = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty());
+        if let Some(parent) = parent {
+            fs::create_dir_all(parent).map_err(io_error(parent))?;
+        }
+        if self.keep_backup && path.exists() {
+            let backup = with_suffix(path, ".bak");
+            fs::copy(path, &backup).map_err(io_error(&backup))?;
+        }
+
+        let temp = with_suffix(path, ".tmp");
+        let result = File::create(&temp)
+            .and_then(|mut file| {
+                file.write_all(script.as_bytes())?;
+                file.sync_all()
+            })
+            .map_err(io_error(&temp))
+            .and_then(|()| fs::rename(&temp, path).map_err(io_error(path)));
+        if result.is_err() {
+            let _ = fs::remove_file(&temp);
+        }
+        result.map(|()| WriteStatus::Written)
+    }
+
+    /// Writes `source_map` as JSON next to the script at `path`, see [`source_map_path`].
+    ///
+    /// No backup of the previous source map is kept.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ScriptGenError::SerializationError`] if the source map cannot be serialized
+    /// and [`ScriptGenError::IOError`] if it cannot be written.
+    pub fn write_source_map(&self, source_map: &SourceMap, path: &Path) -> Result<WriteStatus> {
+        let json = serde_json::to_string_pretty(source_map)?;
+        ScriptWriter { keep_backup: false }.write(&json, &source_map_path(path))
+    }
+}
+
+/// Returns the path of the source map of the script at `path`, `<script>.map`.
+pub fn source_map_path(path: &Path) -> PathBuf {
+    with_suffix(path, ".map")
+}
+
+/// Returns `path` with `suffix` appended to the file name.
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_script_writer() {
+        let dir = std::env::temp_dir().join(format!("script-writer-{}", std::process::id()));
+        let path = dir.join("nested").join("script.tsp");
+        let backup = with_suffix(&path, ".bak");
+        let writer = ScriptWriter { keep_backup: true };
+
+        assert_eq!(writer.write("first", &path).unwrap(), WriteStatus::Written);
+        assert!(!backup.exists());
+        assert_eq!(
+            writer.write("first", &path).unwrap(),
+            WriteStatus::Unchanged
+        );
+        assert_eq!(writer.write("second", &path).unwrap(), WriteStatus::Written);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "second");
+        assert_eq!(fs::read_to_string(&backup).unwrap(), "first");
+        assert!(!with_suffix(&path, ".tmp").exists());
+
+        let source_map = SourceMap::default();
+        writer.write_source_map(&source_map, &path).unwrap();
+        let json = fs::read_to_string(dir.join("nested").join("script.tsp.map")).unwrap();
+        assert_eq!(
+            serde_json::from_str::<SourceMap>(&json).unwrap(),
+            source_map
+        );
+        assert!(!with_suffix(&source_map_path(&path), ".bak").exists());
+
+        // a directory in place of the file cannot be replaced
+        let error = writer.write("third", &dir.join("nested")).unwrap_err();
+        assert!(matches!(error, ScriptGenError::IOError { .. }));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
 }
@@ -2,6 +2,7 @@ use std::{any::Any, collections::HashMap};
 
 use crate::{
     device::DeviceType,
+    error::Result,
     instr_metadata::base_metadata::BaseMetadata,
     model::{
         chan_data::channel_range::ChannelRange,
@@ -50,20 +51,29 @@ impl FunctionModel for SweepModel {
         &self.metadata
     }
 
-    fn to_script(&mut self, sweep_config: &SweepConfig, script_buffer: &mut ScriptBuffer) {
+    fn define_values(&mut self, sweep_config: &SweepConfig) {
+        self.attributes = SweepModelAttributes::new();
+        self.val_replacement_map = HashMap::new();
+
+        self.define_bias_channels(sweep_config);
+        self.define_step_channels(sweep_config);
+        self.define_sweep_channels(sweep_config);
+        self.define_common_settings(sweep_config);
+    }
+
+    fn to_script(
+        &mut self,
+        sweep_config: &SweepConfig,
+        script_buffer: &mut ScriptBuffer,
+    ) -> Result<()> {
         if sweep_config.step_channels.is_empty() && sweep_config.sweep_channels.is_empty() {
             script_buffer.postamble_append(String::from(
                 "-- no sweep ... requires at least 1 step channel or 1 sweep channel",
             ));
+            Ok(())
         } else {
-            self.attributes = SweepModelAttributes::new();
-            self.val_replacement_map = HashMap::new();
-
-            self.define_bias_channels(sweep_config);
-            self.define_step_channels(sweep_config);
-            self.define_sweep_channels(sweep_config);
-            self.define_common_settings(sweep_config);
-            self.build(script_buffer);
+            self.define_values(sweep_config);
+            self.build(script_buffer)
         }
     }
 }
@@ -0,0 +1,516 @@
+//! Results printed by the `_Sweep()` function of a generated script.
+//!
+//! After the sweep, `PrintResults` prints the measure buffers of all channels as a CSV table:
+//!
+//! ```text
+//! Reading Number,bias[1].CURRENT,step[1].CURRENT,sweep[1].CURRENT,sweep[1].VOLTAGE
+//! 1,1.2e-09,3.4e-06,5.6e-03,1.0
+//! ```
+//!
+//! `bias[i]`, `step[i]` and `sweep[i]` are the i-th bias, step and sweep channel of the
+//! [`SweepConfig`] the script was generated from. Every channel measures `measure_count`
+//! times at each sweep point of each step, so the reading number identifies the step, the
+//! sweep point and the sample.
+
+use std::fmt::Write as _;
+
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::{
+    instr_metadata::base_metadata::BaseMetadata,
+    model::{
+        chan_data::start_stop_channel::StartStopChannel, sweep_data::sweep_config::SweepConfig,
+    },
+};
+
+const HEADER_START: &str = "Reading Number,";
+
+/// Errors when parsing the results table.
+#[derive(Error, Debug, PartialEq)]
+pub enum SweepResultsError {
+    /// The output does not contain the results table
+    #[error("The output does not contain sweep results")]
+    NoData,
+
+    /// A column heading does not name a channel of the configuration
+    #[error("Unknown results column '{heading}'")]
+    UnknownColumn {
+        /// The column heading
+        heading: String,
+    },
+
+    /// A row of the table is malformed
+    #[error("Invalid results row {row}: {message}")]
+    InvalidRow {
+        /// Reading number of the row
+        row: usize,
+        /// What is wrong
+        message: String,
+    },
+
+    /// The table has more rows than the configuration produces
+    #[error("The results have {found} rows, but the configuration produces {expected}")]
+    TooManyRows {
+        /// Number of rows the configuration produces
+        expected: usize,
+        /// Number of rows in the table
+        found: usize,
+    },
+}
+
+/// Role of a channel in the sweep.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ChannelRole {
+    Bias,
+    Step,
+    Sweep,
+}
+
+impl ChannelRole {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ChannelRole::Bias => "bias",
+            ChannelRole::Step => "step",
+            ChannelRole::Sweep => "sweep",
+        }
+    }
+}
+
+/// One measurement of one channel.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Measurement {
+    pub channel: String,
+    pub role: ChannelRole,
+    /// Index of the step, starting at 0. Always 0 without step channels.
+    pub step: usize,
+    /// Index of the sweep point, starting at 0.
+    pub point: usize,
+    /// Index of the measurement at the sweep point, starting at 0.
+    pub sample: usize,
+    pub source_function: String,
+    /// Programmed source level of the channel at this point.
+    pub source_value: f64,
+    pub source_unit: String,
+    pub measure_function: String,
+    /// Measured value, `NaN` if the instrument returned none.
+    pub value: f64,
+    pub unit: String,
+}
+
+/// The results of one sweep.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct SweepResults {
+    pub step_points: usize,
+    pub sweep_points: usize,
+    pub measure_count: usize,
+    /// Set if the table has all rows the configuration produces.
+    pub complete: bool,
+    /// Measurements ordered by reading number, then by column.
+    pub measurements: Vec<Measurement>,
+}
+
+/// A column of the results table.
+struct Column {
+    channel: String,
+    role: ChannelRole,
+    source_function: String,
+    source_unit: String,
+    measure_function: String,
+    unit: String,
+    /// Source level at each step (step channels), sweep point (sweep channels) or the bias.
+    levels: Vec<f64>,
+}
+
+impl SweepResults {
+    /// Parses the results table in `output`, the console output of a script generated from
+    /// `sweep_config`. Lines before and after the table are ignored.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the output has no table, or if the table does not match
+    /// `sweep_config`.
+    pub fn parse(output: &str, sweep_config: &SweepConfig) -> Result<Self, SweepResultsError> {
+        let mut lines = output
+            .lines()
+            .map(str::trim)
+            .skip_while(|line| !line.starts_with(HEADER_START));
+        let header = lines.next().ok_or(SweepResultsError::NoData)?;
+
+        let step_points = if sweep_config.step_channels.is_empty() {
+            1
+        } else {
+            to_count(sweep_config.step_global_parameters.step_points.value)
+        };
+        let sweep_points = to_count(sweep_config.sweep_global_parameters.sweep_points.value);
+        let measure_count = to_count(
+            sweep_config
+                .global_parameters
+                .sweep_timing_config
+                .measure_count
+                .value,
+        );
+        let columns = header
+            .split(',')
+            .skip(1)
+            .map(|heading| column(heading.trim(), sweep_config, step_points, sweep_points))
+            .collect::<Result<Vec<Column>, SweepResultsError>>()?;
+
+        let expected = step_points * sweep_points * measure_count;
+        let mut measurements = Vec::new();
+        let mut rows = 0;
+        for line in lines {
+            let mut fields = line.split(',').map(str::trim);
+            // the table ends at the first line that is not the next row
+            if fields.next().and_then(|field| field.parse::<usize>().ok()) != Some(rows + 1) {
+                break;
+            }
+            rows += 1;
+            if rows > expected {
+                return Err(SweepResultsError::TooManyRows {
+                    expected,
+                    found: rows,
+                });
+            }
+
+            let values: Vec<&str> = fields.collect();
+            if values.len() != columns.len() {
+                return Err(SweepResultsError::InvalidRow {
+                    row: rows,
+                    message: format!("expected {} values, found {}", columns.len(), values.len()),
+                });
+            }
+            let index = rows - 1;
+            let sample = index % measure_count;
+            let point = index / measure_count % sweep_points;
+            let step = index / measure_count / sweep_points;
+            for (column, value) in columns.iter().zip(values) {
+                let value = parse_value(value).ok_or_else(|| SweepResultsError::InvalidRow {
+                    row: rows,
+                    message: format!("invalid value '{value}' of {}", column.channel),
+                })?;
+                let source_value = match column.role {
+                    ChannelRole::Bias => column.levels[0],
+                    ChannelRole::Step => column.levels[step],
+                    ChannelRole::Sweep => column.levels[point],
+                };
+                measurements.push(Measurement {
+                    channel: column.channel.clone(),
+                    role: column.role,
+                    step,
+                    point,
+                    sample,
+                    source_function: column.source_function.clone(),
+                    source_value,
+                    source_unit: column.source_unit.clone(),
+                    measure_function: column.measure_function.clone(),
+                    value,
+                    unit: column.unit.clone(),
+                });
+            }
+        }
+
+        Ok(SweepResults {
+            step_points,
+            sweep_points,
+            measure_count,
+            complete: rows == expected,
+            measurements,
+        })
+    }
+
+    /// Returns the measurements of `channel` at sweep point `point` of step `step`.
+    pub fn get<'a>(
+        &'a self,
+        channel: &'a str,
+        step: usize,
+        point: usize,
+    ) -> impl Iterator<Item = &'a Measurement> + 'a {
+        self.measurements.iter().filter(move |measurement| {
+            measurement.channel == channel && measurement.step == step && measurement.point == point
+        })
+    }
+
+    /// Returns the measurements as CSV, one row per measurement.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from(
+            "channel,role,step,point,sample,source_function,source_value,source_unit,\
+             measure_function,value,unit\n",
+        );
+        for m in &self.measurements {
+            let _ = writeln!(
+                csv,
+                "{},{},{},{},{},{},{},{},{},{},{}",
+                csv_field(&m.channel),
+                m.role.as_str(),
+                m.step,
+                m.point,
+                m.sample,
+                m.source_function,
+                m.source_value,
+                m.source_unit,
+                m.measure_function,
+                m.value,
+                m.unit
+            );
+        }
+        csv
+    }
+
+    /// Returns the results as pretty printed JSON, values that are not finite are written as
+    /// `null`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the results cannot be serialized.
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+}
+
+/// Maps a heading such as `sweep[2].VOLTAGE` to its channel.
+fn column(
+    heading: &str,
+    sweep_config: &SweepConfig,
+    step_points: usize,
+    sweep_points: usize,
+) -> Result<Column, SweepResultsError> {
+    let unknown = || SweepResultsError::UnknownColumn {
+        heading: heading.to_string(),
+    };
+    let (channel, quantity) = heading.split_once('.').ok_or_else(unknown)?;
+    let (role, index) = channel
+        .strip_suffix(']')
+        .and_then(|channel| channel.split_once('['))
+        .ok_or_else(unknown)?;
+    let index = index
+        .parse::<usize>()
+        .ok()
+        .and_then(|index| index.checked_sub(1))
+        .ok_or_else(unknown)?;
+
+    let (role, attributes, levels) = match role {
+        "bias" => {
+            let channel = sweep_config.bias_channels.get(index).ok_or_else(unknown)?;
+            (
+                ChannelRole::Bias,
+                &channel.common_chan_attributes,
+                vec![channel.bias.value],
+            )
+        }
+        "step" => {
+            let channel = sweep_config.step_channels.get(index).ok_or_else(unknown)?;
+            let list = sweep_config.step_global_parameters.list_step;
+            (
+                ChannelRole::Step,
+                &channel.start_stop_channel.common_chan_attributes,
+                source_levels(&channel.start_stop_channel, list, step_points),
+            )
+        }
+        "sweep" => {
+            let channel = sweep_config.sweep_channels.get(index).ok_or_else(unknown)?;
+            let list = sweep_config.sweep_global_parameters.list_sweep;
+            (
+                ChannelRole::Sweep,
+                &channel.start_stop_channel.common_chan_attributes,
+                source_levels(&channel.start_stop_channel, list, sweep_points),
+            )
+        }
+        _ => return Err(unknown()),
+    };
+    let measure_function = match quantity {
+        "CURRENT" => BaseMetadata::FUNCTION_CURRENT,
+        "VOLTAGE" => BaseMetadata::FUNCTION_VOLTAGE,
+        _ => return Err(unknown()),
+    };
+
+    Ok(Column {
+        channel: attributes.chan_name.clone(),
+        role,
+        source_function: attributes.source_function.value.clone(),
+        source_unit: unit(&attributes.source_function.value).to_string(),
+        measure_function: measure_function.to_string(),
+        unit: unit(measure_function).to_string(),
+        levels,
+    })
+}
+
+/// Returns the source levels of a step or sweep channel, as the instrument computes them.
+fn source_levels(channel: &StartStopChannel, list: bool, points: usize) -> Vec<f64> {
+    if list {
+        let mut levels: Vec<f64> = channel.list.iter().map(|value| value.value).collect();
+        levels.resize(points, f64::NAN);
+        return levels;
+    }
+    let (start, stop) = (channel.start.value, channel.stop.value);
+    let last = points.saturating_sub(1).max(1) as f64;
+    (0..points)
+        .map(|i| {
+            let fraction = i as f64 / last;
+            if channel.style.value == BaseMetadata::STYLE_LOG {
+                start * (stop / start).powf(fraction)
+            } else {
+                start + (stop - start) * fraction
+            }
+        })
+        .collect()
+}
+
+fn unit(function: &str) -> &'static str {
+    if function == BaseMetadata::FUNCTION_VOLTAGE {
+        BaseMetadata::UNIT_VOLTS
+    } else {
+        BaseMetadata::UNIT_AMPERES
+    }
+}
+
+fn to_count(value: i32) -> usize {
+    usize::try_from(value).unwrap_or(0).max(1)
+}
+
+/// Parses a number printed by Lua, including the `nan` and `inf` spellings of the C library.
+fn parse_value(value: &str) -> Option<f64> {
+    match value.to_ascii_lowercase().as_str() {
+        "nan" | "-nan" | "+nan" | "-nan(ind)" | "nil" => Some(f64::NAN),
+        _ => value.parse().ok(),
+    }
+}
+
+/// Quotes `value` if it contains a delimiter, quote or line break.
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod tests {
+    use super::*;
+    use crate::model::sweep_data::sweep_model::SweepModel;
+
+    /// Bias, step and sweep channel sourcing voltage, 10 steps of 10 points from 0 V to 1 V.
+    const MODEL: &str = include_str!("../tests/fixtures/sweep_model/v2.json");
+
+    fn sweep_config(step_points: i32, sweep_points: i32, measure_count: i32) -> SweepConfig {
+        let mut sweep_config = serde_json::from_str::<SweepModel>(MODEL)
+            .unwrap()
+            .sweep_config;
+        sweep_config.step_global_parameters.step_points.value = step_points;
+        sweep_config.sweep_global_parameters.sweep_points.value = sweep_points;
+        sweep_config
+            .global_parameters
+            .sweep_timing_config
+            .measure_count
+            .value = measure_count;
+        sweep_config
+    }
+
+    #[test]
+    fn test_parse() {
+        let output = "\
+            Sweep started\n\
+            Reading Number,bias[1].CURRENT,step[1].CURRENT,sweep[1].CURRENT,sweep[1].VOLTAGE\n\
+            1,1e-09,2e-06,3e-03,0\n\
+            2,1e-09,2e-06,4e-03,nan\n\
+            3,1e-09,2e-06,5e-03,0.5\n\
+            4,1e-09,2e-06,6e-03,0.5\n\
+            5,1e-09,2e-06,7e-03,1\n\
+            6,1e-09,2e-06,8e-03,1\n\
+            7,1e-09,2e-06,9e-03,0\n\
+            8,1e-09,2e-06,1e-02,0\n\
+            9,1e-09,2e-06,2e-02,0.5\n\
+            10,1e-09,2e-06,3e-02,0.5\n\
+            11,1e-09,2e-06,4e-02,1\n\
+            12,1e-09,2e-06,5e-02,1\n\
+            Done\n";
+        let results = SweepResults::parse(output, &sweep_config(2, 3, 2)).unwrap();
+        assert!(results.complete);
+        assert_eq!(results.measurements.len(), 48);
+
+        let values: Vec<&Measurement> = results.get("sweep1", 1, 2).collect();
+        assert_eq!(values.len(), 4);
+        assert_eq!(values[0].measure_function, BaseMetadata::FUNCTION_CURRENT);
+        assert_eq!(values[0].unit, BaseMetadata::UNIT_AMPERES);
+        assert_eq!(values[0].value, 4e-2);
+        assert_eq!(values[0].source_value, 1.0);
+        assert_eq!(values[0].source_unit, BaseMetadata::UNIT_VOLTS);
+        assert_eq!(values[1].measure_function, BaseMetadata::FUNCTION_VOLTAGE);
+        assert_eq!(values[1].unit, BaseMetadata::UNIT_VOLTS);
+        assert_eq!(values[3].sample, 1);
+
+        let step: Vec<&Measurement> = results.get("step1", 1, 0).collect();
+        assert_eq!(step[0].source_value, 1.0);
+        assert!(results.get("sweep1", 0, 0).nth(3).unwrap().value.is_nan());
+    }
+
+    #[test]
+    fn test_partial_results() {
+        let output = "Reading Number,sweep[1].CURRENT\n1,1e-3\n2,2e-3\n";
+        let results = SweepResults::parse(output, &sweep_config(2, 3, 1)).unwrap();
+        assert!(!results.complete);
+        assert_eq!(results.measurements[1].point, 1);
+    }
+
+    #[test]
+    fn test_list_and_log_levels() {
+        let mut sweep_config = sweep_config(1, 3, 1);
+        let channel = &mut sweep_config.sweep_channels[0].start_stop_channel;
+        channel.start.value = 1e-3;
+        channel.stop.value = 1e-1;
+        channel.style.value = BaseMetadata::STYLE_LOG.to_string();
+        let levels = source_levels(channel, false, 3);
+        assert!((levels[1] - 1e-2).abs() < 1e-12);
+
+        channel.list.truncate(2);
+        channel.list[0].value = 0.25;
+        let levels = source_levels(channel, true, 3);
+        assert_eq!(levels[0], 0.25);
+        assert!(levels[2].is_nan());
+    }
+
+    #[test]
+    fn test_csv() {
+        let output = "Reading Number,bias[1].CURRENT\n1,-nan\n";
+        let results = SweepResults::parse(output, &sweep_config(1, 1, 1)).unwrap();
+        assert_eq!(
+            results.to_csv(),
+            "channel,role,step,point,sample,source_function,source_value,source_unit,\
+             measure_function,value,unit\n\
+             bias1,bias,0,0,0,Voltage,0,V,Current,NaN,A\n"
+        );
+        assert!(results.to_json().unwrap().contains("\"value\": null"));
+    }
+
+    #[test]
+    fn test_errors() {
+        let sweep_config = sweep_config(1, 2, 1);
+        assert_eq!(
+            SweepResults::parse("NO DATA\n", &sweep_config),
+            Err(SweepResultsError::NoData)
+        );
+        assert_eq!(
+            SweepResults::parse("Reading Number,sweep[2].CURRENT\n", &sweep_config),
+            Err(SweepResultsError::UnknownColumn {
+                heading: "sweep[2].CURRENT".to_string()
+            })
+        );
+        assert!(matches!(
+            SweepResults::parse("Reading Number,sweep[1].CURRENT\n1,x\n", &sweep_config),
+            Err(SweepResultsError::InvalidRow { row: 1, .. })
+        ));
+        assert_eq!(
+            SweepResults::parse(
+                "Reading Number,sweep[1].CURRENT\n1,1\n2,2\n3,3\n",
+                &sweep_config
+            ),
+            Err(SweepResultsError::TooManyRows {
+                expected: 2,
+                found: 3
+            })
+        );
+    }
+}
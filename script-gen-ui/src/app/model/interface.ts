@@ -140,6 +140,7 @@ export interface ISweepConfig {
 }
 
 export interface ISweepModel {
+  schema_version?: number;
   sweep_config: ISweepConfig;
 }
 
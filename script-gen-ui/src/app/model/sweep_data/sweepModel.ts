@@ -1,15 +1,21 @@
 import { ISweepModel } from '../interface';
 import { SweepConfig } from './sweepConfig';
 
+// layout version of the sweep model, must match SCHEMA_VERSION in migration.rs
+export const SWEEP_MODEL_SCHEMA_VERSION = 2;
+
 export class SweepModel {
+  schema_version: number;
   sweep_config: SweepConfig;
 
   constructor(data: ISweepModel) {
+    this.schema_version = data.schema_version ?? SWEEP_MODEL_SCHEMA_VERSION;
     this.sweep_config = new SweepConfig(data.sweep_config);
   }
 
   toJSON() {
     return {
+      schema_version: this.schema_version,
       sweep_config: this.sweep_config.toJSON(),
     };
   }
@@ -6,6 +6,23 @@ import { StatusType } from './model/interface';
 import { StatusService } from './services/status.service';
 import { vscode } from './app.component';
 
+/** A frame of a chunked message, see the chunking module of the server. */
+interface ChunkFrame {
+  type?: unknown;
+  msg_id: string;
+  chunk_index: number;
+  total_chunks: number;
+  data: string;
+}
+
+/** The chunks of a message received so far. */
+interface PartialMessage {
+  chunks: (string | undefined)[];
+  received: number;
+  len: number;
+  lastUpdate: number;
+}
+
 @Injectable({
   providedIn: 'root',
 })
@@ -13,8 +30,37 @@ export class WebSocketService {
   private socket: WebSocket;
   private messageSubject: Subject<string> = new Subject<string>();
   private readonly CHUNK_SIZE = 30 * 1024; // 30 KB per chunk
+  // limits of incomplete chunked messages, the same as those of the server
+  private static readonly MAX_TOTAL_CHUNKS = 1 << 16;
+  private static readonly MAX_MESSAGE_LEN = 64 * 1024 * 1024;
+  private static readonly MAX_PENDING_LEN = 128 * 1024 * 1024;
+  private static readonly MAX_PENDING_MESSAGES = 8;
+  private static readonly CHUNK_TIMEOUT_MS = 30 * 1000;
+  // chunks of large messages received from the server, keyed by msg_id
+  private chunkBuffers = new Map<string, PartialMessage>();
+  private pendingLen = 0;
   constructor(private statusService: StatusService) {
-    this.socket = new WebSocket('ws://localhost:27950/ws');
+    this.socket = new WebSocket(WebSocketService.socketUrl());
+  }
+
+  /**
+   * Returns the URL of the WebSocket endpoint of the server that serves the page.
+   * The server rewrites the base URL of index.html to its own URL, so this follows
+   * --host, --port and --auto-port, also when the page is shown in a webview.
+   * The server also names the workspace the page was opened for (`/?workspace=<id>`),
+   * pages without one connect to the default workspace.
+   */
+  private static socketUrl(): string {
+    const base = new URL(document.baseURI);
+    const protocol = base.protocol === 'https:' ? 'wss:' : 'ws:';
+    const url = new URL(`${protocol}//${base.host}/ws`);
+    const workspace = document
+      .querySelector('meta[name="kic-script-gen-workspace"]')
+      ?.getAttribute('content');
+    if (workspace) {
+      url.searchParams.set('workspace', workspace);
+    }
+    return url.toString();
   }
 
   connect(): void {
@@ -25,7 +71,10 @@ export class WebSocketService {
 
     this.socket.onmessage = (event) => {
       //console.log('Message received from server:', event.data);
-      this.messageSubject.next(event.data);
+      const message = this.reassemble(event.data);
+      if (message !== undefined) {
+        this.messageSubject.next(message);
+      }
     };
 
     this.socket.onerror = (error) => {
@@ -41,6 +90,98 @@ export class WebSocketService {
    vscode.postMessage({ command: 'get_initial_configuration' });
   }
 
+  /**
+   * Returns the complete message, or undefined while chunks of it are still missing.
+   * The server sends large messages with the same framing as send(). Chunks with invalid
+   * metadata, or that exceed the limits of incomplete messages, are dropped along with
+   * their message.
+   */
+  private reassemble(data: string): string | undefined {
+    let chunk: ChunkFrame;
+    try {
+      chunk = JSON.parse(data);
+    } catch {
+      return data;
+    }
+    if (chunk === null || typeof chunk !== 'object' || chunk.type !== 'chunk') {
+      return data;
+    }
+
+    const now = Date.now();
+    this.evictExpiredChunks(now);
+    const { msg_id, chunk_index, total_chunks } = chunk;
+    if (
+      typeof msg_id !== 'string' ||
+      typeof chunk.data !== 'string' ||
+      !Number.isInteger(total_chunks) ||
+      total_chunks < 1 ||
+      total_chunks > WebSocketService.MAX_TOTAL_CHUNKS ||
+      !Number.isInteger(chunk_index) ||
+      chunk_index < 0 ||
+      chunk_index >= total_chunks
+    ) {
+      console.error('Dropping invalid chunk of message', msg_id);
+      this.discardChunks(msg_id);
+      return undefined;
+    }
+
+    let partial = this.chunkBuffers.get(msg_id);
+    if (!partial) {
+      if (this.chunkBuffers.size >= WebSocketService.MAX_PENDING_MESSAGES) {
+        console.error('Too many incomplete messages, dropping message', msg_id);
+        return undefined;
+      }
+      partial = { chunks: new Array(total_chunks), received: 0, len: 0, lastUpdate: now };
+      this.chunkBuffers.set(msg_id, partial);
+    } else if (partial.chunks.length !== total_chunks) {
+      console.error('Inconsistent chunk count, dropping message', msg_id);
+      this.discardChunks(msg_id);
+      return undefined;
+    }
+
+    // a repeated chunk replaces the earlier copy
+    const replaced = partial.chunks[chunk_index]?.length ?? 0;
+    const len = partial.len - replaced + chunk.data.length;
+    const pendingLen = this.pendingLen - replaced + chunk.data.length;
+    if (len > WebSocketService.MAX_MESSAGE_LEN || pendingLen > WebSocketService.MAX_PENDING_LEN) {
+      console.error('Message exceeds the size limit, dropping message', msg_id);
+      this.discardChunks(msg_id);
+      return undefined;
+    }
+    if (partial.chunks[chunk_index] === undefined) {
+      partial.received++;
+    }
+    partial.chunks[chunk_index] = chunk.data;
+    partial.len = len;
+    partial.lastUpdate = now;
+    this.pendingLen = pendingLen;
+
+    if (partial.received < total_chunks) {
+      return undefined;
+    }
+    this.discardChunks(msg_id);
+    return partial.chunks.join('');
+  }
+
+  private discardChunks(msgId: string): void {
+    const partial = this.chunkBuffers.get(msgId);
+    if (partial) {
+      this.pendingLen -= partial.len;
+      this.chunkBuffers.delete(msgId);
+    }
+  }
+
+  private evictExpiredChunks(now: number): void {
+    for (const [msgId, partial] of this.chunkBuffers) {
+      if (now - partial.lastUpdate > WebSocketService.CHUNK_TIMEOUT_MS) {
+        console.warn(
+          `Dropping incomplete message ${msgId} after receiving ${partial.received} of ${partial.chunks.length} chunks`
+        );
+        this.discardChunks(msgId);
+      }
+    }
+  }
+
   private chunkString(str: string, size: number): string[] {
     const numChunks = Math.ceil(str.length / size);
     const chunks = new Array(numChunks);
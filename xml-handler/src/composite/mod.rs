@@ -1,16 +1,20 @@
 use std::any::Any;
-use std::collections::HashMap;
 
 use quick_xml::events::Event;
 use quick_xml::name::QName;
 use quick_xml::Reader;
-use script_aggregator::script_buffer::ScriptBuffer;
+use script_aggregator::{
+    script_buffer::ScriptBuffer,
+    source_map::{Iteration, SourceElement},
+};
 
 use crate::condition::Condition;
 use crate::error::{Result, XMLHandlerError};
-use crate::group::{parse_include, ExternalFileResult, IncludeResult};
+use crate::group::{include_path, parse_include, ExternalFileResult, IncludeResult};
+use crate::render::Scope;
 use crate::snippet::Snippet;
 use crate::substitute::Substitute;
+use crate::templates::TemplateLoader;
 
 /// Represents the composite tag in the XML data.
 #[derive(Debug, Clone)]
@@ -30,8 +34,10 @@ pub struct Composite {
     pub substitutions: Vec<Substitute>,
     /// A composite can further contain more composites or snippets.
     pub sub_children: Vec<IncludeResult>,
-    /// The parent Composite, if any.
-    pub parent: Option<Box<Composite>>,
+    /// The template the composite is defined in.
+    pub file: String,
+    /// The byte offset of the composite in `file`.
+    pub position: u64,
 }
 
 impl Composite {
@@ -43,6 +49,8 @@ impl Composite {
         conditions: Vec<Condition>,
         substitutions: Vec<Substitute>,
         sub_children: Vec<IncludeResult>,
+        file: &str,
+        position: u64,
     ) -> Self {
         Composite {
             name,
@@ -52,14 +60,20 @@ impl Composite {
             conditions,
             substitutions,
             sub_children,
-            parent: None,
+            file: file.to_string(),
+            position,
         }
     }
 
+    /// Parses a composite element of the template `file`, loading the templates it includes
+    /// with `loader`.
     pub fn parse_composite<R: std::io::BufRead>(
         reader: &mut Reader<R>,
         attributes: quick_xml::events::attributes::Attributes,
+        file: &str,
+        loader: &mut TemplateLoader,
     ) -> Result<Composite> {
+        let position = reader.buffer_position();
         let mut name = String::new();
         let mut type_: Option<String> = None;
         let mut indent = 0;
@@ -96,18 +110,29 @@ impl Composite {
         loop {
             match reader.read_event_into(&mut buf) {
                 Err(e) => {
-                    eprintln!("Error at position {}: {:?}", reader.error_position(), e);
+                    tracing::error!(
+                        position = reader.error_position(),
+                        "XML parse error: {:?}",
+                        e
+                    );
                     return Err(XMLHandlerError::ParseError { source: e });
                 }
                 Ok(Event::Start(e)) if e.name().as_ref() == b"substitute" => {
                     substitutions.push(Substitute::parse_substitute(reader, e.attributes())?);
                 }
                 Ok(Event::Start(e)) if e.name().as_ref() == b"condition" => {
-                    conditions.push(Condition::parse_condition(reader, e.attributes())?);
+                    conditions.push(Condition::parse_condition(reader, e.attributes(), file)?);
+                }
+                Ok(Event::Empty(e)) if e.name().as_ref() == b"condition" => {
+                    conditions.push(Condition::parse_condition_attr_only(
+                        reader,
+                        e.attributes(),
+                        file,
+                    )?);
                 }
                 Ok(Event::Empty(e)) if e.name().as_ref() == b"include" => {
-                    let res = parse_include(e.attributes())?;
-                    match res {
+                    let path = include_path(e.attributes())?;
+                    match parse_include(&path, loader)? {
                         ExternalFileResult::Snippet(snippet) => {
                             sub_children.push(IncludeResult::Snippet(snippet));
                         }
@@ -115,18 +140,30 @@ impl Composite {
                             sub_children.push(IncludeResult::Composite(composite));
                         }
                         ExternalFileResult::Variables(_) => {
-                            todo!();
+                            return Err(XMLHandlerError::UnsupportedInclude {
+                                file: file.to_string(),
+                                element: format!("<composite name=\"{name}\">"),
+                                position: reader.buffer_position(),
+                                include: path,
+                            });
                         }
                     }
                 }
                 Ok(Event::Start(e)) if e.name().as_ref() == b"snippet" => {
-                    let res = Snippet::parse_snippet(reader, e.attributes())?;
+                    let res = Snippet::parse_snippet(reader, e.attributes(), file)?;
                     sub_children.push(IncludeResult::Snippet(res));
                 }
                 Ok(Event::Start(e)) if e.name().as_ref() == b"composite" => {
-                    let res = Self::parse_composite(reader, e.attributes())?;
+                    let res = Self::parse_composite(reader, e.attributes(), file, loader)?;
                     sub_children.push(IncludeResult::Composite(res));
                 }
+                Ok(Event::Eof) => {
+                    return Err(XMLHandlerError::UnexpectedEof {
+                        file: file.to_string(),
+                        element: format!("<composite name=\"{name}\">"),
+                        position,
+                    });
+                }
                 Ok(Event::End(e)) if e.name().as_ref() == b"composite" => {
                     return Ok(Composite::new(
                         name,
@@ -136,6 +173,8 @@ impl Composite {
                         conditions,
                         substitutions,
                         sub_children,
+                        file,
+                        position,
                     ));
                 }
 
@@ -144,29 +183,19 @@ impl Composite {
         }
     }
 
-    // pub fn to_script(
-    //     &self,
-    //     script_buffer: &mut ScriptBuffer,
-    //     val_replacement_map: &HashMap<String, String>,
-    // ) {
-    //     if self.evaluate_conditions(val_replacement_map) {
-    //         if self.indent > 0 {
-    //             script_buffer.change_indent(self.indent);
-    //         }
-    //         if self.repeat.is_empty() {
-    //             for res in self.sub_children.iter() {
-    //                 if let IncludeResult::Snippet(snippet) = res {
-    //                     snippet.evaluate(script_buffer, val_replacement_map);
-    //                 }
-    //             }
-    //         } else {
-    //             todo!();
-    //         }
-    //         if self.indent > 0 {
-    //             script_buffer.change_indent(-self.indent);
-    //         }
-    //     }
-    // }
+    /// Compiles the snippets of the composite with the substitutes of the composite and of
+    /// its `ancestors`, innermost first.
+    pub(crate) fn compile(&mut self, ancestors: &[&[Substitute]]) {
+        let chain: Vec<&[Substitute]> = std::iter::once(self.substitutions.as_slice())
+            .chain(ancestors.iter().copied())
+            .collect();
+        for child in &mut self.sub_children {
+            match child {
+                IncludeResult::Snippet(snippet) => snippet.compile(&chain),
+                IncludeResult::Composite(composite) => composite.compile(&chain),
+            }
+        }
+    }
 }
 
 pub trait CommonChunk {
@@ -174,184 +203,78 @@ pub trait CommonChunk {
     fn get_repeat(&self) -> &str;
     fn get_indent(&self) -> i32;
     fn get_conditions(&self) -> &Vec<Condition>;
-    fn evaluate(
-        &mut self,
-        script_buffer: &mut ScriptBuffer,
-        val_replacement_map: &HashMap<String, String>,
-    );
+    fn get_source(&self) -> SourceElement;
+    fn evaluate(&self, script_buffer: &mut ScriptBuffer, scope: &Scope) -> Result<()>;
 
     /// The composite/snippet instances are processed and the result is appended to the script buffer.
     ///
+    /// The appended lines are recorded as generated from the composite/snippet, and from the
+    /// iteration of its repeat.
+    ///
     /// # Arguments
     ///
     /// * `script_buffer` - A mutable reference to the script buffer.
-    /// * `val_replacement_map` - A reference to the value replacement map.
-    fn to_script(
-        &mut self,
-        script_buffer: &mut ScriptBuffer,
-        val_replacement_map: &HashMap<String, String>,
-    ) {
+    /// * `scope` - The values of the value replacement map and the enclosing repeats.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `XMLHandlerError` if a condition cannot be evaluated.
+    fn to_script(&self, script_buffer: &mut ScriptBuffer, scope: &Scope) -> Result<()> {
         // determines whether the composite/snippet should be included in the script
-        if self.evaluate_conditions(val_replacement_map) {
+        if self.evaluate_conditions(scope)? {
             if self.get_indent() > 0 {
                 script_buffer.change_indent(self.get_indent());
             }
+            script_buffer.enter_source(self.get_source());
 
-            if self.get_repeat().is_empty() {
-                self.evaluate(script_buffer, val_replacement_map)
-            } else {
-                let repeat_val = self.get_repeat();
-                let active = repeat_val.to_owned() + ":";
-                //TODO: Commenting below line for now, need to discuss if it is required
-                //let obsolete = repeat_val.to_owned() + ".value"; // for backward compatibility
-
-                let mut loop_count = 1;
-                let loop_count_name = repeat_val.to_owned() + ".LOOP-COUNT";
-                let list_arr = val_replacement_map.get(repeat_val);
-                if let Some(list_arr) = list_arr {
-                    let list = list_arr.split(',').collect::<Vec<&str>>();
-                    for val in list {
-                        let mut val_replacement_map1 = val_replacement_map.clone();
-                        val_replacement_map1.insert(active.clone(), val.to_string());
-                        //val_replacement_map.insert(obsolete.clone(), val.to_string());
-                        val_replacement_map1
-                            .insert(loop_count_name.clone(), loop_count.to_string());
-                        self.evaluate(script_buffer, &val_replacement_map1);
-                        loop_count += 1;
-                    }
+            let repeat = self.get_repeat();
+            if repeat.is_empty() {
+                self.evaluate(script_buffer, scope)?;
+            } else if let Some(list) = scope.get(repeat) {
+                //TODO: `<repeat>.value` was defined as the current item for backward
+                //compatibility, need to discuss if it is required
+                for (index, item) in list.split(',').enumerate() {
+                    script_buffer.set_iteration(Some(Iteration {
+                        repeat: repeat.to_string(),
+                        item: item.to_string(),
+                        loop_count: index + 1,
+                    }));
+                    self.evaluate(script_buffer, &scope.item(repeat, item, index + 1))?;
                 }
             }
 
+            script_buffer.leave_source();
             if self.get_indent() > 0 {
                 script_buffer.change_indent(-self.get_indent());
             }
         }
+        Ok(())
     }
 
     /// Evaluates the conditions (if any) associated with the composite/snippet.
     ///
     /// This method checks if the conditions specified in the composite/snippet
-    /// are met based on the values in the value replacement map.
+    /// are met based on the values of `scope`.
     ///
     /// # Arguments
     ///
-    /// * `val_replacement_map` - A reference to the value replacement map.
+    /// * `scope` - The values of the value replacement map and the enclosing repeats.
     ///
     /// # Returns
     ///
     /// A boolean indicating whether the conditions are met.
-    fn evaluate_conditions(&self, val_replacement_map: &HashMap<String, String>) -> bool {
-        let mut include = true;
-        let conditions = self.get_conditions();
-        for condition in conditions {
-            let op = &condition.op;
-            let object = self.lookup(val_replacement_map, &condition.name);
-            if object.is_empty() {
-                //TODO: handle error
-            }
-            if "ne" == op {
-                if condition.value == object {
-                    include = false;
-                    break;
-                }
-            } else if "gt" == op {
-                let val1 = object.parse::<f64>().unwrap();
-                let val2 = condition.value.parse::<f64>().unwrap();
-                if val1 <= val2 {
-                    include = false;
-                    break;
-                }
-            } else if "ge" == op {
-                let val1 = object.parse::<f64>().unwrap();
-                let val2 = condition.value.parse::<f64>().unwrap();
-                if val1 < val2 {
-                    include = false;
-                    break;
-                }
-            } else if "lt" == op {
-                let val1 = object.parse::<f64>().unwrap();
-                let val2 = condition.value.parse::<f64>().unwrap();
-                if val1 >= val2 {
-                    include = false;
-                    break;
-                }
-            } else if "le" == op {
-                let val1 = object.parse::<f64>().unwrap();
-                let val2 = condition.value.parse::<f64>().unwrap();
-                if val1 > val2 {
-                    include = false;
-                    break;
-                }
-            } else if "in" == op {
-                // object must be in expression
-                let expression = &condition.value;
-                let token = object;
-                let index = expression.find(&token);
-                if let Some(index) = index {
-                    // verify complete token match vs. partial token match
-                    if (index > 0 && expression.chars().nth(index - 1) != Some(','))
-                        || (index + token.len() < expression.len()
-                            && expression.chars().nth(index + token.len()) != Some(','))
-                    {
-                        include = false;
-                        break;
-                    }
-                } else {
-                    include = false;
-                    break;
-                }
-            } else if "regex" == op {
-                //TODO: handle regex
-            } else {
-                // must be "e1" (==)
-                if condition.value != object {
-                    include = false;
-                    break;
-                }
-            }
-        }
-        include
-    }
-
-    /// Looks up a value in the value replacement map based on the given symbol.
-    ///
-    /// e.g., The symbol "DEVICES:ASSIGN" has scope "DEVICES" and the value replacement map
-    /// value of "DEVICES:" is extracted before doing the lookup - so if DEVICES: currently
-    /// has the value "bias1" then the lookup becomes "bias1:ASSIGN".
     ///
-    /// # Arguments
-    ///
-    /// * `val_replacement_map` - A reference to the value replacement map.
-    /// * `symbol` - The symbol to look up.
+    /// # Errors
     ///
-    /// # Returns
-    ///
-    /// A string representing the looked-up value.
-    fn lookup(&self, val_replacement_map: &HashMap<String, String>, symbol: &str) -> String {
-        let index = symbol.find(':');
-        let mut temp = "".to_string();
-
-        if index.is_none() || (index.unwrap() + 1) == symbol.len() {
-            temp = symbol.to_string();
-        } else {
-            let index = index.unwrap();
-            let scope = &symbol[..index];
-
-            if let Some(val_arr) = val_replacement_map.get(&(scope.to_string() + ":")) {
-                temp = val_arr.to_string();
-                temp += &symbol[index..];
-            } else {
-                //TODO: handle error
-            }
-        }
-
-        match val_replacement_map.get(&temp) {
-            Some(val) => val.clone(),
-            None => {
-                //handle error
-                "".to_string()
+    /// Returns an `XMLHandlerError` if a condition cannot be evaluated.
+    fn evaluate_conditions(&self, scope: &Scope) -> Result<bool> {
+        let lookup = |symbol: &str| scope.lookup(symbol);
+        for condition in self.get_conditions() {
+            if !condition.is_met(&lookup)? {
+                return Ok(false);
             }
         }
+        Ok(true)
     }
 }
 
@@ -372,24 +295,17 @@ impl CommonChunk for Composite {
         &self.conditions
     }
 
-    fn evaluate(
-        &mut self,
-        script_buffer: &mut ScriptBuffer,
-        val_replacement_map: &HashMap<String, String>,
-    ) {
-        let parent_clone = self.clone();
-        for res in self.sub_children.iter_mut() {
-            match res {
-                IncludeResult::Snippet(snippet) => {
-                    snippet.parent = Some(Box::new(parent_clone.clone()));
-                    snippet.to_script(script_buffer, val_replacement_map)
-                }
+    fn get_source(&self) -> SourceElement {
+        SourceElement::new(&self.file, "composite", &self.name, self.position)
+    }
 
-                IncludeResult::Composite(composite) => {
-                    composite.parent = Some(Box::new(parent_clone.clone()));
-                    composite.to_script(script_buffer, val_replacement_map)
-                }
+    fn evaluate(&self, script_buffer: &mut ScriptBuffer, scope: &Scope) -> Result<()> {
+        for res in &self.sub_children {
+            match res {
+                IncludeResult::Snippet(snippet) => snippet.to_script(script_buffer, scope)?,
+                IncludeResult::Composite(composite) => composite.to_script(script_buffer, scope)?,
             }
         }
+        Ok(())
     }
 }
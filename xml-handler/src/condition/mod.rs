@@ -1,7 +1,30 @@
 use crate::error::{Result, XMLHandlerError};
 use quick_xml::{events::Event, name::QName, Reader};
+use regex::Regex;
 
 /// Represents the condition tag in the XML data.
+///
+/// A condition compares the value of the variable `name` with its text using `op`:
+///
+/// * `eq` (default), `ne` - equal, not equal
+/// * `gt`, `ge`, `lt`, `le` - numeric comparisons
+/// * `in` - the value is an item of the comma separated text
+/// * `contains` - the comma separated value has the text as an item
+/// * `regex` - the value matches the regular expression of the text as a whole
+///
+/// With a `var` attribute the value of that variable is used instead of the text, e.g.
+/// `<condition name="DEVICE:MODEL" op="in" var="FOUR-WIRE-MODELS"/>`.
+///
+/// The operators `and`, `or` and `not` combine the nested conditions:
+///
+/// ```xml
+/// <condition op="or">
+///     <condition name="SWEEP-DEVICE:SENSE" op="regex">FOUR.*</condition>
+///     <condition op="not">
+///         <condition name="STEP-CHANNEL-COUNT">0</condition>
+///     </condition>
+/// </condition>
+/// ```
 #[derive(Debug, Clone)]
 pub struct Condition {
     /// The name of the condition.
@@ -10,51 +33,365 @@ pub struct Condition {
     pub op: String,
     /// The value of the condition.
     pub value: String,
+    /// The variable whose value is compared instead of `value`, if any.
+    pub var: Option<String>,
+    /// The nested conditions of the `and`, `or` and `not` operators.
+    pub conditions: Vec<Condition>,
+    /// The template the condition is defined in.
+    pub file: String,
+    /// The byte offset of the condition in `file`.
+    pub position: u64,
+    /// The compiled `value` of a `regex` condition.
+    regex: Option<Regex>,
 }
 
 impl Condition {
-    fn new(name: String, op: String, value: String) -> Self {
-        Condition {
+    fn new(
+        name: String,
+        op: String,
+        value: String,
+        var: Option<String>,
+        conditions: Vec<Condition>,
+        file: &str,
+        position: u64,
+    ) -> Result<Self> {
+        let regex = if op == "regex" && var.is_none() {
+            Some(compile(&value, file, &name, position)?)
+        } else {
+            None
+        };
+        Ok(Condition {
             name,
             op: if op.is_empty() { "eq".to_string() } else { op },
             value,
-        }
+            var,
+            conditions,
+            file: file.to_string(),
+            position,
+            regex,
+        })
     }
 
+    /// Parses a condition element of the template `file`.
     pub fn parse_condition<R: std::io::BufRead>(
         reader: &mut Reader<R>,
         attributes: quick_xml::events::attributes::Attributes,
+        file: &str,
     ) -> Result<Condition> {
-        let mut name = String::new();
-        let mut op = String::new();
+        let position = reader.buffer_position();
+        let (name, op, var) = Self::parse_attributes(attributes)?;
         let mut value = String::new();
+        let mut conditions: Vec<Condition> = Vec::new();
 
         let mut buf: Vec<u8> = Vec::new();
 
+        loop {
+            match reader.read_event_into(&mut buf) {
+                Err(e) => {
+                    tracing::error!(
+                        position = reader.error_position(),
+                        "XML parse error: {:?}",
+                        e
+                    );
+                    return Err(XMLHandlerError::ParseError { source: e });
+                }
+                Ok(Event::Text(e)) => match e.unescape() {
+                    Ok(text) => value.push_str(&text),
+                    Err(e) => {
+                        tracing::error!("Error reading condition value: {:?}", e);
+                        return Err(XMLHandlerError::ParseError { source: e });
+                    }
+                },
+                Ok(Event::Start(e)) if e.name().as_ref() == b"condition" => {
+                    conditions.push(Self::parse_condition(reader, e.attributes(), file)?);
+                }
+                Ok(Event::Empty(e)) if e.name().as_ref() == b"condition" => {
+                    conditions.push(Self::parse_condition_attr_only(
+                        reader,
+                        e.attributes(),
+                        file,
+                    )?);
+                }
+                Ok(Event::End(e)) if e.name().as_ref() == b"condition" => break,
+                Ok(Event::Eof) => {
+                    return Err(XMLHandlerError::UnexpectedEof {
+                        file: file.to_string(),
+                        element: format!("<condition name=\"{name}\">"),
+                        position,
+                    });
+                }
+                _ => (),
+            }
+        }
+
+        Condition::new(name, op, value, var, conditions, file, position)
+    }
+
+    /// Parses an empty condition element of the template `file`, which compares with another
+    /// variable, e.g. `<condition name="X" op="in" var="Y"/>`.
+    pub fn parse_condition_attr_only<R>(
+        reader: &Reader<R>,
+        attributes: quick_xml::events::attributes::Attributes,
+        file: &str,
+    ) -> Result<Condition> {
+        let (name, op, var) = Self::parse_attributes(attributes)?;
+        Condition::new(
+            name,
+            op,
+            String::new(),
+            var,
+            Vec::new(),
+            file,
+            reader.buffer_position(),
+        )
+    }
+
+    fn parse_attributes(
+        attributes: quick_xml::events::attributes::Attributes,
+    ) -> Result<(String, String, Option<String>)> {
+        let mut name = String::new();
+        let mut op = String::new();
+        let mut var = None;
+
         for attr in attributes {
             let attr = attr?;
             match attr.key {
                 QName(b"name") => name = String::from_utf8_lossy(attr.value.as_ref()).to_string(),
                 QName(b"op") => op = String::from_utf8_lossy(attr.value.as_ref()).to_string(),
+                QName(b"var") => {
+                    var = Some(String::from_utf8_lossy(attr.value.as_ref()).to_string())
+                }
                 _ => {}
             }
         }
+        Ok((name, op, var))
+    }
 
-        match reader.read_event_into(&mut buf) {
-            Err(e) => {
-                eprintln!("Error at position {}: {:?}", reader.error_position(), e);
-                return Err(XMLHandlerError::ParseError { source: e });
-            }
-            Ok(Event::Text(e)) => match e.unescape() {
-                Ok(text) => value = text.to_string(),
-                Err(e) => {
-                    eprintln!("Error reading condition value: {:?}", e);
-                    return Err(XMLHandlerError::ParseError { source: e });
+    /// Returns whether the condition is met, looking up the values of variables with `lookup`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`XMLHandlerError::NotANumber`] if a numeric comparison has a value that is not
+    /// a number and [`XMLHandlerError::RegexError`] if the variable of a `regex` condition is
+    /// not a valid regular expression.
+    pub fn is_met<F: Fn(&str) -> String>(&self, lookup: &F) -> Result<bool> {
+        match self.op.as_str() {
+            "and" => return self.all_met(lookup),
+            "or" => {
+                for condition in &self.conditions {
+                    if condition.is_met(lookup)? {
+                        return Ok(true);
+                    }
                 }
+                return Ok(false);
+            }
+            "not" => return Ok(!self.all_met(lookup)?),
+            _ => {}
+        }
+
+        let object = lookup(&self.name);
+        let value = match &self.var {
+            Some(var) => lookup(var),
+            None => self.value.clone(),
+        };
+        let met = match self.op.as_str() {
+            "ne" => value != object,
+            "gt" => self.number(&object)? > self.number(&value)?,
+            "ge" => self.number(&object)? >= self.number(&value)?,
+            "lt" => self.number(&object)? < self.number(&value)?,
+            "le" => self.number(&object)? <= self.number(&value)?,
+            // object must be an item of the expression
+            "in" => is_item(&value, &object),
+            "contains" => is_item(&object, &value),
+            "regex" => match &self.regex {
+                Some(regex) => regex.is_match(&object),
+                None => compile(&value, &self.file, &self.name, self.position)?.is_match(&object),
             },
-            _ => (),
+            // must be "eq" (==)
+            _ => value == object,
+        };
+        Ok(met)
+    }
+
+    fn all_met<F: Fn(&str) -> String>(&self, lookup: &F) -> Result<bool> {
+        for condition in &self.conditions {
+            if !condition.is_met(lookup)? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    fn number(&self, value: &str) -> Result<f64> {
+        value.parse().map_err(|_| XMLHandlerError::NotANumber {
+            file: self.file.clone(),
+            element: format!("<condition name=\"{}\" op=\"{}\">", self.name, self.op),
+            position: self.position,
+            value: value.to_string(),
+        })
+    }
+}
+
+/// Returns whether `token` is an item of the comma separated `list`.
+fn is_item(list: &str, token: &str) -> bool {
+    list.split(',').any(|item| item == token)
+}
+
+/// Compiles `pattern` of the condition `name` so that it must match a value as a whole.
+fn compile(pattern: &str, file: &str, name: &str, position: u64) -> Result<Regex> {
+    Regex::new(&format!("^(?:{pattern})$")).map_err(|source| XMLHandlerError::RegexError {
+        file: file.to_string(),
+        element: format!("<condition name=\"{name}\" op=\"regex\">"),
+        position,
+        pattern: pattern.to_string(),
+        source,
+    })
+}
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    fn parse(xml: &str) -> Result<Condition> {
+        let mut reader = Reader::from_str(xml);
+        let mut buf = Vec::new();
+        loop {
+            match reader.read_event_into(&mut buf) {
+                Ok(Event::Start(e)) => {
+                    return Condition::parse_condition(&mut reader, e.attributes(), "test.xml")
+                }
+                Ok(Event::Empty(e)) => {
+                    return Condition::parse_condition_attr_only(
+                        &reader,
+                        e.attributes(),
+                        "test.xml",
+                    )
+                }
+                Ok(Event::Eof) => panic!("no condition in {xml}"),
+                Err(e) => return Err(XMLHandlerError::ParseError { source: e }),
+                _ => {}
+            }
         }
+    }
+
+    fn try_is_met(xml: &str) -> Result<bool> {
+        let values = HashMap::from([
+            ("MODEL", "MSMU60-2"),
+            ("SENSE", "FOUR_WIRE"),
+            ("COUNT", "2"),
+            ("SMU-MODELS", "MSMU60-2,MPSU50-2ST"),
+            ("SENSE-MODES", "TWO_WIRE,FOUR_WIRE"),
+            ("FOUR-WIRE", "FOUR.*"),
+            ("GROUP", "("),
+        ]);
+        let lookup = |name: &str| values.get(name).copied().unwrap_or_default().to_string();
+        parse(xml)?.is_met(&lookup)
+    }
+
+    fn is_met(xml: &str) -> bool {
+        try_is_met(xml).unwrap()
+    }
+
+    #[test]
+    fn test_simple_operators() {
+        assert!(is_met(r#"<condition name="MODEL">MSMU60-2</condition>"#));
+        assert!(!is_met(
+            r#"<condition name="MODEL" op="ne">MSMU60-2</condition>"#
+        ));
+        assert!(is_met(r#"<condition name="COUNT" op="gt">1</condition>"#));
+        assert!(is_met(r#"<condition name="COUNT" op="le">2</condition>"#));
+        assert!(is_met(
+            r#"<condition name="MODEL" op="in">MPSU50-2ST,MSMU60-2</condition>"#
+        ));
+        assert!(!is_met(
+            r#"<condition name="MODEL" op="in">MSMU60-22,SMU60-2</condition>"#
+        ));
+    }
+
+    #[test]
+    fn test_not_a_number() {
+        let error = try_is_met(r#"<condition name="MODEL" op="gt">1</condition>"#).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            r#"test.xml (position 32): <condition name="MODEL" op="gt"> expects a number, found 'MSMU60-2'"#
+        );
+        // the other operand is only parsed once the condition is evaluated
+        assert!(!is_met(
+            r#"<condition op="and">
+                <condition name="MODEL">MPSU50-2ST</condition>
+                <condition name="MODEL" op="lt">1</condition>
+            </condition>"#
+        ));
+    }
+
+    #[test]
+    fn test_regex() {
+        assert!(is_met(
+            r#"<condition name="MODEL" op="regex">MSMU\d+-2</condition>"#
+        ));
+        // must match the whole value
+        assert!(!is_met(
+            r#"<condition name="MODEL" op="regex">SMU</condition>"#
+        ));
+        assert!(matches!(
+            parse(r#"<condition name="MODEL" op="regex">(</condition>"#),
+            Err(XMLHandlerError::RegexError { .. })
+        ));
+    }
+
+    #[test]
+    fn test_regex_of_variable() {
+        assert!(is_met(
+            r#"<condition name="SENSE" op="regex" var="FOUR-WIRE"/>"#
+        ));
+        assert!(!is_met(
+            r#"<condition name="MODEL" op="regex" var="FOUR-WIRE"/>"#
+        ));
+        // the variable is only compiled once the condition is evaluated
+        let error = try_is_met(r#"<condition name="MODEL" op="regex" var="GROUP"/>"#).unwrap_err();
+        assert!(matches!(error, XMLHandlerError::RegexError { .. }));
+        assert!(error.to_string().starts_with(
+            r#"test.xml (position 48): <condition name="MODEL" op="regex"> has an invalid regular expression '(': "#
+        ));
+    }
+
+    #[test]
+    fn test_membership_of_variable() {
+        assert!(is_met(
+            r#"<condition name="MODEL" op="in" var="SMU-MODELS"/>"#
+        ));
+        assert!(is_met(
+            r#"<condition name="SENSE-MODES" op="contains">FOUR_WIRE</condition>"#
+        ));
+        assert!(!is_met(
+            r#"<condition name="SMU-MODELS" op="contains" var="SENSE"/>"#
+        ));
+    }
 
-        Ok(Condition::new(name, op, value))
+    #[test]
+    fn test_compound_conditions() {
+        assert!(is_met(
+            r#"<condition op="or">
+                <condition name="MODEL">MPSU50-2ST</condition>
+                <condition name="SENSE" op="regex">FOUR.*</condition>
+            </condition>"#
+        ));
+        assert!(!is_met(
+            r#"<condition op="not">
+                <condition name="MODEL" op="in" var="SMU-MODELS"/>
+                <condition name="COUNT">2</condition>
+            </condition>"#
+        ));
+        assert!(is_met(
+            r#"<condition op="and">
+                <condition name="COUNT" op="ge">2</condition>
+                <condition op="not">
+                    <condition name="SENSE">TWO_WIRE</condition>
+                </condition>
+            </condition>"#
+        ));
     }
 }
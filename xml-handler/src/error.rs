@@ -1,3 +1,5 @@
+use std::path::PathBuf;
+
 use quick_xml::events::attributes;
 use thiserror::Error;
 
@@ -28,6 +30,85 @@ pub enum XMLHandlerError {
         source: quick_xml::Error,
     },
 
+    /// A template file of the template directory could not be read
+    #[error("Failed to read template {}: {source}", path.display())]
+    TemplateReadError {
+        /// The template file
+        path: PathBuf,
+        /// The original `[std::io::Error]`
+        source: std::io::Error,
+    },
+
+    /// A template ended before an element was closed
+    #[error("{file} (position {position}): unexpected end of template in {element}")]
+    UnexpectedEof {
+        /// The template that ended
+        file: String,
+        /// The unclosed element
+        element: String,
+        /// The byte offset of the unclosed element in `file`
+        position: u64,
+    },
+
+    /// A `regex` condition has an invalid regular expression
+    #[error("{file} (position {position}): {element} has an invalid regular expression '{pattern}': {source}")]
+    RegexError {
+        /// The template with the condition
+        file: String,
+        /// The condition
+        element: String,
+        /// The byte offset of the condition in `file`
+        position: u64,
+        /// The regular expression
+        pattern: String,
+        /// The original error
+        source: regex::Error,
+    },
+
+    /// A template includes a template that cannot be used at that place
+    #[error("{file} (position {position}): {element} cannot include {include}")]
+    UnsupportedInclude {
+        /// The template with the include
+        file: String,
+        /// The element with the include
+        element: String,
+        /// The byte offset of the include in `file`
+        position: u64,
+        /// The included template
+        include: String,
+    },
+
+    /// A template element has a value that is not a number
+    #[error("{file} (position {position}): {element} expects a number, found '{value}'")]
+    NotANumber {
+        /// The template with the element
+        file: String,
+        /// The element
+        element: String,
+        /// The byte offset of the element in `file`
+        position: u64,
+        /// The value
+        value: String,
+    },
+
+    /// Template variables depend on each other
+    #[error("Template variables depend on each other: {}", cycle.join(" -> "))]
+    DependencyCycle {
+        /// The ids of the variables of the cycle, starting and ending with the same variable
+        cycle: Vec<String>,
+    },
+
+    /// A constraint of a template variable refers to a value that is not a number
+    #[error("Constraint of template variable {variable} refers to {reference}, expected a number, found '{value}'")]
+    InvalidBound {
+        /// The id of the constrained variable
+        variable: String,
+        /// The id of the referenced variable or value
+        reference: String,
+        /// The referenced value
+        value: String,
+    },
+
     /// When the xml file supplied is not recognized
     #[error("Unknown XML file error: {file_name}")]
     UnknownXMLFileError {
@@ -3,10 +3,16 @@ use quick_xml::reader::Reader;
 
 use crate::error::{Result, XMLHandlerError};
 use crate::group::Group;
-use crate::resources::SWEEP_FUNC_METADATA;
+use crate::templates::TemplateLoader;
+
+/// Include path of the template that all other templates are included from.
+pub const ROOT_TEMPLATE: &str = "SWEEP_FUNC_METADATA";
 
 /// Parses the XML data from the SweepFunctionMetaData.xml file and returns a vector of `Group` objects.
 ///
+/// The file and the templates it includes are loaded with `loader`, which records where each
+/// of them came from.
+///
 /// # Errors
 ///
 /// This function will return an `XMLHandlerError` if there is an error while reading
@@ -18,8 +24,8 @@ use crate::resources::SWEEP_FUNC_METADATA;
 /// A `Result` which is:
 /// - `Ok(Vec<Group>)` containing a vector of `Group` objects if parsing is successful.
 /// - `Err(XMLHandlerError)` if there is an error during parsing.
-pub fn parse_xml() -> Result<Vec<Group>> {
-    let binding = SWEEP_FUNC_METADATA.to_string();
+pub fn parse_xml(loader: &mut TemplateLoader) -> Result<Vec<Group>> {
+    let binding = loader.load(ROOT_TEMPLATE)?;
     let mut reader = Reader::from_str(binding.as_str());
     //reader.config_mut().trim_text(true);
 
@@ -29,13 +35,18 @@ pub fn parse_xml() -> Result<Vec<Group>> {
     loop {
         match reader.read_event_into(&mut buf) {
             Err(e) => {
-                eprintln!("Error at position {}: {:?}", reader.error_position(), e);
+                tracing::error!(
+                    position = reader.error_position(),
+                    "XML parse error: {:?}",
+                    e
+                );
                 return Err(XMLHandlerError::ParseError { source: e });
             }
             Ok(Event::Eof) => break,
             Ok(Event::Start(e)) => match e.name().as_ref() {
                 b"group" => {
-                    let group = Group::parse_group(&mut reader, e.attributes())?;
+                    let group =
+                        Group::parse_group(&mut reader, e.attributes(), ROOT_TEMPLATE, loader)?;
                     groups.push(group);
                 }
                 _ => (),
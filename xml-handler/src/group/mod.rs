@@ -1,10 +1,10 @@
 use quick_xml::{events::Event, name::QName, Reader};
 
-use crate::resources::Resource;
 use crate::{
     composite::Composite,
     error::{Result, XMLHandlerError},
     snippet::Snippet,
+    templates::TemplateLoader,
     variable::{Variable, Variables},
 };
 
@@ -36,12 +36,24 @@ impl Group {
         }
     }
 
+    /// Compiles the snippets of the group, once all substitutes of their ancestors are known.
+    fn compile(&mut self) {
+        for child in &mut self.children {
+            match child {
+                IncludeResult::Snippet(snippet) => snippet.compile(&[]),
+                IncludeResult::Composite(composite) => composite.compile(&[]),
+            }
+        }
+    }
+
     /// Parses a `Group` from the XML reader and its attributes.
     ///
     /// # Arguments
     ///
     /// * `reader` - A mutable reference to a `Reader` that reads the XML data.
     /// * `attributes` - The attributes of the XML element being parsed.
+    /// * `file` - The template the group is defined in.
+    /// * `loader` - Loads the templates of `<include>` elements.
     ///
     /// # Errors
     ///
@@ -56,7 +68,10 @@ impl Group {
     pub fn parse_group<R: std::io::BufRead>(
         reader: &mut Reader<R>,
         attributes: quick_xml::events::attributes::Attributes,
+        file: &str,
+        loader: &mut TemplateLoader,
     ) -> Result<Group> {
+        let position = reader.buffer_position();
         let mut id = String::new();
         let mut type_ = String::new();
 
@@ -77,30 +92,45 @@ impl Group {
         loop {
             match reader.read_event_into(&mut buf) {
                 Err(e) => {
-                    eprintln!("Error at position {}: {:?}", reader.error_position(), e);
+                    tracing::error!(
+                        position = reader.error_position(),
+                        "XML parse error: {:?}",
+                        e
+                    );
                     return Err(XMLHandlerError::ParseError { source: e });
                 }
                 Ok(Event::Start(e)) if e.name().as_ref() == b"composite" => {
-                    let res = Composite::parse_composite(reader, e.attributes())?;
+                    let res = Composite::parse_composite(reader, e.attributes(), file, loader)?;
                     children.push(IncludeResult::Composite(res));
                 }
                 Ok(Event::Empty(e)) if e.name().as_ref() == b"include" => {
-                    let res = parse_include(e.attributes());
-                    match res {
-                        Ok(ExternalFileResult::Snippet(snippet)) => {
-                            todo!();
+                    let path = include_path(e.attributes())?;
+                    match parse_include(&path, loader)? {
+                        ExternalFileResult::Snippet(_) => {
+                            return Err(XMLHandlerError::UnsupportedInclude {
+                                file: file.to_string(),
+                                element: format!("<group id=\"{id}\">"),
+                                position: reader.buffer_position(),
+                                include: path,
+                            });
                         }
-                        Ok(ExternalFileResult::Composite(composite)) => {
+                        ExternalFileResult::Composite(composite) => {
                             children.push(IncludeResult::Composite(composite));
                         }
-                        Ok(ExternalFileResult::Variables(vars)) => {
-                            variable_list = vars.variable_array
-                        }
-                        _ => {}
+                        ExternalFileResult::Variables(vars) => variable_list = vars.variable_array,
                     }
                 }
                 Ok(Event::End(e)) if e.name().as_ref() == b"group" => {
-                    return Ok(Group::new(id, type_, children, variable_list));
+                    let mut group = Group::new(id, type_, children, variable_list);
+                    group.compile();
+                    return Ok(group);
+                }
+                Ok(Event::Eof) => {
+                    return Err(XMLHandlerError::UnexpectedEof {
+                        file: file.to_string(),
+                        element: format!("<group id=\"{id}\">"),
+                        position,
+                    });
                 }
 
                 _ => {}
@@ -109,11 +139,28 @@ impl Group {
     }
 }
 
-/// Parses an `ExternalFileResult` from the provided XML attributes.
+/// Returns the `path` attribute of an `<include>` element.
+///
+/// # Errors
+///
+/// Returns an `XMLHandlerError` if an attribute cannot be parsed.
+pub fn include_path(attributes: quick_xml::events::attributes::Attributes) -> Result<String> {
+    let mut path = String::new();
+    for attr in attributes {
+        let attr = attr?;
+        if let QName(b"path") = attr.key {
+            path = String::from_utf8_lossy(attr.value.as_ref()).to_string();
+        }
+    }
+    Ok(path)
+}
+
+/// Parses an `ExternalFileResult` from the included template.
 ///
 /// # Arguments
 ///
-/// * `attributes` - The attributes of the XML element being parsed.
+/// * `path` - The include path of the template.
+/// * `loader` - Loads the included template.
 ///
 /// # Errors
 ///
@@ -126,53 +173,45 @@ impl Group {
 /// - `Ok(ExternalFileResult)` containing the parsed `ExternalFileResult (Snippet, Composite or Variables)` object
 /// if parsing is successful.
 /// - `Err(XMLHandlerError)` if there is an error during parsing.
-pub fn parse_include(
-    attributes: quick_xml::events::attributes::Attributes,
-) -> Result<ExternalFileResult> {
-    let mut file_attr = String::new();
-    let mut snippet: Option<Snippet> = None;
-    let mut composite: Option<Composite> = None;
-
-    for attr in attributes {
-        let attr = attr?;
-        if let QName(b"path") = attr.key {
-            file_attr = String::from_utf8_lossy(attr.value.as_ref()).to_string();
-        }
-    }
-
-    match Resource::match_resource(file_attr.as_str()) {
-        Some(res) => {
-            let xml_string = res.to_string();
-            let mut reader = Reader::from_str(xml_string.as_str());
-            //reader.config_mut().trim_text(true);
-
-            let mut buf: Vec<u8> = Vec::new();
-
-            loop {
-                match reader.read_event_into(&mut buf) {
-                    Err(e) => {
-                        eprintln!("Error at position {}: {:?}", reader.error_position(), e);
-                        return Err(XMLHandlerError::ParseError { source: e });
-                    }
-                    Ok(Event::Start(e)) if e.name().as_ref() == b"snippet" => {
-                        snippet = Some(Snippet::parse_snippet(&mut reader, e.attributes())?);
-                        return Ok(ExternalFileResult::Snippet(snippet.unwrap()));
-                    }
-                    Ok(Event::Start(e)) if e.name().as_ref() == b"composite" => {
-                        composite = Some(Composite::parse_composite(&mut reader, e.attributes())?);
-                        return Ok(ExternalFileResult::Composite(composite.unwrap()));
-                    }
-                    Ok(Event::Start(e)) if e.name().as_ref() == b"variables" => {
-                        let variables = Variables::parse_variables(&mut reader, e.attributes())?;
-                        return Ok(ExternalFileResult::Variables(variables));
-                    }
-                    _ => (),
-                }
+pub fn parse_include(path: &str, loader: &mut TemplateLoader) -> Result<ExternalFileResult> {
+    let xml_string = loader.load(path)?;
+    let mut reader = Reader::from_str(xml_string.as_str());
+    //reader.config_mut().trim_text(true);
+
+    let mut buf: Vec<u8> = Vec::new();
+
+    loop {
+        match reader.read_event_into(&mut buf) {
+            Err(e) => {
+                tracing::error!(
+                    position = reader.error_position(),
+                    "XML parse error: {:?}",
+                    e
+                );
+                return Err(XMLHandlerError::ParseError { source: e });
+            }
+            Ok(Event::Start(e)) if e.name().as_ref() == b"snippet" => {
+                let snippet = Snippet::parse_snippet(&mut reader, e.attributes(), path)?;
+                return Ok(ExternalFileResult::Snippet(snippet));
             }
+            Ok(Event::Start(e)) if e.name().as_ref() == b"composite" => {
+                let composite =
+                    Composite::parse_composite(&mut reader, e.attributes(), path, loader)?;
+                return Ok(ExternalFileResult::Composite(composite));
+            }
+            Ok(Event::Start(e)) if e.name().as_ref() == b"variables" => {
+                let variables = Variables::parse_variables(&mut reader, e.attributes(), path)?;
+                return Ok(ExternalFileResult::Variables(variables));
+            }
+            Ok(Event::Eof) => {
+                return Err(XMLHandlerError::UnexpectedEof {
+                    file: path.to_string(),
+                    element: format!("<include path=\"{path}\">"),
+                    position: reader.buffer_position(),
+                });
+            }
+            _ => (),
         }
-        None => Err(XMLHandlerError::UnknownXMLFileError {
-            file_name: file_attr,
-        }),
     }
 }
 
@@ -196,6 +235,10 @@ mod tests {
 
     // Non-testable helper function
     fn parse_groups_from_xml(xml: &str) -> Result<Vec<Group>> {
+        parse_groups_with_loader(xml, &mut TemplateLoader::default())
+    }
+
+    fn parse_groups_with_loader(xml: &str, loader: &mut TemplateLoader) -> Result<Vec<Group>> {
         let mut reader = Reader::from_str(xml);
         let mut buf = Vec::new();
         let mut groups: Vec<Group> = Vec::new();
@@ -203,12 +246,14 @@ mod tests {
         loop {
             match reader.read_event_into(&mut buf) {
                 Ok(Event::Start(e)) => match e.name().as_ref() {
-                    b"group" => match Group::parse_group(&mut reader, e.attributes()) {
-                        Ok(group) => groups.push(group),
-                        Err(e) => {
-                            return Err(e);
+                    b"group" => {
+                        match Group::parse_group(&mut reader, e.attributes(), "test.xml", loader) {
+                            Ok(group) => groups.push(group),
+                            Err(e) => {
+                                return Err(e);
+                            }
                         }
-                    },
+                    }
                     _ => (),
                 },
                 Ok(Event::Eof) => break,
@@ -362,4 +407,87 @@ mod tests {
             Err(e) => assert!(false, "Test failed due to error: {}", e),
         }
     }
+
+    #[test]
+    fn test_parse_composite_with_variables_include() {
+        let xml = r#"<group id="test_group" type="example_type">
+                                <composite name="test_composite">
+                                    <include path="DEFAULT_LIMITS_XML"/>
+                                </composite>
+                        </group>"#;
+
+        match parse_groups_from_xml(xml) {
+            Err(XMLHandlerError::UnsupportedInclude {
+                file,
+                element,
+                include,
+                ..
+            }) => {
+                assert_eq!(file, "test.xml");
+                assert_eq!(element, r#"<composite name="test_composite">"#);
+                assert_eq!(include, "DEFAULT_LIMITS_XML");
+            }
+            res => panic!("expected an unsupported include, got {res:?}"),
+        }
+    }
+
+    #[test]
+    fn test_parse_group_with_snippet_include() {
+        let xml = r#"<group id="test_group" type="example_type">
+                            <include path="Snippet.xml"/>
+                        </group>"#;
+        let dir = std::env::temp_dir().join(format!("xml-group-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("Snippet.xml"), r#"<snippet name="s"></snippet>"#).unwrap();
+
+        let res = parse_groups_with_loader(xml, &mut TemplateLoader::new(Some(dir.clone())));
+        std::fs::remove_dir_all(dir).unwrap();
+        match res {
+            Err(XMLHandlerError::UnsupportedInclude {
+                file,
+                element,
+                position,
+                include,
+            }) => {
+                assert_eq!(file, "test.xml");
+                assert_eq!(element, r#"<group id="test_group">"#);
+                assert_eq!(
+                    &xml[..position as usize],
+                    &xml[..xml.find("/>").unwrap() + 2]
+                );
+                assert_eq!(include, "Snippet.xml");
+            }
+            res => panic!("expected an unsupported include, got {res:?}"),
+        }
+    }
+
+    #[test]
+    fn test_unexpected_eof() {
+        let xml = r#"<group id="test_group" type="example_type">
+                            <composite name="test_composite">"#;
+
+        match parse_groups_from_xml(xml) {
+            Err(XMLHandlerError::UnexpectedEof {
+                file,
+                element,
+                position,
+            }) => {
+                assert_eq!(file, "test.xml");
+                assert_eq!(element, r#"<composite name="test_composite">"#);
+                assert_eq!(position as usize, xml.len());
+            }
+            res => panic!("expected an unexpected end, got {res:?}"),
+        }
+
+        let dir = std::env::temp_dir().join(format!("xml-group-eof-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("Broken.xml"), r#"<snippet name="broken">"#).unwrap();
+        let error =
+            parse_include("Broken.xml", &mut TemplateLoader::new(Some(dir.clone()))).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            r#"Broken.xml (position 23): unexpected end of template in <snippet name="broken">"#
+        );
+        std::fs::remove_dir_all(dir).unwrap();
+    }
 }
@@ -9,8 +9,11 @@ mod resources;
 pub mod composite;
 pub mod condition;
 pub mod group;
+pub mod lint;
+pub mod render;
 pub mod snippet;
 pub mod substitute;
+pub mod templates;
 pub mod variable;
 
 pub mod error;
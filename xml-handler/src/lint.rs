@@ -0,0 +1,615 @@
+//! Checks script templates for mistakes that the parser and the renderer accept silently.
+//!
+//! A substitute or condition whose value is never put into the value replacement map is
+//! replaced with `""`, so the generated script has a hole instead of failing. The checks here
+//! report these and similar problems, given a value replacement map that is representative for
+//! the model that renders the group.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+    path::PathBuf,
+};
+
+use quick_xml::{events::Event, name::QName, Reader};
+
+use crate::{
+    composite::{CommonChunk, Composite},
+    condition::Condition,
+    error::XMLHandlerError,
+    group::{Group, IncludeResult},
+    snippet::Snippet,
+    substitute::Substitute,
+    templates::TemplateLoader,
+    variable,
+};
+
+/// How serious a [`LintIssue`] is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// The templates cannot be loaded
+    Error,
+    /// The templates load, but the generated script is likely wrong
+    Warning,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Error => write!(f, "error"),
+            Self::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+/// The kind of a [`LintIssue`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LintKind {
+    /// An `<include path>` names no template
+    UnknownInclude,
+    /// A template includes itself, directly or indirectly
+    IncludeCycle,
+    /// A template is not valid XML or not a valid template
+    ParseError,
+    /// A substitute names a value that is never populated
+    UnpopulatedSubstitute,
+    /// A condition tests a value that is never populated
+    UnpopulatedCondition,
+    /// A substitute pattern does not occur in the code it applies to
+    UnusedPattern,
+    /// A repeat does not iterate over a list of channels
+    SuspiciousRepeat,
+    /// A group has a type that no model renders
+    UnknownGroup,
+    /// Template variables refer to each other
+    VariableCycle,
+}
+
+/// A problem found in the templates.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LintIssue {
+    pub severity: Severity,
+    pub kind: LintKind,
+    /// The template or chunk the issue was found in
+    pub location: String,
+    pub message: String,
+}
+
+impl LintIssue {
+    /// Creates an issue of severity [`Severity::Error`].
+    pub fn error(kind: LintKind, location: &str, message: String) -> Self {
+        LintIssue {
+            severity: Severity::Error,
+            kind,
+            location: location.to_string(),
+            message,
+        }
+    }
+
+    /// Creates an issue of severity [`Severity::Warning`].
+    pub fn warning(kind: LintKind, location: &str, message: String) -> Self {
+        LintIssue {
+            severity: Severity::Warning,
+            ..Self::error(kind, location, message)
+        }
+    }
+}
+
+impl Display for LintIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}: {}", self.severity, self.location, self.message)
+    }
+}
+
+/// Checks that the template `root` and all templates it includes, directly or indirectly,
+/// exist and are well-formed, without including themselves.
+///
+/// Templates are resolved against `template_dir` first, like [`TemplateLoader`] does.
+pub fn lint_includes(template_dir: Option<PathBuf>, root: &str) -> Vec<LintIssue> {
+    let mut includes = IncludeCheck {
+        loader: TemplateLoader::new(template_dir),
+        stack: Vec::new(),
+        checked: HashSet::new(),
+        issues: Vec::new(),
+    };
+    includes.check(root, root);
+    includes.issues
+}
+
+struct IncludeCheck {
+    loader: TemplateLoader,
+    /// The templates being checked, outermost first
+    stack: Vec<String>,
+    checked: HashSet<String>,
+    issues: Vec<LintIssue>,
+}
+
+impl IncludeCheck {
+    fn check(&mut self, name: &str, parent: &str) {
+        if self.stack.iter().any(|included| included == name) {
+            let cycle = [self.stack.as_slice(), &[name.to_string()]].concat();
+            self.issues.push(LintIssue::error(
+                LintKind::IncludeCycle,
+                parent,
+                format!("include cycle {}", cycle.join(" -> ")),
+            ));
+            return;
+        }
+        if !self.checked.insert(name.to_string()) {
+            return;
+        }
+
+        let source = match self.loader.load(name) {
+            Ok(source) => source,
+            Err(XMLHandlerError::UnknownXMLFileError { file_name }) => {
+                self.issues.push(LintIssue::error(
+                    LintKind::UnknownInclude,
+                    parent,
+                    format!("unknown include path '{file_name}'"),
+                ));
+                return;
+            }
+            Err(e) => {
+                self.issues
+                    .push(LintIssue::error(LintKind::ParseError, name, e.to_string()));
+                return;
+            }
+        };
+        let paths = match include_paths(&source) {
+            Ok(paths) => paths,
+            Err(e) => {
+                self.issues
+                    .push(LintIssue::error(LintKind::ParseError, name, e.to_string()));
+                return;
+            }
+        };
+
+        self.stack.push(name.to_string());
+        for path in paths {
+            self.check(&path, name);
+        }
+        self.stack.pop();
+    }
+}
+
+/// Returns the paths of the `<include>` elements of a template.
+fn include_paths(source: &str) -> Result<Vec<String>, XMLHandlerError> {
+    let mut reader = Reader::from_str(source);
+    let mut paths = Vec::new();
+    loop {
+        match reader.read_event()? {
+            Event::Start(e) | Event::Empty(e) if e.name().as_ref() == b"include" => {
+                for attr in e.attributes() {
+                    let attr = attr?;
+                    if let QName(b"path") = attr.key {
+                        paths.push(String::from_utf8_lossy(attr.value.as_ref()).to_string());
+                    }
+                }
+            }
+            Event::Eof => return Ok(paths),
+            _ => {}
+        }
+    }
+}
+
+/// Checks the substitutes, conditions and repeats of `group` against `values`, the value
+/// replacement map of the model that renders the group.
+///
+/// The map should have the values of at least one channel of each kind, as the channel
+/// attributes of a repeat (e.g. `SWEEP-DEVICE:MODEL`) are looked up for its items.
+pub fn lint_group(group: &Group, values: &HashMap<String, String>) -> Vec<LintIssue> {
+    let mut issues = Vec::new();
+    // the values of the model are only known at runtime, cycles are errors regardless
+    if let Err(e @ XMLHandlerError::DependencyCycle { .. }) =
+        variable::resolve(&group.variable_list, |_| Vec::new())
+    {
+        issues.push(LintIssue::error(
+            LintKind::VariableCycle,
+            &group.type_,
+            e.to_string(),
+        ));
+    }
+
+    let mut lint = GroupLint {
+        values,
+        location: vec![group.type_.clone()],
+        scopes: Vec::new(),
+        substitutions: Vec::new(),
+        reported: HashSet::new(),
+        issues,
+    };
+    for child in &group.children {
+        lint.check_child(child);
+    }
+    lint.issues
+}
+
+struct GroupLint<'a> {
+    values: &'a HashMap<String, String>,
+    location: Vec<String>,
+    /// The names of the enclosing repeats
+    scopes: Vec<String>,
+    /// The substitutes of the enclosing composites and their locations
+    substitutions: Vec<(String, &'a [Substitute])>,
+    /// The substitutes reported as unpopulated, by location and name
+    reported: HashSet<(String, String)>,
+    issues: Vec<LintIssue>,
+}
+
+impl<'a> GroupLint<'a> {
+    fn check_child(&mut self, child: &'a IncludeResult) {
+        match child {
+            IncludeResult::Composite(composite) => self.check_composite(composite),
+            IncludeResult::Snippet(snippet) => self.check_snippet(snippet),
+        }
+    }
+
+    fn check_composite(&mut self, composite: &'a Composite) {
+        self.enter("composite", &composite.name, composite);
+        for sub in &composite.substitutions {
+            if !composite.sub_children.iter().any(|c| uses_pattern(c, sub)) {
+                self.unused_pattern(sub);
+            }
+        }
+        self.substitutions
+            .push((self.location.join(" / "), &composite.substitutions));
+        for child in &composite.sub_children {
+            self.check_child(child);
+        }
+        self.substitutions.pop();
+        self.leave(composite);
+    }
+
+    fn check_snippet(&mut self, snippet: &'a Snippet) {
+        self.enter("snippet", &snippet.name, snippet);
+        for sub in &snippet.substitutions {
+            if !snippet.code_snippet.contains(&sub.value) {
+                self.unused_pattern(sub);
+            }
+        }
+
+        // substitutes are looked up when a snippet is rendered, in the scope of the snippet
+        let location = self.location.join(" / ");
+        let substitutions: Vec<(String, &[Substitute])> = self
+            .substitutions
+            .iter()
+            .cloned()
+            .chain(std::iter::once((
+                location,
+                snippet.substitutions.as_slice(),
+            )))
+            .collect();
+        for (location, subs) in substitutions {
+            for sub in subs {
+                if !snippet.code_snippet.contains(&sub.value) {
+                    continue;
+                }
+                if let Err(reason) = self.resolve(&sub.name) {
+                    if self.reported.insert((location.clone(), sub.name.clone())) {
+                        self.issues.push(LintIssue::warning(
+                            LintKind::UnpopulatedSubstitute,
+                            &location,
+                            format!("substitute '{}' for {} {reason}", sub.name, sub.value),
+                        ));
+                    }
+                }
+            }
+        }
+        self.leave(snippet);
+    }
+
+    /// Checks the conditions and the repeat of a chunk. Conditions are evaluated before the
+    /// chunk is repeated, the repeat applies to the substitutes and the children.
+    fn enter<C: CommonChunk>(&mut self, element: &str, name: &str, chunk: &C) {
+        self.location.push(if name.is_empty() {
+            element.to_string()
+        } else {
+            format!("{element} '{name}'")
+        });
+
+        for condition in chunk.get_conditions() {
+            self.check_condition(condition);
+        }
+        let repeat = chunk.get_repeat();
+        if !repeat.is_empty() {
+            self.check_repeat(repeat);
+            self.scopes.push(repeat.to_string());
+        }
+    }
+
+    fn leave<C: CommonChunk>(&mut self, chunk: &C) {
+        if !chunk.get_repeat().is_empty() {
+            self.scopes.pop();
+        }
+        self.location.pop();
+    }
+
+    fn check_repeat(&mut self, repeat: &str) {
+        let message = if self.scopes.iter().any(|scope| scope == repeat) {
+            format!("repeat '{repeat}' is nested in a repeat over the same list")
+        } else {
+            match self.values.get(repeat) {
+                None => format!("repeat '{repeat}' is not a value of the model"),
+                Some(items) if !items.split(',').any(|item| self.has_attributes(item)) => {
+                    format!("repeat '{repeat}' does not iterate over channels ('{items}')")
+                }
+                Some(_) => return,
+            }
+        };
+        self.issue(LintKind::SuspiciousRepeat, message);
+    }
+
+    fn check_condition(&mut self, condition: &Condition) {
+        if matches!(condition.op.as_str(), "and" | "or" | "not") {
+            for nested in &condition.conditions {
+                self.check_condition(nested);
+            }
+            return;
+        }
+        for name in std::iter::once(&condition.name).chain(&condition.var) {
+            if let Err(reason) = self.resolve(name) {
+                self.issue(
+                    LintKind::UnpopulatedCondition,
+                    format!("condition on '{name}' {reason}"),
+                );
+            }
+        }
+    }
+
+    /// Checks that `symbol` resolves to a value, the way `CommonChunk::lookup` resolves it.
+    fn resolve(&self, symbol: &str) -> Result<(), String> {
+        let in_scope = |scope: &str| self.scopes.iter().any(|s| s == scope);
+        match symbol.split_once(':') {
+            None if self.values.contains_key(symbol) => Ok(()),
+            None => match symbol.strip_suffix(".LOOP-COUNT") {
+                Some(scope) if in_scope(scope) => Ok(()),
+                _ => Err("is never populated".to_string()),
+            },
+            Some((scope, _)) if !in_scope(scope) => {
+                Err(format!("is used outside a repeat over '{scope}'"))
+            }
+            Some((_, "")) => Ok(()),
+            Some((scope, attribute)) => {
+                let items = self.values.get(scope).map_or("", String::as_str);
+                if items
+                    .split(',')
+                    .all(|item| self.values.contains_key(&format!("{item}:{attribute}")))
+                {
+                    Ok(())
+                } else {
+                    Err(format!("is never populated for the items of '{scope}'"))
+                }
+            }
+        }
+    }
+
+    fn has_attributes(&self, item: &str) -> bool {
+        let prefix = format!("{item}:");
+        self.values.keys().any(|key| key.starts_with(&prefix))
+    }
+
+    fn unused_pattern(&mut self, sub: &Substitute) {
+        self.issue(
+            LintKind::UnusedPattern,
+            format!(
+                "pattern {} of substitute '{}' does not occur in its code",
+                sub.value, sub.name
+            ),
+        );
+    }
+
+    fn issue(&mut self, kind: LintKind, message: String) {
+        let location = self.location.join(" / ");
+        self.issues
+            .push(LintIssue::warning(kind, &location, message));
+    }
+}
+
+/// Returns whether the code of `chunk` or its descendants contains the pattern of `sub`.
+fn uses_pattern(chunk: &IncludeResult, sub: &Substitute) -> bool {
+    match chunk {
+        IncludeResult::Snippet(snippet) => snippet.code_snippet.contains(&sub.value),
+        IncludeResult::Composite(composite) => composite
+            .sub_children
+            .iter()
+            .any(|child| uses_pattern(child, sub)),
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    fn parse_group(xml: &str) -> Group {
+        let mut reader = Reader::from_str(xml);
+        let mut buf = Vec::new();
+        loop {
+            match reader.read_event_into(&mut buf).unwrap() {
+                Event::Start(e) if e.name().as_ref() == b"group" => {
+                    return Group::parse_group(
+                        &mut reader,
+                        e.attributes(),
+                        "test.xml",
+                        &mut TemplateLoader::default(),
+                    )
+                    .unwrap();
+                }
+                Event::Eof => panic!("no group in {xml}"),
+                _ => {}
+            }
+        }
+    }
+
+    fn values() -> HashMap<String, String> {
+        [
+            ("SWEEP-DEVICE", "sweep1"),
+            ("sweep1:MODEL", "MSMU60-2"),
+            ("SWEEP-POINTS", "10"),
+        ]
+        .into_iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect()
+    }
+
+    fn issues(xml: &str) -> Vec<(LintKind, String)> {
+        lint_group(&parse_group(xml), &values())
+            .into_iter()
+            .map(|issue| (issue.kind, issue.message))
+            .collect()
+    }
+
+    #[test]
+    fn test_valid_group() {
+        let xml = r#"<group type="Sweep">
+            <composite name="Sweep">
+                <substitute name="SWEEP-POINTS">%POINTS%</substitute>
+                <composite repeat="SWEEP-DEVICE">
+                    <snippet>
+                        <condition name="SWEEP-DEVICE:MODEL" op="ne">MPSU50-2ST</condition>
+                        <substitute name="SWEEP-DEVICE:MODEL">%MODEL%</substitute>
+                        <substitute name="SWEEP-DEVICE.LOOP-COUNT">%INDEX%</substitute>
+                        smu[%INDEX%] = "%MODEL%" -- %POINTS%
+                    </snippet>
+                </composite>
+            </composite>
+        </group>"#;
+        assert_eq!(issues(xml), []);
+    }
+
+    #[test]
+    fn test_unpopulated_values() {
+        let xml = r#"<group type="Sweep">
+            <composite name="Sweep">
+                <substitute name="SWEEP-DEVICE:MODEL">%MODEL%</substitute>
+                <condition name="EPSILON">0</condition>
+                <snippet repeat="SWEEP-DEVICE">
+                    <substitute name="SWEEP-DEVICE:RANGE">%RANGE%</substitute>
+                    %MODEL% %RANGE%
+                </snippet>
+                <snippet>
+                    <substitute name="SWEEP-DEVICE:MODEL">%OTHER%</substitute>
+                    %OTHER%
+                </snippet>
+            </composite>
+        </group>"#;
+        assert_eq!(
+            issues(xml),
+            [
+                (
+                    LintKind::UnpopulatedCondition,
+                    "condition on 'EPSILON' is never populated".to_string()
+                ),
+                (
+                    LintKind::UnpopulatedSubstitute,
+                    "substitute 'SWEEP-DEVICE:RANGE' for %RANGE% is never populated for the \
+                     items of 'SWEEP-DEVICE'"
+                        .to_string()
+                ),
+                (
+                    LintKind::UnpopulatedSubstitute,
+                    "substitute 'SWEEP-DEVICE:MODEL' for %OTHER% is used outside a repeat over \
+                     'SWEEP-DEVICE'"
+                        .to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unused_patterns_and_repeats() {
+        let xml = r#"<group type="Sweep">
+            <composite name="Sweep" repeat="SWEEP-POINTS">
+                <substitute name="SWEEP-POINTS">%POINTS%</substitute>
+                <snippet repeat="BIAS-DEVICE">-- no patterns</snippet>
+            </composite>
+        </group>"#;
+        assert_eq!(
+            issues(xml),
+            [
+                (
+                    LintKind::SuspiciousRepeat,
+                    "repeat 'SWEEP-POINTS' does not iterate over channels ('10')".to_string()
+                ),
+                (
+                    LintKind::UnusedPattern,
+                    "pattern %POINTS% of substitute 'SWEEP-POINTS' does not occur in its code"
+                        .to_string()
+                ),
+                (
+                    LintKind::SuspiciousRepeat,
+                    "repeat 'BIAS-DEVICE' is not a value of the model".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_variable_cycle() {
+        let mut group = parse_group(r#"<group type="Sweep"></group>"#);
+        let constraint = |max_ref: &str| variable::Constraint {
+            max_ref: Some(max_ref.to_string()),
+            ..variable::Constraint::new(0.0, f64::NAN)
+        };
+        group.variable_list = [
+            ("pulseWidth", "sweepTimePerPoint"),
+            ("sweepTimePerPoint", "pulseWidth"),
+        ]
+        .into_iter()
+        .map(|(id, max_ref)| variable::Variable {
+            id: id.to_string(),
+            default: "1".to_string(),
+            value_attr: String::new(),
+            depends_array: Vec::new(),
+            ref_array: Vec::new(),
+            constraint: Some(constraint(max_ref)),
+        })
+        .collect();
+        assert_eq!(
+            lint_group(&group, &values()),
+            [LintIssue::error(
+                LintKind::VariableCycle,
+                "Sweep",
+                "Template variables depend on each other: pulseWidth -> sweepTimePerPoint -> pulseWidth"
+                    .to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn test_includes() {
+        let dir = std::env::temp_dir().join(format!("xml-lint-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("root.xml"),
+            r#"<data><include path="a.xml"/><include path="MISSING"/></data>"#,
+        )
+        .unwrap();
+        fs::write(dir.join("a.xml"), r#"<include path="b.xml"/>"#).unwrap();
+        fs::write(dir.join("b.xml"), r#"<include path="a.xml"/>"#).unwrap();
+
+        let issues = lint_includes(Some(dir.clone()), "root.xml");
+        fs::remove_dir_all(dir).unwrap();
+        assert_eq!(
+            issues,
+            [
+                LintIssue::error(
+                    LintKind::IncludeCycle,
+                    "b.xml",
+                    "include cycle root.xml -> a.xml -> b.xml -> a.xml".to_string()
+                ),
+                LintIssue::error(
+                    LintKind::UnknownInclude,
+                    "root.xml",
+                    "unknown include path 'MISSING'".to_string()
+                ),
+            ]
+        );
+        assert_eq!(
+            lint_includes(None, crate::generic_parser::ROOT_TEMPLATE),
+            []
+        );
+    }
+}
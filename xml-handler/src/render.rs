@@ -0,0 +1,200 @@
+//! Precompiled snippet code and the values it is rendered with.
+//!
+//! The code of a snippet is split into tokens once, when its group is parsed, at the patterns
+//! of the substitutes of the snippet and of its ancestor composites. Rendering is a single pass
+//! over the tokens that looks up the value of each substitute in a [`Scope`]: the value
+//! replacement map of the function model, extended by the current item of each enclosing
+//! `repeat`.
+
+use std::{borrow::Cow, collections::HashMap};
+
+use crate::substitute::Substitute;
+
+/// The values a template is rendered with.
+#[derive(Debug, Clone, Copy)]
+pub enum Scope<'a> {
+    /// The value replacement map of a function model
+    Root(&'a HashMap<String, String>),
+    /// An iteration of a `repeat`, which defines `<repeat>:` as the current item and
+    /// `<repeat>.LOOP-COUNT` as its one based index
+    Item {
+        /// The enclosing scope
+        parent: &'a Scope<'a>,
+        /// The name of the repeated list
+        repeat: &'a str,
+        /// The current item of the list
+        item: &'a str,
+        /// The one based index of the item
+        loop_count: usize,
+    },
+}
+
+impl<'a> Scope<'a> {
+    /// Creates a scope of the values of `val_replacement_map`.
+    pub const fn new(val_replacement_map: &'a HashMap<String, String>) -> Self {
+        Scope::Root(val_replacement_map)
+    }
+
+    /// Creates the scope of the iteration over `item` of the list `repeat`.
+    pub const fn item(&'a self, repeat: &'a str, item: &'a str, loop_count: usize) -> Self {
+        Scope::Item {
+            parent: self,
+            repeat,
+            item,
+            loop_count,
+        }
+    }
+
+    /// Returns the value of `key`, if it is defined.
+    pub fn get(&self, key: &str) -> Option<Cow<'a, str>> {
+        match *self {
+            Scope::Root(values) => values.get(key).map(|value| Cow::Borrowed(value.as_str())),
+            Scope::Item {
+                parent,
+                repeat,
+                item,
+                loop_count,
+            } => {
+                if key.strip_suffix(':') == Some(repeat) {
+                    Some(Cow::Borrowed(item))
+                } else if key.strip_suffix(".LOOP-COUNT") == Some(repeat) {
+                    Some(Cow::Owned(loop_count.to_string()))
+                } else {
+                    parent.get(key)
+                }
+            }
+        }
+    }
+
+    /// Looks up the value of `symbol`, or an empty string if it is not defined.
+    ///
+    /// e.g., The symbol "DEVICES:ASSIGN" has scope "DEVICES" and the value of "DEVICES:" is
+    /// extracted before doing the lookup - so if DEVICES: currently has the value "bias1" then
+    /// the lookup becomes "bias1:ASSIGN".
+    pub fn lookup(&self, symbol: &str) -> String {
+        let key = match symbol.find(':') {
+            Some(index) if index + 1 < symbol.len() => match self.get(&symbol[..=index]) {
+                Some(active) => Cow::Owned(format!("{active}{}", &symbol[index..])),
+                None => Cow::Borrowed(""),
+            },
+            _ => Cow::Borrowed(symbol),
+        };
+        self.get(&key).map(Cow::into_owned).unwrap_or_default()
+    }
+}
+
+/// Code split at the patterns of its substitutes.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Template {
+    tokens: Vec<Token>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    /// Code that is copied verbatim
+    Text(String),
+    /// The value of the substitute with this name
+    Value(String),
+}
+
+impl Template {
+    /// Splits `code` at the patterns of `substitutes`.
+    ///
+    /// The patterns are matched in the order of `substitutes`, so that the first substitute
+    /// wins if patterns overlap. Values are inserted verbatim when rendering, they are not
+    /// matched against the patterns.
+    pub fn compile<'s>(code: &str, substitutes: impl IntoIterator<Item = &'s Substitute>) -> Self {
+        let mut tokens = vec![Token::Text(code.to_string())];
+        for sub in substitutes {
+            if sub.value.is_empty() {
+                continue;
+            }
+            tokens = tokens
+                .into_iter()
+                .flat_map(|token| match token {
+                    Token::Text(text) if text.contains(&sub.value) => split(&text, sub),
+                    token => vec![token],
+                })
+                .collect();
+        }
+        Template { tokens }
+    }
+
+    /// Renders the template with the values of `scope`.
+    pub fn render(&self, scope: &Scope) -> String {
+        let mut code = String::new();
+        for token in &self.tokens {
+            match token {
+                Token::Text(text) => code.push_str(text),
+                Token::Value(name) => code.push_str(&scope.lookup(name)),
+            }
+        }
+        code
+    }
+}
+
+fn split(text: &str, sub: &Substitute) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    for (index, part) in text.split(sub.value.as_str()).enumerate() {
+        if index > 0 {
+            tokens.push(Token::Value(sub.name.clone()));
+        }
+        if !part.is_empty() {
+            tokens.push(Token::Text(part.to_string()));
+        }
+    }
+    tokens
+}
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod tests {
+    use super::*;
+
+    fn sub(name: &str, value: &str) -> Substitute {
+        Substitute {
+            name: name.to_string(),
+            value: value.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_render() {
+        let values = HashMap::from([
+            ("NODE".to_string(), "node[2]".to_string()),
+            ("CHANNELS".to_string(), "smua,smub".to_string()),
+            ("smub:RANGE".to_string(), "1e-3".to_string()),
+        ]);
+        let template = Template::compile(
+            "%NODE%.%CH%.range = %RANGE% -- %N%/%NODE%",
+            &[
+                sub("CHANNELS:", "%CH%"),
+                sub("CHANNELS:RANGE", "%RANGE%"),
+                sub("CHANNELS.LOOP-COUNT", "%N%"),
+                sub("NODE", "%NODE%"),
+            ],
+        );
+
+        let root = Scope::new(&values);
+        assert_eq!(template.render(&root), "node[2]..range =  -- /node[2]");
+        let item = root.item("CHANNELS", "smub", 2);
+        assert_eq!(
+            template.render(&item),
+            "node[2].smub.range = 1e-3 -- 2/node[2]"
+        );
+    }
+
+    #[test]
+    fn test_overlapping_patterns() {
+        let values = HashMap::from([
+            ("A".to_string(), "a".to_string()),
+            ("B".to_string(), "b".to_string()),
+        ]);
+        // the first substitute wins
+        let template = Template::compile("x,%A%", &[sub("A", "%A%"), sub("B", ",%A%")]);
+        let scope = Scope::new(&values);
+        assert_eq!(template.render(&scope), "x,a");
+        let template = Template::compile("x,%A%", &[sub("B", ",%A%"), sub("A", "%A%")]);
+        assert_eq!(template.render(&scope), "xb");
+    }
+}
@@ -27,54 +27,67 @@ const VERSION_REPLACE: &str = "!<!<VERSION>!>!";
 
 pub const DEFAULT_FUNC_METADATA: Resource = Resource {
     source: include_str!("./DefaultFunctionMetaData.xml"),
+    file_name: "DefaultFunctionMetaData.xml",
 };
 
 pub const SWEEP_FUNC_METADATA: Resource = Resource {
     source: include_str!("./SweepFunctionMetaData.xml"),
+    file_name: "SweepFunctionMetaData.xml",
 };
 
 pub const INITIALIZE_XML: Resource = Resource {
     source: include_str!("./Initialize.xml"),
+    file_name: "Initialize.xml",
 };
 
 pub const SUPPORT_XML: Resource = Resource {
     source: include_str!("./Support.xml"),
+    file_name: "Support.xml",
 };
 
 pub const DATA_REPORT_XML: Resource = Resource {
     source: include_str!("./DataReport.xml"),
+    file_name: "DataReport.xml",
 };
 
 pub const FINALIZE_XML: Resource = Resource {
     source: include_str!("./Finalize.xml"),
+    file_name: "Finalize.xml",
 };
 
 pub const DEFAULT_LIMITS_XML: Resource = Resource {
     source: include_str!("./sweep/DefaultLimits.xml"),
+    file_name: "sweep/DefaultLimits.xml",
 };
 
 pub const MP5000_SWEEP_XML: Resource = Resource {
     source: include_str!("./sweep/MP5000Sweep.xml"),
+    file_name: "sweep/MP5000Sweep.xml",
 };
 
 pub const DEFAULT_SWEEP_CHUNK_XML: Resource = Resource {
     source: include_str!("./sweep/DefaultSweepChunk.xml"),
+    file_name: "sweep/DefaultSweepChunk.xml",
 };
 
 pub const NO_STEP_AUTO_XML: Resource = Resource {
     source: include_str!("./sweep/NoStepAuto.xml"),
+    file_name: "sweep/NoStepAuto.xml",
 };
 
 pub const STEP_AUTO_XML: Resource = Resource {
     source: include_str!("./sweep/StepAuto.xml"),
+    file_name: "sweep/StepAuto.xml",
 };
 
 pub const NO_STEP_FIXED_XML: Resource = Resource {
     source: include_str!("./sweep/NoStepFixed.xml"),
+    file_name: "sweep/NoStepFixed.xml",
 };
 
 pub const STEP_FIXED_XML: Resource = Resource {
     source: include_str!("./sweep/StepFixed.xml"),
+    file_name: "sweep/StepFixed.xml",
 };
 
 /// A resource that can be used as-is
@@ -82,12 +95,13 @@ pub const STEP_FIXED_XML: Resource = Resource {
 pub struct Resource {
     /// The raw resource that can be used as-is
     source: &'static str,
+    /// The file of the resource, relative to the resources directory
+    file_name: &'static str,
 }
 
 impl Display for Resource {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let source = self.source.replace(VERSION_REPLACE, VERSION);
-        write!(f, "{source}")
+        write!(f, "{}", Self::expand(self.source))
     }
 }
 
@@ -96,4 +110,14 @@ impl Resource {
     pub fn match_resource(input: &str) -> Option<&'static Resource> {
         RESOURCE_MAP.get(input).copied()
     }
+
+    /// Returns the file of the resource, relative to the resources directory.
+    pub fn file_name(&self) -> &'static str {
+        self.file_name
+    }
+
+    /// Replaces the placeholders of a resource, such as the generator version.
+    pub fn expand(source: &str) -> String {
+        source.replace(VERSION_REPLACE, VERSION)
+    }
 }
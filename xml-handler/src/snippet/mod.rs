@@ -1,16 +1,13 @@
-use std::{
-    any::Any,
-    collections::HashMap,
-    io::{self, BufRead, Cursor},
-};
+use std::any::Any;
 
 use quick_xml::{events::Event, name::QName, Reader};
-use script_aggregator::script_buffer::ScriptBuffer;
+use script_aggregator::{script_buffer::ScriptBuffer, source_map::SourceElement};
 
 use crate::{
-    composite::{CommonChunk, Composite},
+    composite::CommonChunk,
     condition::Condition,
     error::{Result, XMLHandlerError},
+    render::{Scope, Template},
     substitute::Substitute,
 };
 
@@ -29,8 +26,12 @@ pub struct Snippet {
     pub substitutions: Vec<Substitute>,
     /// The conditions associated with the snippet.
     pub conditions: Vec<Condition>,
-    /// The parent Composite, if any.
-    pub parent: Option<Box<Composite>>,
+    /// The code split at the patterns of the substitutes of the snippet and its ancestors.
+    pub template: Template,
+    /// The template the snippet is defined in.
+    pub file: String,
+    /// The byte offset of the snippet in `file`.
+    pub position: u64,
 }
 
 impl Snippet {
@@ -41,6 +42,8 @@ impl Snippet {
         code_snippet: String,
         substitutions: Vec<Substitute>,
         conditions: Vec<Condition>,
+        file: &str,
+        position: u64,
     ) -> Self {
         Snippet {
             name,
@@ -49,14 +52,19 @@ impl Snippet {
             code_snippet,
             substitutions,
             conditions,
-            parent: None,
+            template: Template::default(),
+            file: file.to_string(),
+            position,
         }
     }
 
+    /// Parses a snippet element of the template `file`.
     pub fn parse_snippet<R: std::io::BufRead>(
         reader: &mut Reader<R>,
         attributes: quick_xml::events::attributes::Attributes,
+        file: &str,
     ) -> Result<Snippet> {
+        let position = reader.buffer_position();
         let mut name = String::new();
         let mut repeat = String::new();
 
@@ -80,7 +88,11 @@ impl Snippet {
         loop {
             match reader.read_event_into(&mut buf) {
                 Err(e) => {
-                    eprintln!("Error at position {}: {:?}", reader.error_position(), e);
+                    tracing::error!(
+                        position = reader.error_position(),
+                        "XML parse error: {:?}",
+                        e
+                    );
                     return Err(XMLHandlerError::ParseError { source: e });
                 }
                 Ok(Event::Text(mut e)) => {
@@ -94,7 +106,7 @@ impl Snippet {
                             // file.write_all(code_snippet.as_bytes())?;
                         }
                         Err(e) => {
-                            eprintln!("Error decoding text: {}", e);
+                            tracing::error!("Error decoding text: {}", e);
                             return Err(XMLHandlerError::ParseError { source: e });
                         }
                     }
@@ -103,7 +115,21 @@ impl Snippet {
                     substitutions.push(Substitute::parse_substitute(reader, e.attributes())?);
                 }
                 Ok(Event::Start(e)) if e.name().as_ref() == b"condition" => {
-                    conditions.push(Condition::parse_condition(reader, e.attributes())?);
+                    conditions.push(Condition::parse_condition(reader, e.attributes(), file)?);
+                }
+                Ok(Event::Empty(e)) if e.name().as_ref() == b"condition" => {
+                    conditions.push(Condition::parse_condition_attr_only(
+                        reader,
+                        e.attributes(),
+                        file,
+                    )?);
+                }
+                Ok(Event::Eof) => {
+                    return Err(XMLHandlerError::UnexpectedEof {
+                        file: file.to_string(),
+                        element: format!("<snippet name=\"{name}\">"),
+                        position,
+                    });
                 }
                 Ok(Event::End(e)) if e.name().as_ref() == b"snippet" => {
                     return Ok(Snippet::new(
@@ -112,6 +138,8 @@ impl Snippet {
                         code_snippet,
                         substitutions,
                         conditions,
+                        file,
+                        position,
                     ));
                 }
                 _ => (),
@@ -119,38 +147,27 @@ impl Snippet {
         }
     }
 
+    /// Compiles the code with the substitutes of the snippet and then of its `ancestors`,
+    /// innermost first.
+    pub(crate) fn compile(&mut self, ancestors: &[&[Substitute]]) {
+        let substitutes = self
+            .substitutions
+            .iter()
+            .chain(ancestors.iter().flat_map(|subs| subs.iter()));
+        self.template = Template::compile(&self.code_snippet, substitutes);
+    }
+
     /// Evaluates the snippet and inserts the resulting code into the script buffer.
     ///
-    /// This method processes the substitutions in the snippet and its parent composites,
-    /// replaces the placeholders in the code snippet with the corresponding values,
-    /// and inserts the resulting code into the script buffer.
+    /// This method replaces the placeholders of the compiled code snippet with the values of
+    /// the substitutes in `scope` and inserts the resulting code into the script buffer.
     ///
     /// # Arguments
     ///
     /// * `script_buffer` - A mutable reference to the script buffer.
-    /// * `val_replacement_map` - A reference to the value replacement map.
-    pub fn evaluate_snippet(
-        &self,
-        script_buffer: &mut ScriptBuffer,
-        val_replacement_map: &std::collections::HashMap<String, String>,
-    ) {
-        let mut temp_code_snippet = self.code_snippet.clone();
-
-        for sub in self.substitutions.iter() {
-            let to_val = self.lookup(val_replacement_map, &sub.name);
-            temp_code_snippet = temp_code_snippet.replace(&sub.value, &to_val);
-        }
-
-        let mut current_parent = self.parent.as_deref(); // Use as_deref to get Option<&Composite>
-        while let Some(parent) = current_parent {
-            for sub in parent.substitutions.iter() {
-                let to_val = parent.lookup(val_replacement_map, &sub.name);
-                temp_code_snippet = temp_code_snippet.replace(&sub.value, &to_val);
-            }
-            current_parent = parent.parent.as_deref(); // Use as_deref to get Option<&Composite>
-        }
-
-        self.insert(script_buffer, temp_code_snippet);
+    /// * `scope` - The values of the value replacement map and the enclosing repeats.
+    pub fn evaluate_snippet(&self, script_buffer: &mut ScriptBuffer, scope: &Scope) {
+        self.insert(script_buffer, self.template.render(scope));
     }
 
     /// Inserts the given text into the script buffer.
@@ -163,21 +180,8 @@ impl Snippet {
     /// * `script_buffer` - A mutable reference to the script buffer.
     /// * `temp_code` - The text to be inserted into the script buffer.
     fn insert(&self, script_buffer: &mut ScriptBuffer, temp_code: String) {
-        // Create a cursor to read the string as bytes
-        let cursor = Cursor::new(temp_code);
-        let reader = io::BufReader::new(cursor);
-
-        // Read the input string line by line
-        for line in reader.lines() {
-            match line {
-                Ok(line) => {
-                    script_buffer.body_append(line);
-                }
-                Err(e) => {
-                    //TODO: Add error handling
-                    eprintln!("Error reading line: {}", e);
-                }
-            }
+        for line in temp_code.lines() {
+            script_buffer.body_append(line.to_string());
         }
     }
 }
@@ -199,11 +203,12 @@ impl CommonChunk for Snippet {
         &self.conditions
     }
 
-    fn evaluate(
-        &mut self,
-        script_buffer: &mut ScriptBuffer,
-        val_replacement_map: &HashMap<String, String>,
-    ) {
-        self.evaluate_snippet(script_buffer, val_replacement_map);
+    fn get_source(&self) -> SourceElement {
+        SourceElement::new(&self.file, "snippet", &self.name, self.position)
+    }
+
+    fn evaluate(&self, script_buffer: &mut ScriptBuffer, scope: &Scope) -> Result<()> {
+        self.evaluate_snippet(script_buffer, scope);
+        Ok(())
     }
 }
@@ -36,7 +36,11 @@ impl Substitute {
 
         match reader.read_event_into(&mut buf) {
             Err(e) => {
-                eprintln!("Error at position {}: {:?}", reader.error_position(), e);
+                tracing::error!(
+                    position = reader.error_position(),
+                    "XML parse error: {:?}",
+                    e
+                );
                 return Err(XMLHandlerError::ParseError { source: e });
             }
             Ok(Event::Text(e)) => {
@@ -44,7 +48,7 @@ impl Substitute {
                 match e.unescape() {
                     Ok(text) => value = text.to_string(),
                     Err(e) => {
-                        eprintln!("Error reading substitute value: {:?}", e);
+                        tracing::error!("Error reading substitute value: {:?}", e);
                         return Err(XMLHandlerError::ParseError { source: e });
                     }
                 }
@@ -0,0 +1,171 @@
+//! Loads the XML templates referenced by `<include path="..."/>`.
+//!
+//! The templates are compiled into the binary. A template directory can override them at
+//! runtime: an embedded resource such as `MP5000_SWEEP_XML` is read from its file name
+//! relative to the directory (`sweep/MP5000Sweep.xml`) if that file exists, and an include path
+//! that names no embedded resource is read as a path relative to the directory.
+
+use std::{
+    fmt::Display,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    error::{Result, XMLHandlerError},
+    resources::Resource,
+};
+
+/// Where a template was loaded from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ResourceOrigin {
+    /// The template compiled into the binary
+    Embedded,
+    /// A file of the template directory
+    File(PathBuf),
+}
+
+impl Display for ResourceOrigin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Embedded => write!(f, "embedded"),
+            Self::File(path) => write!(f, "{}", path.display()),
+        }
+    }
+}
+
+/// A template loaded by a [`TemplateLoader`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoadedResource {
+    /// The include path of the template
+    pub name: String,
+    /// Where the template was loaded from
+    pub origin: ResourceOrigin,
+}
+
+/// Resolves include paths against an optional template directory and the embedded resources,
+/// and records where each template was loaded from.
+#[derive(Debug, Default)]
+pub struct TemplateLoader {
+    dir: Option<PathBuf>,
+    loaded: Vec<LoadedResource>,
+}
+
+impl TemplateLoader {
+    /// Creates a loader that prefers the templates in `dir`, if any, over the embedded ones.
+    pub fn new(dir: Option<PathBuf>) -> Self {
+        TemplateLoader {
+            dir,
+            loaded: Vec::new(),
+        }
+    }
+
+    /// Returns the template directory.
+    pub fn dir(&self) -> Option<&Path> {
+        self.dir.as_deref()
+    }
+
+    /// Returns the contents of the template with include path `name`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the template file of the directory cannot be read, or if `name`
+    /// is neither a file of the directory nor an embedded resource.
+    pub fn load(&mut self, name: &str) -> Result<String> {
+        let resource = Resource::match_resource(name);
+        if let Some(dir) = &self.dir {
+            let path = dir.join(resource.map_or(name, |resource| resource.file_name()));
+            if path.is_file() {
+                let source = fs::read_to_string(&path).map_err(|source| {
+                    XMLHandlerError::TemplateReadError {
+                        path: path.clone(),
+                        source,
+                    }
+                })?;
+                self.record(name, ResourceOrigin::File(path));
+                return Ok(Resource::expand(&source));
+            }
+        }
+
+        match resource {
+            Some(resource) => {
+                self.record(name, ResourceOrigin::Embedded);
+                Ok(resource.to_string())
+            }
+            None => Err(XMLHandlerError::UnknownXMLFileError {
+                file_name: name.to_string(),
+            }),
+        }
+    }
+
+    /// Returns the templates loaded so far, in load order.
+    pub fn loaded(&self) -> &[LoadedResource] {
+        &self.loaded
+    }
+
+    fn record(&mut self, name: &str, origin: ResourceOrigin) {
+        self.loaded.push(LoadedResource {
+            name: name.to_string(),
+            origin,
+        });
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_embedded() {
+        let mut loader = TemplateLoader::default();
+        let source = loader.load("MP5000_SWEEP_XML").unwrap();
+        assert!(source.contains("PrintResults"));
+        assert_eq!(
+            loader.loaded(),
+            [LoadedResource {
+                name: "MP5000_SWEEP_XML".to_string(),
+                origin: ResourceOrigin::Embedded,
+            }]
+        );
+        assert!(matches!(
+            loader.load("CUSTOM.xml"),
+            Err(XMLHandlerError::UnknownXMLFileError { .. })
+        ));
+    }
+
+    #[test]
+    fn test_load_from_dir() {
+        let dir = std::env::temp_dir().join(format!("xml-templates-{}", std::process::id()));
+        fs::create_dir_all(dir.join("sweep")).unwrap();
+        fs::write(
+            dir.join("sweep/MP5000Sweep.xml"),
+            "<snippet>!<!<VERSION>!>!</snippet>",
+        )
+        .unwrap();
+        fs::write(dir.join("Custom.xml"), "<snippet/>").unwrap();
+
+        let mut loader = TemplateLoader::new(Some(dir.clone()));
+        assert_eq!(
+            loader.load("MP5000_SWEEP_XML").unwrap(),
+            format!("<snippet>{}</snippet>", crate::VERSION)
+        );
+        assert_eq!(loader.load("Custom.xml").unwrap(), "<snippet/>");
+        // not overridden
+        loader.load("DEFAULT_LIMITS_XML").unwrap();
+        assert_eq!(
+            loader
+                .loaded()
+                .iter()
+                .map(|resource| resource.origin.clone())
+                .collect::<Vec<_>>(),
+            [
+                ResourceOrigin::File(dir.join("sweep/MP5000Sweep.xml")),
+                ResourceOrigin::File(dir.join("Custom.xml")),
+                ResourceOrigin::Embedded,
+            ]
+        );
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
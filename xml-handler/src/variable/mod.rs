@@ -1,7 +1,9 @@
 use quick_xml::{events::Event, name::QName, Reader};
+mod resolve;
 mod sub_mod;
 
 use crate::error::{Result, XMLHandlerError};
+pub use resolve::{resolve, ResolvedVariable};
 pub use sub_mod::{Constraint, Reference};
 
 /// Represents a variables tag in the XML data.
@@ -18,20 +20,25 @@ pub struct Variable {
     pub id: String,
     /// The default value of the variable.
     pub default: String,
-    /// The value attribute of the variable.
-    value_attr: String,
+    /// The value attribute of the variable, the value of the variable it depends on that
+    /// selects this case.
+    pub value_attr: String,
     /// A list of dependencies for this variable.
-    depends_array: Vec<Depend>,
+    pub depends_array: Vec<Depend>,
     /// A list of references associated with this variable.
-    ref_array: Vec<Reference>,
+    pub ref_array: Vec<Reference>,
     /// An optional constraint applied to the variable.
     pub constraint: Option<Constraint>,
 }
 
+/// Represents a depends tag in the XML data, the cases of a variable selected by the value of
+/// another variable.
 #[derive(Debug, Clone)]
 pub struct Depend {
-    re_f: String,
-    _variables: Vec<Variable>,
+    /// The id of the variable (or referenced value) that selects the case.
+    pub re_f: String,
+    /// The cases, variables whose `value_attr` is the value that selects them.
+    pub cases: Vec<Variable>,
 }
 
 impl Variables {
@@ -39,10 +46,13 @@ impl Variables {
         Variables { variable_array }
     }
 
+    /// Parses a variables element of the template `file`.
     pub fn parse_variables<R: std::io::BufRead>(
         reader: &mut Reader<R>,
         attributes: quick_xml::events::attributes::Attributes,
+        file: &str,
     ) -> Result<Variables> {
+        let position = reader.buffer_position();
         let mut variables: Vec<Variable> = Vec::new();
 
         let mut buf: Vec<u8> = Vec::new();
@@ -50,11 +60,22 @@ impl Variables {
         loop {
             match reader.read_event_into(&mut buf) {
                 Err(e) => {
-                    eprintln!("Error at position {}: {:?}", reader.error_position(), e);
+                    tracing::error!(
+                        position = reader.error_position(),
+                        "XML parse error: {:?}",
+                        e
+                    );
                     return Err(XMLHandlerError::ParseError { source: e });
                 }
                 Ok(Event::Start(e)) if e.name().as_ref() == b"variable" => {
-                    variables.push(Variable::parse_variable(reader, e.attributes())?);
+                    variables.push(Variable::parse_variable(reader, e.attributes(), file)?);
+                }
+                Ok(Event::Eof) => {
+                    return Err(XMLHandlerError::UnexpectedEof {
+                        file: file.to_string(),
+                        element: "<variables>".to_string(),
+                        position,
+                    });
                 }
                 Ok(Event::End(e)) if e.name().as_ref() == b"variables" => {
                     return Ok(Variables::new(variables));
@@ -84,10 +105,13 @@ impl Variable {
         }
     }
 
+    /// Parses a variable (or case) element of the template `file`.
     pub fn parse_variable<R: std::io::BufRead>(
         reader: &mut Reader<R>,
         attributes: quick_xml::events::attributes::Attributes,
+        file: &str,
     ) -> Result<Variable> {
+        let position = reader.buffer_position();
         let mut id = String::new();
         let mut default = String::new();
         let mut value_attr = String::new();
@@ -112,14 +136,22 @@ impl Variable {
         loop {
             match reader.read_event_into(&mut buf) {
                 Err(e) => {
-                    eprintln!("Error at position {}: {:?}", reader.error_position(), e);
+                    tracing::error!(
+                        position = reader.error_position(),
+                        "XML parse error: {:?}",
+                        e
+                    );
                     return Err(XMLHandlerError::ParseError { source: e });
                 }
                 Ok(Event::Start(e)) if e.name().as_ref() == b"default" => {
                     // Read text content of <default> tag
                     match reader.read_event_into(&mut buf) {
                         Err(e) => {
-                            eprintln!("Error at position {}: {:?}", reader.error_position(), e);
+                            tracing::error!(
+                                position = reader.error_position(),
+                                "XML parse error: {:?}",
+                                e
+                            );
                             return Err(XMLHandlerError::ParseError { source: e });
                         }
                         Ok(Event::Text(e)) => {
@@ -127,7 +159,7 @@ impl Variable {
                             match e.unescape() {
                                 Ok(text) => default = text.to_string(),
                                 Err(e) => {
-                                    eprintln!("Error reading default value: {:?}", e);
+                                    tracing::error!("Error reading default value: {:?}", e);
                                     return Err(XMLHandlerError::ParseError { source: e });
                                 }
                             }
@@ -142,10 +174,17 @@ impl Variable {
                     ref_array.push(Reference::parse_reference(reader, e.attributes())?);
                 }
                 Ok(Event::Start(e)) if e.name().as_ref() == b"constraints" => {
-                    constraint = Some(Constraint::parse_constraint(reader, e.attributes())?);
+                    constraint = Some(Constraint::parse_constraint(reader, e.attributes(), file)?);
                 }
                 Ok(Event::Start(e)) if e.name().as_ref() == b"depends" => {
-                    depends_array.push(Depend::parse_depend(reader, e.attributes())?);
+                    depends_array.push(Depend::parse_depend(reader, e.attributes(), file)?);
+                }
+                Ok(Event::Eof) => {
+                    return Err(XMLHandlerError::UnexpectedEof {
+                        file: file.to_string(),
+                        element: format!("<variable id=\"{id}\">"),
+                        position,
+                    });
                 }
                 Ok(Event::End(e))
                     if e.name().as_ref() == b"case" || e.name().as_ref() == b"variable" =>
@@ -166,16 +205,18 @@ impl Variable {
 }
 
 impl Depend {
-    fn new(re_f: String, _variables: Vec<Variable>) -> Self {
-        Depend { re_f, _variables }
+    fn new(re_f: String, cases: Vec<Variable>) -> Self {
+        Depend { re_f, cases }
     }
 
     fn parse_depend<R: std::io::BufRead>(
         reader: &mut Reader<R>,
         attributes: quick_xml::events::attributes::Attributes,
+        file: &str,
     ) -> Result<Depend> {
+        let position = reader.buffer_position();
         let mut re_f = String::new();
-        let mut _variables: Vec<Variable> = Vec::new();
+        let mut cases: Vec<Variable> = Vec::new();
 
         let mut buf: Vec<u8> = Vec::new();
 
@@ -189,14 +230,25 @@ impl Depend {
         loop {
             match reader.read_event_into(&mut buf) {
                 Err(e) => {
-                    eprintln!("Error at position {}: {:?}", reader.error_position(), e);
+                    tracing::error!(
+                        position = reader.error_position(),
+                        "XML parse error: {:?}",
+                        e
+                    );
                     return Err(XMLHandlerError::ParseError { source: e });
                 }
                 Ok(Event::Start(e)) if e.name().as_ref() == b"case" => {
-                    _variables.push(Variable::parse_variable(reader, e.attributes())?);
+                    cases.push(Variable::parse_variable(reader, e.attributes(), file)?);
+                }
+                Ok(Event::Eof) => {
+                    return Err(XMLHandlerError::UnexpectedEof {
+                        file: file.to_string(),
+                        element: format!("<depends ref=\"{re_f}\">"),
+                        position,
+                    });
                 }
                 Ok(Event::End(e)) if e.name().as_ref() == b"depends" => {
-                    return Ok(Depend::new(re_f, _variables));
+                    return Ok(Depend::new(re_f, cases));
                 }
                 _ => (),
             }
@@ -218,11 +270,19 @@ mod tests {
         loop {
             match reader.read_event_into(&mut buf) {
                 Err(e) => {
-                    eprintln!("Error at position {}: {:?}", reader.buffer_position(), e);
+                    tracing::error!(
+                        position = reader.buffer_position(),
+                        "XML parse error: {:?}",
+                        e
+                    );
                     return Err(XMLHandlerError::ParseError { source: e });
                 }
                 Ok(Event::Start(e)) if e.name().as_ref() == b"variable" => {
-                    variables.push(Variable::parse_variable(&mut reader, e.attributes())?);
+                    variables.push(Variable::parse_variable(
+                        &mut reader,
+                        e.attributes(),
+                        "test.xml",
+                    )?);
                 }
                 Ok(Event::End(e)) if e.name().as_ref() == b"variables" => {
                     return Ok(Variables::new(variables));
@@ -317,9 +377,9 @@ mod tests {
 
                 let depend = &vars.variable_array[0].depends_array[0];
                 assert_eq!(depend.re_f, "varFunction");
-                assert_eq!(depend._variables.len(), 2);
+                assert_eq!(depend.cases.len(), 2);
 
-                for (i, case) in depend._variables.iter().enumerate() {
+                for (i, case) in depend.cases.iter().enumerate() {
                     assert_eq!(case.value_attr, format!("case_{}", i + 1));
                     assert_eq!(case.default, "0");
                     assert_eq!(case.depends_array.len(), 0);
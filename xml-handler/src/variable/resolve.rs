@@ -0,0 +1,337 @@
+//! Resolves the values and constraints of template variables.
+//!
+//! The value of a variable is, in order of precedence:
+//!
+//! 1. the value the caller provides for the id of the variable,
+//! 2. the value of the first `<reference>` that applies,
+//! 3. the `<default>` of the variable.
+//!
+//! A `<depends ref="...">` selects the `<case>` whose `value` is the value of `ref`. The
+//! default, references and constraints of the case replace the ones of the variable. The
+//! bounds of a constraint can be the value of another variable, e.g. `<max ref="..."/>`.
+//!
+//! The ids of `ref` attributes and references name another variable, or else a value the caller
+//! provides. A value of the caller can have several items, such as the source function of each
+//! SMU channel for `smu@.source.function`. A reference with a `value` attribute applies if all
+//! items (any item with `useall="false"`) equal `value`, its value is the text of the
+//! reference. A reference without `value` applies if all items are the same (always with
+//! `useall="false"`), its value is the first item.
+//!
+//! A variable is resolved after the variables it refers to, variables that refer to each other
+//! are an error.
+
+use std::collections::HashMap;
+
+use super::{Constraint, Reference, Variable};
+use crate::error::{Result, XMLHandlerError};
+
+/// A variable with the values it refers to resolved.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResolvedVariable {
+    /// The id of the variable.
+    pub id: String,
+    /// The value of the variable.
+    pub value: String,
+    /// The constraint of the variable, with the bounds that refer to other variables replaced
+    /// by their values.
+    pub constraint: Option<Constraint>,
+}
+
+/// Resolves `variables`, looking up the items of the values the caller provides with `lookup`.
+///
+/// Returns the resolved variables in dependency order: a variable follows the variables it
+/// refers to.
+///
+/// # Errors
+///
+/// Returns [`XMLHandlerError::DependencyCycle`] if variables refer to each other and
+/// [`XMLHandlerError::InvalidBound`] if a bound of a constraint refers to a value that is not
+/// a number.
+pub fn resolve<F>(variables: &[Variable], lookup: F) -> Result<Vec<ResolvedVariable>>
+where
+    F: Fn(&str) -> Vec<String>,
+{
+    let mut resolver = Resolver {
+        variables: HashMap::new(),
+        lookup,
+        resolved: Vec::new(),
+        index: HashMap::new(),
+        path: Vec::new(),
+    };
+    for variable in variables {
+        resolver
+            .variables
+            .entry(variable.id.as_str())
+            .or_insert(variable);
+    }
+    for variable in variables {
+        resolver.resolve(variable)?;
+    }
+    Ok(resolver.resolved)
+}
+
+struct Resolver<'a, F> {
+    variables: HashMap<&'a str, &'a Variable>,
+    lookup: F,
+    resolved: Vec<ResolvedVariable>,
+    /// The index of each resolved variable in `resolved`
+    index: HashMap<&'a str, usize>,
+    /// The variables being resolved, each one refers to the next one
+    path: Vec<&'a str>,
+}
+
+impl<'a, F: Fn(&str) -> Vec<String>> Resolver<'a, F> {
+    /// Returns the items of the value of `id`.
+    fn values(&mut self, id: &'a str) -> Result<Vec<String>> {
+        match self.variables.get(id) {
+            Some(&variable) => {
+                let index = self.resolve(variable)?;
+                Ok(vec![self.resolved[index].value.clone()])
+            }
+            None => Ok((self.lookup)(id)),
+        }
+    }
+
+    fn resolve(&mut self, variable: &'a Variable) -> Result<usize> {
+        if let Some(&index) = self.index.get(variable.id.as_str()) {
+            return Ok(index);
+        }
+        if let Some(start) = self.path.iter().position(|id| *id == variable.id) {
+            let mut cycle: Vec<String> =
+                self.path[start..].iter().map(|id| id.to_string()).collect();
+            cycle.push(variable.id.clone());
+            return Err(XMLHandlerError::DependencyCycle { cycle });
+        }
+        self.path.push(&variable.id);
+
+        let mut default = &variable.default;
+        let mut references = &variable.ref_array;
+        let mut constraint = variable.constraint.as_ref();
+        for depend in &variable.depends_array {
+            let items = self.values(&depend.re_f)?;
+            let Some(case) = depend
+                .cases
+                .iter()
+                .find(|case| all_equal(&items, &case.value_attr))
+            else {
+                continue;
+            };
+            if !case.default.is_empty() {
+                default = &case.default;
+            }
+            if !case.ref_array.is_empty() {
+                references = &case.ref_array;
+            }
+            if case.constraint.is_some() {
+                constraint = case.constraint.as_ref();
+            }
+        }
+
+        let mut value = (self.lookup)(&variable.id).into_iter().next();
+        for reference in references {
+            if value.is_some() {
+                break;
+            }
+            value = self.reference(reference)?;
+        }
+        let constraint = match constraint {
+            Some(constraint) => Some(self.constraint(&variable.id, constraint)?),
+            None => None,
+        };
+
+        self.path.pop();
+        self.resolved.push(ResolvedVariable {
+            id: variable.id.clone(),
+            value: value.unwrap_or_else(|| default.clone()),
+            constraint,
+        });
+        let index = self.resolved.len() - 1;
+        self.index.insert(&variable.id, index);
+        Ok(index)
+    }
+
+    /// Returns the value of `reference`, if it applies.
+    fn reference(&mut self, reference: &'a Reference) -> Result<Option<String>> {
+        let items = self.values(&reference.id)?;
+        let any = reference.useall == "false";
+        let value = if reference.value.is_empty() {
+            let applies = any || all_equal(&items, &items.first().cloned().unwrap_or_default());
+            items.into_iter().next().filter(|_| applies)
+        } else {
+            let applies = if any {
+                items.contains(&reference.value)
+            } else {
+                all_equal(&items, &reference.value)
+            };
+            applies.then(|| reference.default.clone())
+        };
+        Ok(value)
+    }
+
+    fn constraint(&mut self, id: &str, constraint: &'a Constraint) -> Result<Constraint> {
+        let mut resolved = Constraint::new(constraint.min, constraint.max);
+        if let Some(reference) = &constraint.min_ref {
+            resolved.min = self.bound(id, reference)?;
+        }
+        if let Some(reference) = &constraint.max_ref {
+            resolved.max = self.bound(id, reference)?;
+        }
+        Ok(resolved)
+    }
+
+    fn bound(&mut self, id: &str, reference: &'a str) -> Result<f64> {
+        let value = self
+            .values(reference)?
+            .into_iter()
+            .next()
+            .unwrap_or_default();
+        value
+            .trim()
+            .parse()
+            .map_err(|_| XMLHandlerError::InvalidBound {
+                variable: id.to_string(),
+                reference: reference.to_string(),
+                value,
+            })
+    }
+}
+
+/// Returns whether `items` is not empty and each item is `value`.
+fn all_equal(items: &[String], value: &str) -> bool {
+    !items.is_empty() && items.iter().all(|item| item == value)
+}
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod tests {
+    use quick_xml::{events::Event, Reader};
+
+    use super::*;
+    use crate::variable::Variables;
+
+    fn parse(xml: &str) -> Vec<Variable> {
+        let mut reader = Reader::from_str(xml);
+        let mut buf = Vec::new();
+        loop {
+            match reader.read_event_into(&mut buf).unwrap() {
+                Event::Start(e) if e.name().as_ref() == b"variables" => {
+                    return Variables::parse_variables(&mut reader, e.attributes(), "test.xml")
+                        .unwrap()
+                        .variable_array;
+                }
+                Event::Eof => panic!("no variables in {xml}"),
+                _ => {}
+            }
+        }
+    }
+
+    fn value<'a>(resolved: &'a [ResolvedVariable], id: &str) -> &'a ResolvedVariable {
+        resolved.iter().find(|variable| variable.id == id).unwrap()
+    }
+
+    const VARIABLES: &str = r#"<variables>
+        <variable id="pulseWidth">
+            <default>1e-3</default>
+            <constraints>
+                <min>1e-6</min>
+                <max ref="sweepTimePerPoint"/>
+            </constraints>
+        </variable>
+        <variable id="sweepTimePerPoint">
+            <default>50e-3</default>
+        </variable>
+        <variable id="sourceRange">
+            <depends ref="sourceFunction">
+                <case value="voltage">
+                    <default>1 V</default>
+                    <reference id="smu@.source.autorange" useall="false" value="1">AUTO</reference>
+                </case>
+                <case value="current">
+                    <default>10 mA</default>
+                    <reference id="smu@.source.rangei"/>
+                </case>
+            </depends>
+        </variable>
+        <variable id="sourceFunction">
+            <default>voltage</default>
+        </variable>
+    </variables>"#;
+
+    #[test]
+    fn test_resolve_defaults() {
+        let resolved = resolve(&parse(VARIABLES), |_| Vec::new()).unwrap();
+        // dependency order
+        let ids: Vec<&str> = resolved.iter().map(|v| v.id.as_str()).collect();
+        assert_eq!(
+            ids,
+            [
+                "sweepTimePerPoint",
+                "pulseWidth",
+                "sourceFunction",
+                "sourceRange"
+            ]
+        );
+        let constraint = value(&resolved, "pulseWidth").constraint.clone().unwrap();
+        assert_eq!((constraint.min, constraint.max), (1e-6, 50e-3));
+        assert_eq!(value(&resolved, "sourceRange").value, "1 V");
+    }
+
+    #[test]
+    fn test_resolve_values_and_references() {
+        let values = HashMap::from([
+            ("sweepTimePerPoint", vec!["2e-3".to_string()]),
+            ("sourceFunction", vec!["current".to_string()]),
+            (
+                "smu@.source.rangei",
+                vec!["1e-3".to_string(), "1e-3".to_string()],
+            ),
+        ]);
+        let lookup = |id: &str| values.get(id).cloned().unwrap_or_default();
+        let resolved = resolve(&parse(VARIABLES), lookup).unwrap();
+        assert_eq!(
+            value(&resolved, "pulseWidth")
+                .constraint
+                .as_ref()
+                .unwrap()
+                .max,
+            2e-3
+        );
+        assert_eq!(value(&resolved, "sourceRange").value, "1e-3");
+
+        // any of the channels is in autorange
+        let values = HashMap::from([(
+            "smu@.source.autorange",
+            vec!["0".to_string(), "1".to_string()],
+        )]);
+        let lookup = |id: &str| values.get(id).cloned().unwrap_or_default();
+        let resolved = resolve(&parse(VARIABLES), lookup).unwrap();
+        assert_eq!(value(&resolved, "sourceRange").value, "AUTO");
+    }
+
+    #[test]
+    fn test_resolve_errors() {
+        let variables = parse(
+            r#"<variables>
+                <variable id="a"><depends ref="b"></depends></variable>
+                <variable id="b"><reference id="c"/></variable>
+                <variable id="c"><constraints><max ref="a"/></constraints></variable>
+            </variables>"#,
+        );
+        let error = resolve(&variables, |_| Vec::new()).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Template variables depend on each other: a -> b -> c -> a"
+        );
+
+        let variables = parse(
+            r#"<variables>
+                <variable id="a"><constraints><min ref="mode"/></constraints></variable>
+            </variables>"#,
+        );
+        let error = resolve(&variables, |_| vec!["fast".to_string()]).unwrap_err();
+        assert!(matches!(
+            error,
+            XMLHandlerError::InvalidBound { ref value, .. } if value == "fast"
+        ));
+    }
+}
@@ -7,21 +7,27 @@ pub struct Reference {
     /// A unique identifier for the reference.
     pub id: String,
     /// The default value of the reference.
-    default: String,
+    pub default: String,
     /// Indicates the useall attribute of the reference.
-    useall: String,
+    pub useall: String,
     /// The value of the reference.
-    value: String,
+    pub value: String,
 }
 
 /// Represents a constraint with minimum and maximum values.
-/// This corresponds to constarints tag in the XML data.
-#[derive(Debug, Clone)]
+/// This corresponds to constarints tag in the XML data. A bound that is not defined is NaN.
+///
+/// A bound can refer to the value of another variable instead, e.g. `<max ref="sweepTimePerPoint"/>`.
+#[derive(Debug, Clone, PartialEq)]
 pub struct Constraint {
     /// The minimum value of the constraint.
     pub min: f64,
     /// The minimum value of the constraint.
     pub max: f64,
+    /// The variable whose value is the minimum, if any.
+    pub min_ref: Option<String>,
+    /// The variable whose value is the maximum, if any.
+    pub max_ref: Option<String>,
 }
 
 impl Reference {
@@ -78,13 +84,17 @@ impl Reference {
         loop {
             match reader.read_event_into(&mut buf) {
                 Err(e) => {
-                    eprintln!("Error at position {}: {:?}", reader.error_position(), e);
+                    tracing::error!(
+                        position = reader.error_position(),
+                        "XML parse error: {:?}",
+                        e
+                    );
                     return Err(XMLHandlerError::ParseError { source: e });
                 }
                 Ok(Event::Text(e)) => match e.unescape() {
                     Ok(text) => default = text.to_string(),
                     Err(e) => {
-                        eprintln!("Error reading default reference value: {:?}", e);
+                        tracing::error!("Error reading default reference value: {:?}", e);
                         return Err(XMLHandlerError::ParseError { source: e });
                     }
                 },
@@ -101,35 +111,66 @@ impl Reference {
 
 impl Constraint {
     pub fn new(min: f64, max: f64) -> Self {
-        Constraint { min, max }
+        Constraint {
+            min,
+            max,
+            min_ref: None,
+            max_ref: None,
+        }
+    }
+
+    /// Returns the ids of the variables the bounds refer to.
+    pub fn refs(&self) -> impl Iterator<Item = &str> {
+        self.min_ref.iter().chain(&self.max_ref).map(String::as_str)
     }
 
+    /// Parses a constraints element of the template `file`.
     pub fn parse_constraint<R: std::io::BufRead>(
         reader: &mut Reader<R>,
         attributes: quick_xml::events::attributes::Attributes,
+        file: &str,
     ) -> Result<Constraint> {
         let mut buf: Vec<u8> = Vec::new();
 
-        let mut min: f64 = 0.0;
-        let mut max: f64 = 0.0; // Initialize max variable
+        let mut min = f64::NAN;
+        let mut max = f64::NAN;
+        let mut min_ref = None;
+        let mut max_ref = None;
 
         loop {
             match reader.read_event_into(&mut buf) {
                 Err(e) => {
-                    eprintln!("Error at position {}: {:?}", reader.error_position(), e);
+                    tracing::error!(
+                        position = reader.error_position(),
+                        "XML parse error: {:?}",
+                        e
+                    );
                     return Err(XMLHandlerError::ParseError { source: e });
                 }
+                Ok(Event::Empty(e)) if e.name().as_ref() == b"min" => {
+                    min_ref = parse_ref(e.attributes())?;
+                }
+                Ok(Event::Empty(e)) if e.name().as_ref() == b"max" => {
+                    max_ref = parse_ref(e.attributes())?;
+                }
                 Ok(Event::Start(e)) if e.name().as_ref() == b"min" => {
+                    min_ref = parse_ref(e.attributes())?;
                     // Read text content of <min> tag
                     match reader.read_event_into(&mut buf) {
                         Err(e) => {
-                            eprintln!("Error at position {}: {:?}", reader.error_position(), e);
+                            tracing::error!(
+                                position = reader.error_position(),
+                                "XML parse error: {:?}",
+                                e
+                            );
                             return Err(XMLHandlerError::ParseError { source: e });
                         }
                         Ok(Event::Text(e)) => match e.unescape() {
-                            Ok(text) => min = text.parse().unwrap(),
+                            Ok(text) => {
+                                min = parse_limit(&text, "<min>", file, reader.buffer_position())?
+                            }
                             Err(e) => {
-                                eprintln!("Error reading min constraint value: {:?}", e);
+                                tracing::error!("Error reading min constraint value: {:?}", e);
                                 return Err(XMLHandlerError::ParseError { source: e });
                             }
                         },
@@ -137,16 +178,23 @@ impl Constraint {
                     }
                 }
                 Ok(Event::Start(e)) if e.name().as_ref() == b"max" => {
+                    max_ref = parse_ref(e.attributes())?;
                     // Read text content of <max> tag
                     match reader.read_event_into(&mut buf) {
                         Err(e) => {
-                            eprintln!("Error at position {}: {:?}", reader.error_position(), e);
+                            tracing::error!(
+                                position = reader.error_position(),
+                                "XML parse error: {:?}",
+                                e
+                            );
                             return Err(XMLHandlerError::ParseError { source: e });
                         }
                         Ok(Event::Text(e)) => match e.unescape() {
-                            Ok(text) => max = text.parse().unwrap(),
+                            Ok(text) => {
+                                max = parse_limit(&text, "<max>", file, reader.buffer_position())?
+                            }
                             Err(e) => {
-                                eprintln!("Error reading max constraint value: {:?}", e);
+                                tracing::error!("Error reading max constraint value: {:?}", e);
                                 return Err(XMLHandlerError::ParseError { source: e });
                             }
                         },
@@ -161,6 +209,34 @@ impl Constraint {
             }
         }
 
-        Ok(Constraint::new(min, max))
+        Ok(Constraint {
+            min,
+            max,
+            min_ref,
+            max_ref,
+        })
     }
 }
+
+fn parse_ref(attributes: quick_xml::events::attributes::Attributes) -> Result<Option<String>> {
+    for attr in attributes {
+        let attr = attr?;
+        if let QName(b"ref") = attr.key {
+            return Ok(Some(
+                String::from_utf8_lossy(attr.value.as_ref()).to_string(),
+            ));
+        }
+    }
+    Ok(None)
+}
+
+fn parse_limit(text: &str, element: &str, file: &str, position: u64) -> Result<f64> {
+    text.trim()
+        .parse()
+        .map_err(|_| XMLHandlerError::NotANumber {
+            file: file.to_string(),
+            element: element.to_string(),
+            position,
+            value: text.to_string(),
+        })
+}